@@ -0,0 +1,99 @@
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{env, fs, process};
+
+use anyhow::Context;
+use wasi_common::CacheSetting;
+use wasmtime::Config;
+
+use crate::AnyResult;
+
+// the sequence of the generated cache configure files.
+static CACHE_CONFIG_SEQ: AtomicUsize = AtomicUsize::new(0);
+
+/// apply the compilation cache setting to the engine configure.
+pub(crate) fn cache_config(conf: &mut Config, setting: &CacheSetting) -> AnyResult<()> {
+    match setting {
+        CacheSetting::Default => {
+            conf.cache_config_load_default()
+                .context("failed to load the default cache configure")?;
+        }
+        CacheSetting::Disabled => {
+            conf.disable_cache();
+        }
+        CacheSetting::ConfigFile(path) => {
+            conf.cache_config_load(path)
+                .with_context(|| format!("failed to load the cache configure file: {path}"))?;
+        }
+        CacheSetting::Directory { path, max_size } => {
+            let dir = std::path::absolute(path)
+                .with_context(|| format!("invalid cache directory: {path}"))?;
+            // the wasmtime cache only can be configured by the configure file.
+            let seq = CACHE_CONFIG_SEQ.fetch_add(1, Ordering::Relaxed);
+            let file = env::temp_dir().join(format!("bls-cache-{}-{seq}.toml", process::id()));
+            fs::write(&file, directory_cache_toml(&dir, *max_size))
+                .context("failed to write the cache configure file")?;
+            let rs = conf.cache_config_load(&file).map(|_| ());
+            let _ = fs::remove_file(&file);
+            rs.with_context(|| format!("failed to use the cache directory: {path}"))?;
+        }
+    }
+    Ok(())
+}
+
+fn directory_cache_toml(dir: &Path, max_size: Option<u64>) -> String {
+    let mut toml = String::from("[cache]\nenabled = true\n");
+    toml.push_str(&format!(
+        "directory = {}\n",
+        toml_string(&dir.to_string_lossy())
+    ));
+    if let Some(size) = max_size {
+        toml.push_str(&format!("files-total-size-soft-limit = \"{size}\"\n"));
+    }
+    toml
+}
+
+/// the toml basic string.
+fn toml_string(s: &str) -> String {
+    let mut rs = String::with_capacity(s.len() + 2);
+    rs.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => rs.push_str("\\\""),
+            '\\' => rs.push_str("\\\\"),
+            c if c.is_control() => rs.push_str(&format!("\\u{:04X}", c as u32)),
+            c => rs.push(c),
+        }
+    }
+    rs.push('"');
+    rs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_directory_cache_toml() {
+        let toml = directory_cache_toml(Path::new("/tmp/bls \"cache\""), Some(1024));
+        assert_eq!(
+            toml,
+            "[cache]\nenabled = true\ndirectory = \"/tmp/bls \\\"cache\\\"\"\nfiles-total-size-soft-limit = \"1024\"\n"
+        );
+    }
+
+    #[test]
+    fn test_cache_config() {
+        let mut conf = Config::new();
+        assert!(cache_config(&mut conf, &CacheSetting::Disabled).is_ok());
+        let setting = CacheSetting::ConfigFile("/not/exists/cache.toml".into());
+        assert!(cache_config(&mut conf, &setting).is_err());
+        let temp_dir = TempDir::new("blockless_cache").unwrap();
+        let setting = CacheSetting::Directory {
+            path: temp_dir.path().to_str().unwrap().into(),
+            max_size: Some(1024 * 1024),
+        };
+        assert!(cache_config(&mut conf, &setting).is_ok());
+    }
+}
@@ -0,0 +1,41 @@
+use bytes::Bytes;
+use wasi_common::OutputPipe;
+use wasmtime_wasi::{OutputStream, Pollable, StdoutStream, StreamResult};
+
+// the write permit of the capture stream, the output exceed the max size is dropped by the pipe.
+const CAPTURE_WRITE_PERMIT: usize = 64 * 1024;
+
+/// The preview2 output stream of the capture pipe.
+#[derive(Clone)]
+pub(crate) struct CaptureStream(pub(crate) OutputPipe);
+
+impl StdoutStream for CaptureStream {
+    fn stream(&self) -> Box<dyn OutputStream> {
+        Box::new(self.clone())
+    }
+
+    fn isatty(&self) -> bool {
+        false
+    }
+}
+
+#[wasmtime_wasi::async_trait]
+impl OutputStream for CaptureStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        self.0.write(&bytes);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(CAPTURE_WRITE_PERMIT)
+    }
+}
+
+#[wasmtime_wasi::async_trait]
+impl Pollable for CaptureStream {
+    async fn ready(&mut self) {}
+}
@@ -0,0 +1,67 @@
+use anyhow::{Context, bail};
+use wasi_common::{BlocklessConfig, CacheSetting};
+use wasmtime::{CodeBuilder, CodeHint, Engine, Precompiled};
+
+use crate::{AnyResult, BlocklessConfig2Preview1WasiBuilder};
+
+/// The precompiled artifact of the wasm module or component.
+pub struct PrecompiledArtifact {
+    pub kind: Precompiled,
+    pub bytes: Vec<u8>,
+}
+
+/// precompile the wasm(binary or text) ahead of time, the artifact can be run by the engine
+/// with the same engine options(`opts`, `feature_thread`, `debug_info`, fuel and time limits).
+/// the `target` is the target triple for cross compiling, the default is the host.
+pub fn precompile(
+    b_conf: &BlocklessConfig,
+    target: Option<&str>,
+    bytes: &[u8],
+) -> AnyResult<PrecompiledArtifact> {
+    let mut b_conf = b_conf.clone();
+    // the compilation cache never used by the precompiling.
+    b_conf.set_cache(CacheSetting::Disabled);
+    let mut conf = b_conf.preview1_engine_config()?;
+    if let Some(target) = target {
+        conf.target(target)
+            .with_context(|| format!("unsupported compilation target: {target}"))?;
+    }
+    let engine = Engine::new(&conf)?;
+    if engine.detect_precompiled(bytes).is_some() {
+        bail!("the input is already precompiled");
+    }
+    let mut code = CodeBuilder::new(&engine);
+    code.wasm_binary_or_text(bytes, None)?;
+    let artifact = match code.hint() {
+        Some(CodeHint::Component) => PrecompiledArtifact {
+            kind: Precompiled::Component,
+            bytes: engine.precompile_component(bytes)?,
+        },
+        Some(CodeHint::Module) | None => PrecompiledArtifact {
+            kind: Precompiled::Module,
+            bytes: engine.precompile_module(bytes)?,
+        },
+    };
+    Ok(artifact)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_precompile() {
+        let conf = BlocklessConfig::new("test.wasm");
+        let code = br#"(module (func (export "_start")))"#;
+        let artifact = precompile(&conf, None, code).unwrap();
+        assert!(matches!(artifact.kind, Precompiled::Module));
+        let engine = Engine::default();
+        assert!(matches!(
+            engine.detect_precompiled(&artifact.bytes),
+            Some(Precompiled::Module)
+        ));
+        // the precompiled artifact can't be precompiled again.
+        assert!(precompile(&conf, None, &artifact.bytes).is_err());
+        assert!(precompile(&conf, Some("not-a-target"), code).is_err());
+    }
+}
@@ -1,10 +1,23 @@
 use std::sync::{Arc, Mutex};
 
-use wasi_common::PermissionsConfig;
-use wasmtime::StoreLimits;
+use blockless_drivers::wit::BlocklessWitCtx;
+use wasi_common::{
+    AuditTrace, BlsRuntimePermissionsContainer, HostCallAudit, OutputPipe, PermissionsConfig,
+};
 use wasmtime_wasi::IoView;
 use wasmtime_wasi::preview1::WasiP1Ctx;
-use wasmtime_wasi_threads::WasiThreadsCtx;
+use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::types::{
+    HostFutureIncomingResponse, OutgoingRequestConfig, default_send_request,
+};
+use wasmtime_wasi_http::{HttpResult, WasiHttpCtx, WasiHttpView};
+
+use crate::deadline::Deadline;
+use crate::limits::BlocklessLimiter;
+use crate::modules::InstanceCtx;
+use crate::sandbox::ModuleSandboxCtx;
+use crate::threads::BlocklessThreadsCtx;
 
 #[derive(Clone, Default)]
 pub(crate) struct BlocklessContext {
@@ -12,13 +25,44 @@ pub(crate) struct BlocklessContext {
 
     pub(crate) preview2_ctx: Option<Arc<Mutex<WasiP1Ctx>>>,
 
-    pub(crate) wasi_threads: Option<Arc<WasiThreadsCtx<BlocklessContext>>>,
+    pub(crate) blockless_wit: Option<Arc<Mutex<BlocklessWitCtx>>>,
+
+    pub(crate) wasi_http: Option<Arc<Mutex<WasiHttpCtx>>>,
+
+    /// the permissions of the outgoing requests of `wasi:http`, all requests are
+    /// denied if it's not set.
+    pub(crate) http_perms: Option<BlsRuntimePermissionsContainer>,
+
+    pub(crate) wasi_threads: Option<Arc<BlocklessThreadsCtx>>,
 
     pub(crate) wasi_nn_wit: Option<Arc<wasmtime_wasi_nn::wit::WasiNnCtx>>,
 
     pub(crate) wasi_nn_witx: Option<Arc<wasmtime_wasi_nn::witx::WasiNnCtx>>,
 
-    pub(crate) store_limits: StoreLimits,
+    pub(crate) limiter: BlocklessLimiter,
+
+    pub(crate) deadline: Option<Deadline>,
+
+    /// the linked modules and the registered mcalls of the run.
+    pub(crate) instance_ctx: InstanceCtx,
+
+    /// the contexts of the sandboxed library modules.
+    pub(crate) sandboxes: Vec<ModuleSandboxCtx>,
+
+    /// the sandboxes entered, the last is the running module.
+    pub(crate) sandbox_stack: Vec<usize>,
+
+    /// the name of the entry module.
+    pub(crate) entry_module: Option<Arc<str>>,
+
+    /// the library modules entered, the last is the running module,
+    /// the entry is running if it's empty.
+    pub(crate) module_stack: Vec<Arc<str>>,
+
+    /// the pipes of the stdout and stderr captured in memory.
+    pub(crate) stdout_capture: Option<OutputPipe>,
+
+    pub(crate) stderr_capture: Option<OutputPipe>,
 }
 
 impl BlocklessContext {
@@ -30,6 +74,61 @@ impl BlocklessContext {
             .unwrap()
     }
 
+    /// the wasi context of the running module, the sandboxed module has its own context.
+    /// the host calls of the context are audited with the running module.
+    pub(crate) fn running_preview1_ctx(&mut self) -> &mut wasi_common::WasiCtx {
+        let module = self.running_module();
+        let ctx = match self.sandbox_stack.last() {
+            Some(i) => &mut self.sandboxes[*i].preview1_ctx,
+            None => self.preview1_ctx.as_mut().unwrap(),
+        };
+        ctx.set_audit_module(module);
+        ctx
+    }
+
+    /// the name of the running module, the library module entered or the entry.
+    pub(crate) fn running_module(&self) -> Option<Arc<str>> {
+        self.module_stack
+            .last()
+            .or(self.entry_module.as_ref())
+            .cloned()
+    }
+
+    /// the limiter of the running module, the memories of the sandboxed module
+    /// are limited by its own limiter.
+    pub(crate) fn running_limiter(&mut self) -> &mut BlocklessLimiter {
+        match self.sandbox_stack.last() {
+            Some(i) => &mut self.sandboxes[*i].limiter,
+            None => &mut self.limiter,
+        }
+    }
+
+    /// the index of the sandbox of the library module.
+    pub(crate) fn sandbox_index(&self, name: &str) -> Option<usize> {
+        self.sandboxes.iter().position(|s| s.name == name)
+    }
+
+    pub(crate) fn blockless_wit_ctx(&mut self) -> &mut BlocklessWitCtx {
+        let ctx = self.blockless_wit.as_mut().unwrap();
+        Arc::get_mut(ctx)
+            .expect("blockless extensions was not compatiable threads")
+            .get_mut()
+            .unwrap()
+    }
+
+    pub(crate) fn wasi_http_ctx(&mut self) -> &mut WasiHttpCtx {
+        let ctx = self.wasi_http.as_mut().unwrap();
+        Arc::get_mut(ctx)
+            .expect("wasmtime_wasi_http was not compatiable threads")
+            .get_mut()
+            .unwrap()
+    }
+
+    /// the audit trace of the core modules.
+    pub(crate) fn audit_trace(&self) -> Option<Arc<AuditTrace>> {
+        self.preview1_ctx.as_ref().and_then(|ctx| ctx.audit_trace())
+    }
+
     pub(crate) fn set_permisions(&mut self, config: &PermissionsConfig) {
         if let Some(ctx) = self.preview1_ctx.as_mut() {
             ctx.set_permissions_config(config).unwrap();
@@ -48,3 +147,34 @@ impl wasmtime_wasi::WasiView for BlocklessContext {
         self.preview2_ctx().ctx()
     }
 }
+
+impl WasiHttpView for BlocklessContext {
+    fn ctx(&mut self) -> &mut WasiHttpCtx {
+        self.wasi_http_ctx()
+    }
+
+    /// the outgoing request is checked by the same permissions as the http extension,
+    /// the requests are served on the async runtime, so only the granted hosts are allowed
+    /// without the prompt.
+    fn send_request(
+        &mut self,
+        request: hyper::Request<HyperOutgoingBody>,
+        config: OutgoingRequestConfig,
+    ) -> HttpResult<HostFutureIncomingResponse> {
+        let uri = request.uri();
+        let Some(host) = uri.host() else {
+            return Err(ErrorCode::HttpRequestDenied.into());
+        };
+        let port = uri
+            .port_u16()
+            .unwrap_or(if config.use_tls { 443 } else { 80 });
+        let granted = self
+            .http_perms
+            .as_ref()
+            .is_some_and(|perms| perms.is_net_granted(host, Some(port)));
+        if !granted {
+            return Err(ErrorCode::HttpRequestDenied.into());
+        }
+        Ok(default_send_request(request, config))
+    }
+}
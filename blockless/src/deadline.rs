@@ -0,0 +1,90 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use wasmtime::{Engine, Store, Trap, UpdateDeadline};
+
+/// the interval of the engine epoch ticks.
+pub(crate) const EPOCH_TICK: Duration = Duration::from_millis(10);
+
+// the epoch deadline of the store without deadline, it never be reached.
+const NO_DEADLINE_TICKS: u64 = u64::MAX >> 1;
+
+/// The ticker increment the engine epoch every `EPOCH_TICK`,
+/// the ticker thread will be stopped when the ticker is dropped.
+pub(crate) struct EpochTicker {
+    _stop: mpsc::Sender<()>,
+}
+
+impl EpochTicker {
+    pub(crate) fn start(engine: &Engine) -> Self {
+        let (stop, rx) = mpsc::channel::<()>();
+        let engine = engine.clone();
+        thread::Builder::new()
+            .name("blockless-epoch-ticker".into())
+            .spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = rx.recv_timeout(EPOCH_TICK) {
+                    engine.increment_epoch();
+                }
+            })
+            .expect("spawn epoch ticker thread fail.");
+        Self { _stop: stop }
+    }
+}
+
+/// The wall-clock deadline of the run, every store(include the stores of threads)
+/// of the run share the same deadline, so the run can be stopped before the
+/// deadline by `expire`, e.g. when a thread of the run failed.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Deadline {
+    // `None` if the run has no time limit.
+    at: Option<Instant>,
+    expired: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    /// the deadline after `millis` milliseconds from now.
+    pub(crate) fn after_millis(millis: u64) -> Self {
+        Deadline {
+            at: Some(Instant::now() + Duration::from_millis(millis)),
+            expired: Default::default(),
+        }
+    }
+
+    /// stop the run, all stores of the run trap at the next epoch tick.
+    pub(crate) fn expire(&self) {
+        self.expired.store(true, Ordering::SeqCst);
+    }
+
+    #[inline]
+    pub(crate) fn is_expired(&self) -> bool {
+        self.expired.load(Ordering::SeqCst) || self.is_timeout()
+    }
+
+    /// the wall-clock time limit of the run is reached.
+    #[inline]
+    pub(crate) fn is_timeout(&self) -> bool {
+        self.at.is_some_and(|at| Instant::now() >= at)
+    }
+}
+
+/// set the epoch deadline to the store, the store will trap with `Trap::Interrupt`
+/// when the deadline reached or expired. When the engine enable the epoch interruption,
+/// every store must be set the deadline, otherwise the store will trap immediately.
+pub(crate) fn set_store_deadline<T: 'static>(store: &mut Store<T>, deadline: Option<Deadline>) {
+    let Some(deadline) = deadline else {
+        store.set_epoch_deadline(NO_DEADLINE_TICKS);
+        store.epoch_deadline_trap();
+        return;
+    };
+    // the deadline can be expired by the other threads, so it's checked at every tick.
+    store.epoch_deadline_callback(move |_| {
+        if deadline.is_expired() {
+            return Err(Trap::Interrupt.into());
+        }
+        Ok(UpdateDeadline::Continue(1))
+    });
+    store.set_epoch_deadline(1);
+}
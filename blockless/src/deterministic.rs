@@ -0,0 +1,301 @@
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use anyhow::bail;
+use cap_rand::SeedableRng;
+use cap_rand::rngs::StdRng;
+use wasi_common::{DeterministicConfig, WasiClocks, WasiMonotonicClock, WasiSystemClock};
+use wasmtime::component::types::ComponentItem;
+use wasmtime::component::{self, Component, ResourceType};
+use wasmtime::{Engine, ExternType, Linker, Module};
+use wasmtime_wasi::{HostMonotonicClock, HostWallClock};
+
+use crate::AnyResult;
+
+/// The nondeterministic extensions, they are denied in the deterministic mode unless
+/// they are allowed by the configure. The `memory` extension only reads the stdin
+/// and envs of the configure, so it's always allowed.
+pub(crate) const NONDETERMINISTIC_EXTENSIONS: [&str; 7] =
+    ["drivers", "http", "ipfs", "s3", "cgi", "socket", "llm"];
+
+/// the prefix of the extension modules of the core modules, e.g. `blockless_http`.
+const CORE_EXTENSION_PREFIX: &str = "blockless_";
+
+/// the package of the extension interfaces of the components, e.g. `blockless:extensions/http`.
+const COMPONENT_EXTENSION_PREFIX: &str = "blockless:extensions/";
+
+/// every read of the virtual clocks advances the time by the step,
+/// so the guest sees the time is moving and the time only depends on the reads.
+const CLOCK_STEP: Duration = Duration::from_millis(1);
+
+/// the base of the virtual monotonic clock, the guest only sees the offset from the base.
+static BASE_INSTANT: OnceLock<Instant> = OnceLock::new();
+
+/// check the extension can be called in the deterministic mode.
+pub(crate) fn is_extension_allowed(conf: Option<&DeterministicConfig>, name: &str) -> bool {
+    match conf {
+        Some(conf) => {
+            !NONDETERMINISTIC_EXTENSIONS.contains(&name) || conf.is_extension_allowed(name)
+        }
+        None => true,
+    }
+}
+
+fn denied_error(name: &str) -> anyhow::Error {
+    anyhow::anyhow!("the extension `{name}` is not allowed in the deterministic mode.")
+}
+
+/// The virtual clock of the deterministic mode, the wall clock and monotonic clock
+/// share the elapsed time.
+#[derive(Clone)]
+pub(crate) struct VirtualClock {
+    // the start time of the wall clock, in nanoseconds since the unix epoch.
+    start: u64,
+    elapsed: Arc<AtomicU64>,
+}
+
+impl VirtualClock {
+    pub(crate) fn new(conf: &DeterministicConfig) -> Self {
+        Self {
+            start: conf.start_time.saturating_mul(1_000_000),
+            elapsed: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// advance the clock and return the elapsed nanoseconds.
+    fn tick(&self) -> u64 {
+        let step = CLOCK_STEP.as_nanos() as u64;
+        self.elapsed.fetch_add(step, Ordering::SeqCst) + step
+    }
+
+    /// the clocks of the preview1 context.
+    pub(crate) fn wasi_clocks(&self) -> WasiClocks {
+        WasiClocks::new()
+            .with_system(self.clone())
+            .with_monotonic(self.clone())
+    }
+}
+
+impl WasiSystemClock for VirtualClock {
+    fn resolution(&self) -> Duration {
+        CLOCK_STEP
+    }
+
+    fn now(&self, _precision: Duration) -> cap_std::time::SystemTime {
+        let now = Duration::from_nanos(self.start.saturating_add(self.tick()));
+        cap_std::time::SystemTime::from_std(UNIX_EPOCH + now)
+    }
+}
+
+impl WasiMonotonicClock for VirtualClock {
+    fn resolution(&self) -> Duration {
+        CLOCK_STEP
+    }
+
+    fn now(&self, _precision: Duration) -> cap_std::time::Instant {
+        let base = BASE_INSTANT.get_or_init(Instant::now);
+        cap_std::time::Instant::from_std(*base + Duration::from_nanos(self.tick()))
+    }
+}
+
+impl HostWallClock for VirtualClock {
+    fn resolution(&self) -> Duration {
+        CLOCK_STEP
+    }
+
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.start.saturating_add(self.tick()))
+    }
+}
+
+impl HostMonotonicClock for VirtualClock {
+    fn resolution(&self) -> u64 {
+        CLOCK_STEP.as_nanos() as u64
+    }
+
+    fn now(&self) -> u64 {
+        self.tick()
+    }
+}
+
+/// the seeded random generator of the deterministic mode.
+pub(crate) fn seeded_random(conf: &DeterministicConfig) -> StdRng {
+    StdRng::seed_from_u64(conf.seed)
+}
+
+/// define the imports of the denied extensions as the traps,
+/// the denied extensions must not be added to the linker.
+pub(crate) fn deny_core_extensions<T: 'static>(
+    linker: &mut Linker<T>,
+    conf: &DeterministicConfig,
+    modules: &[&Module],
+) -> AnyResult<()> {
+    let mut defined = HashSet::new();
+    for module in modules {
+        for import in module.imports() {
+            let Some(name) = import.module().strip_prefix(CORE_EXTENSION_PREFIX) else {
+                continue;
+            };
+            if is_extension_allowed(Some(conf), name) {
+                continue;
+            }
+            let ExternType::Func(ty) = import.ty() else {
+                continue;
+            };
+            if !defined.insert((import.module(), import.name())) {
+                continue;
+            }
+            let func = format!("{}.{}", import.module(), import.name());
+            linker.func_new(import.module(), import.name(), ty, move |_, _, _| {
+                Err(denied_error(&func))
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// the extensions imported by the core modules, e.g. `http`.
+pub(crate) fn core_extension_imports(modules: &[&Module]) -> Vec<String> {
+    let names = modules
+        .iter()
+        .flat_map(|m| m.imports())
+        .filter_map(|import| {
+            import
+                .module()
+                .strip_prefix(CORE_EXTENSION_PREFIX)
+                .map(String::from)
+        });
+    names.collect::<HashSet<_>>().into_iter().collect()
+}
+
+/// the extensions imported by the component, e.g. `http`.
+pub(crate) fn component_extension_imports(engine: &Engine, component: &Component) -> Vec<String> {
+    let names = component
+        .component_type()
+        .imports(engine)
+        .filter_map(|(import, _)| {
+            let interface = import.strip_prefix(COMPONENT_EXTENSION_PREFIX)?;
+            Some(interface.split('@').next().unwrap_or(interface).to_string())
+        });
+    names.collect::<HashSet<_>>().into_iter().collect()
+}
+
+/// check the extensions linked by the prepare can be called in the deterministic mode
+/// of the run, the extensions denied by the prepare are the traps already.
+pub(crate) fn check_run_extensions(
+    prepared: Option<&DeterministicConfig>,
+    conf: &DeterministicConfig,
+    imports: &[String],
+) -> AnyResult<()> {
+    for name in imports {
+        if is_extension_allowed(prepared, name) && !is_extension_allowed(Some(conf), name) {
+            return Err(denied_error(name));
+        }
+    }
+    Ok(())
+}
+
+/// the resource of the denied extensions, it never be created.
+struct DeniedResource;
+
+/// define the imports of the denied extensions of the component as the traps,
+/// the denied extensions must not be added to the linker.
+pub(crate) fn deny_component_extensions<T: 'static>(
+    linker: &mut component::Linker<T>,
+    engine: &Engine,
+    conf: &DeterministicConfig,
+    component: &Component,
+) -> AnyResult<()> {
+    for (import, item) in component.component_type().imports(engine) {
+        let Some(interface) = import.strip_prefix(COMPONENT_EXTENSION_PREFIX) else {
+            continue;
+        };
+        // the version of the interface, e.g. `http@0.1.0`.
+        let name = interface.split('@').next().unwrap_or(interface);
+        if is_extension_allowed(Some(conf), name) {
+            continue;
+        }
+        let ComponentItem::ComponentInstance(instance_ty) = item else {
+            bail!("the import `{import}` is not an instance.");
+        };
+        let mut instance = linker.instance(import)?;
+        for (export, item) in instance_ty.exports(engine) {
+            match item {
+                ComponentItem::ComponentFunc(_) => {
+                    let func = format!("{name}#{export}");
+                    instance.func_new(export, move |_, _, _| Err(denied_error(&func)))?;
+                }
+                ComponentItem::Resource(_) => {
+                    instance.resource(export, ResourceType::host::<DeniedResource>(), |_, _| {
+                        Ok(())
+                    })?;
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_extension_allowed() {
+        let conf = DeterministicConfig {
+            allowed_extensions: vec!["http".into()],
+            ..Default::default()
+        };
+        assert!(is_extension_allowed(None, "ipfs"));
+        assert!(is_extension_allowed(Some(&conf), "http"));
+        assert!(is_extension_allowed(Some(&conf), "memory"));
+        assert!(!is_extension_allowed(Some(&conf), "ipfs"));
+        assert!(!is_extension_allowed(Some(&conf), "drivers"));
+    }
+
+    #[test]
+    fn test_check_run_extensions() {
+        let conf = DeterministicConfig::default();
+        let imports = vec!["http".to_string(), "memory".to_string()];
+        assert!(check_run_extensions(None, &conf, &imports).is_err());
+        // the extensions denied by the prepare are the traps.
+        assert!(check_run_extensions(Some(&conf), &conf, &imports).is_ok());
+        let allowed = DeterministicConfig {
+            allowed_extensions: vec!["http".into()],
+            ..Default::default()
+        };
+        assert!(check_run_extensions(None, &allowed, &imports).is_ok());
+        assert!(check_run_extensions(Some(&allowed), &conf, &imports).is_err());
+    }
+
+    #[test]
+    fn test_virtual_clock() {
+        let conf = DeterministicConfig {
+            start_time: 1_000,
+            ..Default::default()
+        };
+        let clock = VirtualClock::new(&conf);
+        assert_eq!(HostWallClock::now(&clock), Duration::from_millis(1_001));
+        assert_eq!(HostMonotonicClock::now(&clock), 2_000_000);
+        // the clones share the elapsed time.
+        let other = clock.clone();
+        assert_eq!(HostMonotonicClock::now(&other), 3_000_000);
+        let clock = VirtualClock::new(&conf);
+        assert_eq!(HostWallClock::now(&clock), Duration::from_millis(1_001));
+    }
+
+    #[test]
+    fn test_seeded_random() {
+        use cap_rand::RngCore;
+        let conf = DeterministicConfig {
+            seed: 42,
+            ..Default::default()
+        };
+        let a = seeded_random(&conf).next_u64();
+        let b = seeded_random(&conf).next_u64();
+        assert_eq!(a, b);
+    }
+}
@@ -1,7 +1,7 @@
 use std::fmt::Display;
 
 #[non_exhaustive]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum McallError {
     None,
     MemoryNotFound,
@@ -10,6 +10,7 @@ pub enum McallError {
     MCallMemoryNotFound,
     MCallError,
     Fail,
+    Encoding,
 }
 
 impl From<McallError> for u32 {
@@ -22,6 +23,7 @@ impl From<McallError> for u32 {
             McallError::MCallError => 4,
             McallError::Fail => 5,
             McallError::MCallMemoryNotFound => 6,
+            McallError::Encoding => 7,
         }
     }
 }
@@ -38,6 +40,7 @@ impl Display for McallError {
             McallError::MCallError => write!(f, "MCall error"),
             McallError::Fail => write!(f, "Call faill"),
             McallError::MCallMemoryNotFound => write!(f, "mcall memory not found"),
+            McallError::Encoding => write!(f, "mcall encoding mismatch"),
         }
     }
 }
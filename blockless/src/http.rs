@@ -0,0 +1,376 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, anyhow, bail};
+use bytes::Bytes;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Incoming;
+use log::{debug, error, info};
+use tokio::net::TcpListener;
+use wasi_common::{BlocklessConfig, BlocklessConfigVersion};
+use wasmtime::component::Linker;
+use wasmtime_wasi_http::bindings::ProxyPre;
+use wasmtime_wasi_http::bindings::http::types::Scheme;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::io::TokioIo;
+use wasmtime_wasi_http::{WasiHttpCtx, WasiHttpView};
+
+use crate::context::BlocklessContext;
+use crate::{AnyResult, BlocklessEngine, BlocklessRunner, BlsLinker, BlsRunTarget};
+
+/// The HTTP handler of the component exporting `wasi:http/incoming-handler`,
+/// every request is handled by a new instance of the pre-instantiated component
+/// with the fuel, memory and time limits of the configure.
+#[derive(Clone)]
+pub struct HttpHandler {
+    engine: BlocklessEngine,
+    conf: BlocklessConfig,
+    pre: ProxyPre<BlocklessContext>,
+}
+
+impl BlocklessEngine {
+    /// compile and pre-instantiate the component of the configure to handle the HTTP requests.
+    pub fn prepare_http(&self, b_conf: &BlocklessConfig) -> AnyResult<HttpHandler> {
+        if b_conf.deterministic_ref().is_some() {
+            bail!("the HTTP handler is not supported in the deterministic mode.");
+        }
+        if matches!(b_conf.version(), BlocklessConfigVersion::Version1) {
+            bail!("the HTTP handler only supports the single component.");
+        }
+        BlocklessRunner::init_drivers(b_conf);
+        let BlsRunTarget::Component(component) = self.load_module(b_conf.entry_ref())? else {
+            bail!("the HTTP handler must be a component exporting `wasi:http/incoming-handler`.");
+        };
+        let mut linker = BlsLinker::Component(Linker::new(self.engine()));
+        if b_conf.nn {
+            BlocklessRunner::nn_linker_setup(&mut linker)?;
+        }
+        let BlsLinker::Component(mut linker) = linker else {
+            unreachable!("the linker of the component");
+        };
+        wasmtime_wasi_http::add_only_http_to_linker_async(&mut linker)?;
+        self.component_linker_setup(&mut linker, b_conf, &component)?;
+        let pre = ProxyPre::new(linker.instantiate_pre(&component)?)
+            .context("the component doesn't export `wasi:http/incoming-handler`")?;
+        Ok(HttpHandler {
+            engine: self.clone(),
+            conf: b_conf.clone(),
+            pre,
+        })
+    }
+}
+
+impl HttpHandler {
+    /// handle the request by a new instance, the time limit of the request
+    /// covers the streaming of the response body.
+    pub async fn handle(
+        &self,
+        req: hyper::Request<Incoming>,
+    ) -> AnyResult<hyper::Response<HyperOutgoingBody>> {
+        let (mut store, _) = self.engine.limited_store(&self.conf)?;
+        BlocklessRunner::preview2_setup(&self.conf, store.data_mut())?;
+        if self.conf.nn {
+            BlocklessRunner::nn_store_setup(&self.conf, &mut store, true)?;
+        }
+        store.data_mut().wasi_http = Some(Arc::new(Mutex::new(WasiHttpCtx::new())));
+
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let req = store.data_mut().new_incoming_request(
+            Scheme::Http,
+            req.map(|body| {
+                body.map_err(wasmtime_wasi_http::hyper_request_error)
+                    .boxed()
+            }),
+        )?;
+        let out = store.data_mut().new_response_outparam(sender)?;
+        let pre = self.pre.clone();
+        // the store is moved to the task, the task streams the body after the response is set.
+        let task = tokio::spawn(async move {
+            let proxy = pre.instantiate_async(&mut store).await?;
+            proxy
+                .wasi_http_incoming_handler()
+                .call_handle(&mut store, req, out)
+                .await
+        });
+        match receiver.await {
+            Ok(Ok(resp)) => {
+                // the task keeps streaming the body, the error after the response is logged.
+                tokio::spawn(async move {
+                    match task.await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(e)) => error!("the handler failed after the response: {e:#}"),
+                        Err(e) => error!("the handler task failed after the response: {e}"),
+                    }
+                });
+                Ok(resp)
+            }
+            Ok(Err(e)) => Err(e.into()),
+            Err(_) => {
+                let e = match task.await {
+                    Ok(Ok(())) => anyhow!("the handler returned"),
+                    Ok(Err(e)) => e,
+                    Err(e) => e.into(),
+                };
+                Err(e.context("the guest never set the response"))
+            }
+        }
+    }
+
+    /// the response of the request, the error is responded as `500 Internal Server Error`.
+    async fn respond(
+        &self,
+        req: hyper::Request<Incoming>,
+    ) -> Result<hyper::Response<HyperOutgoingBody>, Infallible> {
+        match self.handle(req).await {
+            Ok(resp) => Ok(resp),
+            Err(e) => {
+                error!("failed to handle the request: {e:#}");
+                let body = Full::new(Bytes::from_static(b"Internal Server Error\n"))
+                    .map_err(|never| match never {})
+                    .boxed();
+                let mut resp = hyper::Response::new(body);
+                *resp.status_mut() = hyper::StatusCode::INTERNAL_SERVER_ERROR;
+                Ok(resp)
+            }
+        }
+    }
+
+    /// serve the HTTP/1.1 connections of the listener forever.
+    pub async fn serve(self, listener: TcpListener) -> AnyResult<()> {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(e) => {
+                    error!("failed to accept the connection: {e}");
+                    continue;
+                }
+            };
+            let handler = self.clone();
+            tokio::spawn(async move {
+                let service = hyper::service::service_fn(move |req| {
+                    let handler = handler.clone();
+                    async move { handler.respond(req).await }
+                });
+                let conn = hyper::server::conn::http1::Builder::new()
+                    .keep_alive(true)
+                    .serve_connection(TokioIo::new(stream), service);
+                if let Err(e) = conn.await {
+                    debug!("failed to serve the connection from {addr}: {e}");
+                }
+            });
+        }
+    }
+}
+
+/// serve the component of the configure as the HTTP handler on the address.
+pub async fn blockless_serve_http(b_conf: BlocklessConfig, addr: SocketAddr) -> AnyResult<()> {
+    let engine = BlocklessEngine::new(&b_conf)?;
+    let handler = engine.prepare_http(&b_conf)?;
+    let listener = TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("failed to listen on {addr}"))?;
+    info!("serving the HTTP requests on {}", listener.local_addr()?);
+    handler.serve(listener).await
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use wasi_common::{
+        BlsRuntimePermissionsContainer, DeterministicConfig, PermissionGrant, PermissionsConfig,
+    };
+    use wasmtime_wasi_http::bindings::http::types::ErrorCode;
+    use wasmtime_wasi_http::types::OutgoingRequestConfig;
+
+    /// the handler responds `200 OK` with the empty body.
+    const HTTP_OK_WAT: &str = r#"
+    (component
+        (import "wasi:http/types@0.2.3" (instance $types
+            (export "fields" (type $fields (sub resource)))
+            (export "incoming-request" (type (sub resource)))
+            (export "outgoing-response" (type $outgoing-response (sub resource)))
+            (export "response-outparam" (type $response-outparam (sub resource)))
+            (type $dns-payload (record
+                (field "rcode" (option string))
+                (field "info-code" (option u16))))
+            (export "DNS-error-payload" (type $dns-error-payload (eq $dns-payload)))
+            (type $tls-payload (record
+                (field "alert-id" (option u8))
+                (field "alert-message" (option string))))
+            (export "TLS-alert-received-payload" (type $tls-alert-payload (eq $tls-payload)))
+            (type $size-payload (record
+                (field "field-name" (option string))
+                (field "field-size" (option u32))))
+            (export "field-size-payload" (type $field-size-payload (eq $size-payload)))
+            (type $error (variant
+                (case "DNS-timeout")
+                (case "DNS-error" $dns-error-payload)
+                (case "destination-not-found")
+                (case "destination-unavailable")
+                (case "destination-IP-prohibited")
+                (case "destination-IP-unroutable")
+                (case "connection-refused")
+                (case "connection-terminated")
+                (case "connection-timeout")
+                (case "connection-read-timeout")
+                (case "connection-write-timeout")
+                (case "connection-limit-reached")
+                (case "TLS-protocol-error")
+                (case "TLS-certificate-error")
+                (case "TLS-alert-received" $tls-alert-payload)
+                (case "HTTP-request-denied")
+                (case "HTTP-request-length-required")
+                (case "HTTP-request-body-size" (option u64))
+                (case "HTTP-request-method-invalid")
+                (case "HTTP-request-URI-invalid")
+                (case "HTTP-request-URI-too-long")
+                (case "HTTP-request-header-section-size" (option u32))
+                (case "HTTP-request-header-size" (option $field-size-payload))
+                (case "HTTP-request-trailer-section-size" (option u32))
+                (case "HTTP-request-trailer-size" $field-size-payload)
+                (case "HTTP-response-incomplete")
+                (case "HTTP-response-header-section-size" (option u32))
+                (case "HTTP-response-header-size" $field-size-payload)
+                (case "HTTP-response-body-size" (option u64))
+                (case "HTTP-response-trailer-section-size" (option u32))
+                (case "HTTP-response-trailer-size" $field-size-payload)
+                (case "HTTP-response-transfer-coding" (option string))
+                (case "HTTP-response-content-coding" (option string))
+                (case "HTTP-response-timeout")
+                (case "HTTP-upgrade-failed")
+                (case "HTTP-protocol-error")
+                (case "loop-detected")
+                (case "configuration-error")
+                (case "internal-error" (option string))))
+            (export "error-code" (type $error-code (eq $error)))
+            (export "[constructor]fields" (func (result (own $fields))))
+            (export "[constructor]outgoing-response"
+                (func (param "headers" (own $fields)) (result (own $outgoing-response))))
+            (export "[static]response-outparam.set"
+                (func
+                    (param "param" (own $response-outparam))
+                    (param "response" (result (own $outgoing-response) (error $error-code)))))
+        ))
+        (alias export $types "incoming-request" (type $incoming-request))
+        (alias export $types "response-outparam" (type $response-outparam))
+        (alias export $types "[constructor]fields" (func $new-fields))
+        (alias export $types "[constructor]outgoing-response" (func $new-response))
+        (alias export $types "[static]response-outparam.set" (func $set-response))
+
+        (core module $Memory (memory (export "memory") 1))
+        (core instance $memory (instantiate $Memory))
+        (core func $core-new-fields (canon lower (func $new-fields)))
+        (core func $core-new-response (canon lower (func $new-response)))
+        (core func $core-set-response
+            (canon lower (func $set-response) (memory $memory "memory")))
+        (core module $Handler
+            (import "types" "new-fields" (func $new-fields (result i32)))
+            (import "types" "new-response" (func $new-response (param i32) (result i32)))
+            (import "types" "set-response"
+                (func $set-response (param i32 i32 i32 i32 i64 i32 i32 i32 i32)))
+            (func (export "handle") (param $request i32) (param $out i32)
+                (call $set-response
+                    (local.get $out)
+                    (i32.const 0)
+                    (call $new-response (call $new-fields))
+                    (i32.const 0) (i64.const 0) (i32.const 0)
+                    (i32.const 0) (i32.const 0) (i32.const 0))
+            )
+        )
+        (core instance $handler (instantiate $Handler
+            (with "types" (instance
+                (export "new-fields" (func $core-new-fields))
+                (export "new-response" (func $core-new-response))
+                (export "set-response" (func $core-set-response))
+            ))
+        ))
+        (func $handle
+            (param "request" (own $incoming-request))
+            (param "response-out" (own $response-outparam))
+            (canon lift (core func $handler "handle")))
+        (instance $incoming-handler (export "handle" (func $handle)))
+        (export "wasi:http/incoming-handler@0.2.3" (instance $incoming-handler))
+    )
+    "#;
+
+    #[test]
+    fn test_prepare_http() {
+        let temp_dir = tempdir::TempDir::new("blockless_http").unwrap();
+        let file_path = temp_dir.path().join("test_http.wat");
+        std::fs::write(&file_path, r#"(module (func (export "_start")))"#).unwrap();
+        let mut conf = BlocklessConfig::new(file_path.to_str().unwrap());
+        let engine = BlocklessEngine::new(&conf).unwrap();
+        let err = engine.prepare_http(&conf).err().unwrap();
+        assert!(err.to_string().contains("must be a component"));
+
+        conf.set_deterministic(Some(DeterministicConfig::default()));
+        let err = engine.prepare_http(&conf).err().unwrap();
+        assert!(err.to_string().contains("deterministic mode"));
+    }
+
+    #[test]
+    fn test_serve_http() {
+        let temp_dir = tempdir::TempDir::new("blockless_http").unwrap();
+        let file_path = temp_dir.path().join("test_http.wat");
+        std::fs::write(&file_path, HTTP_OK_WAT).unwrap();
+        let conf = BlocklessConfig::new(file_path.to_str().unwrap());
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_io()
+            .enable_time()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let engine = BlocklessEngine::new(&conf).unwrap();
+            let handler = engine.prepare_http(&conf).unwrap();
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let addr = listener.local_addr().unwrap();
+            tokio::spawn(handler.serve(listener));
+
+            let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+            let request = "GET /hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n";
+            stream.write_all(request.as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        });
+    }
+
+    #[test]
+    fn test_send_request_permissions() {
+        let send = |perms: Option<BlsRuntimePermissionsContainer>, url: &str| {
+            let mut ctx = BlocklessContext {
+                http_perms: perms,
+                ..Default::default()
+            };
+            let body = http_body_util::Empty::new()
+                .map_err(|never| match never {})
+                .boxed();
+            let request = hyper::Request::get(url).body(body).unwrap();
+            let config = OutgoingRequestConfig {
+                use_tls: false,
+                connect_timeout: Duration::from_secs(1),
+                first_byte_timeout: Duration::from_secs(1),
+                between_bytes_timeout: Duration::from_secs(1),
+            };
+            ctx.send_request(request, config)
+                .err()
+                .map(|e| e.downcast().unwrap())
+        };
+        // all requests are denied without the permissions.
+        let denied = send(None, "http://127.0.0.1:8080/");
+        assert!(matches!(denied, Some(ErrorCode::HttpRequestDenied)));
+
+        let perms = BlsRuntimePermissionsContainer::new_with_env_cwd(None);
+        let config = PermissionsConfig {
+            allow_net: Some(PermissionGrant::List(vec!["127.0.0.1:8080".into()])),
+            ..Default::default()
+        };
+        perms.set_permissions_config(&config).unwrap();
+        let denied = send(Some(perms), "http://127.0.0.1:9090/");
+        assert!(matches!(denied, Some(ErrorCode::HttpRequestDenied)));
+    }
+}
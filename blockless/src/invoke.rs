@@ -0,0 +1,158 @@
+use anyhow::{Context, anyhow, bail};
+use wasmtime::{Instance, Store, Val, ValType, component};
+
+use crate::AnyResult;
+use crate::context::BlocklessContext;
+use wasi_common::Invoke;
+
+/// parse the argument of the core function by the value type.
+fn core_arg(ty: &ValType, arg: &str) -> AnyResult<Val> {
+    let val = match ty {
+        // the unsigned value is accepted, it's same bits as the signed value.
+        ValType::I32 => Val::I32(
+            arg.parse::<i32>()
+                .or_else(|_| arg.parse::<u32>().map(|v| v as i32))?,
+        ),
+        ValType::I64 => Val::I64(
+            arg.parse::<i64>()
+                .or_else(|_| arg.parse::<u64>().map(|v| v as i64))?,
+        ),
+        ValType::F32 => Val::F32(arg.parse::<f32>()?.to_bits()),
+        ValType::F64 => Val::F64(arg.parse::<f64>()?.to_bits()),
+        ty => bail!("the argument type {ty} is not supported."),
+    };
+    Ok(val)
+}
+
+/// parse the arguments of the core function by the params types.
+pub(crate) fn core_args(params: &[ValType], args: &[String]) -> AnyResult<Vec<Val>> {
+    if params.len() != args.len() {
+        bail!(
+            "the function expects {} arguments, but {} given.",
+            params.len(),
+            args.len()
+        );
+    }
+    params
+        .iter()
+        .zip(args)
+        .enumerate()
+        .map(|(i, (ty, arg))| {
+            core_arg(ty, arg).with_context(|| format!("invalid argument {i}: `{arg}` for {ty}."))
+        })
+        .collect()
+}
+
+/// format the result of the core function.
+pub(crate) fn core_result(val: &Val) -> String {
+    match val {
+        Val::I32(v) => v.to_string(),
+        Val::I64(v) => v.to_string(),
+        Val::F32(v) => f32::from_bits(*v).to_string(),
+        Val::F64(v) => f64::from_bits(*v).to_string(),
+        Val::V128(v) => format!("{:#034x}", v.as_u128()),
+        // the references can't be printed, only show if it's null.
+        val => match val.clone().ref_() {
+            Some(r) if r.is_null() => "null".to_string(),
+            _ => "<ref>".to_string(),
+        },
+    }
+}
+
+/// invoke the export of the core instance, return the formatted results.
+/// The export of the library module is named as `module#function`, e.g. `lib#add`.
+pub(crate) async fn invoke_core(
+    store: &mut Store<BlocklessContext>,
+    instance: Instance,
+    invoke: &Invoke,
+) -> AnyResult<Vec<String>> {
+    let func = match instance.get_func(&mut *store, &invoke.name) {
+        Some(func) => Some(func),
+        None => invoke
+            .name
+            .split_once('#')
+            .and_then(|(module, name)| store.data().instance_ctx.export_func(module, name)),
+    }
+    .ok_or_else(|| anyhow!("the export function `{}` is not found.", invoke.name))?;
+    let ty = func.ty(&*store);
+    let params: Vec<ValType> = ty.params().collect();
+    let params = core_args(&params, &invoke.args)?;
+    let mut results = vec![Val::I32(0); ty.results().len()];
+    func.call_async(&mut *store, &params, &mut results).await?;
+    Ok(results.iter().map(core_result).collect())
+}
+
+/// invoke the export of the component, the arguments and results are the wasm-wave values.
+/// The export in the interface is named as `interface#function`,
+/// e.g. `example:calc/ops#add`.
+pub(crate) async fn invoke_component(
+    store: &mut Store<BlocklessContext>,
+    instance: &component::Instance,
+    invoke: &Invoke,
+) -> AnyResult<Vec<String>> {
+    let not_found = || anyhow!("the export function `{}` is not found.", invoke.name);
+    let index = match invoke.name.rsplit_once('#') {
+        Some((interface, name)) => {
+            let interface = instance
+                .get_export(&mut *store, None, interface)
+                .ok_or_else(not_found)?;
+            instance.get_export(&mut *store, Some(&interface), name)
+        }
+        None => instance.get_export(&mut *store, None, &invoke.name),
+    }
+    .ok_or_else(not_found)?;
+    let func = instance
+        .get_func(&mut *store, index)
+        .ok_or_else(not_found)?;
+    let params = func.params(&*store);
+    if params.len() != invoke.args.len() {
+        bail!(
+            "the function expects {} arguments, but {} given.",
+            params.len(),
+            invoke.args.len()
+        );
+    }
+    let params = params
+        .iter()
+        .zip(&invoke.args)
+        .map(|((name, ty), arg)| {
+            component::Val::from_wave(ty, arg)
+                .with_context(|| format!("invalid argument `{arg}` for {name}."))
+        })
+        .collect::<AnyResult<Vec<_>>>()?;
+    let mut results = vec![component::Val::Bool(false); func.results(&*store).len()];
+    func.call_async(&mut *store, &params, &mut results).await?;
+    func.post_return_async(&mut *store).await?;
+    results.iter().map(|r| r.to_wave()).collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_core_args() {
+        let params = [ValType::I32, ValType::I64, ValType::F32, ValType::F64];
+        let args = ["-1", "18446744073709551615", "1.5", "2.25"].map(String::from);
+        let vals = core_args(&params, &args).unwrap();
+        assert_eq!(vals[0].unwrap_i32(), -1);
+        assert_eq!(vals[1].unwrap_i64(), -1);
+        assert_eq!(vals[2].unwrap_f32(), 1.5);
+        assert_eq!(vals[3].unwrap_f64(), 2.25);
+        let vals = core_args(&[ValType::I32], &["4294967295".into()]).unwrap();
+        assert_eq!(vals[0].unwrap_i32(), -1);
+
+        assert!(core_args(&[ValType::I32], &[]).is_err());
+        assert!(core_args(&[ValType::I32], &["a".into()]).is_err());
+        assert!(core_args(&[ValType::I32], &["1.5".into()]).is_err());
+    }
+
+    #[test]
+    fn test_core_result() {
+        assert_eq!(core_result(&Val::I32(-3)), "-3");
+        assert_eq!(core_result(&Val::I64(1 << 40)), "1099511627776");
+        assert_eq!(core_result(&Val::F32(2.5f32.to_bits())), "2.5");
+        assert_eq!(core_result(&Val::F64(0.125f64.to_bits())), "0.125");
+        assert_eq!(core_result(&Val::FuncRef(None)), "null");
+    }
+}
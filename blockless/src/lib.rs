@@ -1,39 +1,58 @@
+mod cache;
+mod capture;
+mod compile;
 mod context;
+mod deadline;
+mod deterministic;
 pub mod error;
+mod http;
+mod invoke;
+mod limits;
 mod modules;
+mod profiling;
+mod report;
+mod sandbox;
+mod threads;
 
 pub use anyhow::Result as AnyResult;
-use anyhow::{Context, bail};
+use anyhow::{Context, anyhow, bail};
+use blockless_drivers::wit::BlocklessWitCtx;
 use blockless_drivers::{CdylibDriver, DriverConetxt};
 pub use blockless_multiaddr::MultiAddr;
 use cap_std::ambient_authority;
+use capture::CaptureStream;
+pub use compile::*;
 use context::BlocklessContext;
+use deadline::{Deadline, EpochTicker, set_store_deadline};
+use deterministic::VirtualClock;
 pub use error::*;
+pub use http::*;
+use limits::BlocklessLimiter;
 use log::{debug, error};
 use modules::ModuleLinker;
+use profiling::Profiler;
+pub use report::*;
 use std::fs::File;
+use std::net::SocketAddr;
 use std::sync::Mutex;
+use std::time::Instant;
 use std::{env, path::Path, sync::Arc};
+use threads::BlocklessThreadsCtx;
 use wasi_common::sync::WasiCtxBuilder;
 use wasi_common::sync::{Dir, TcpListener};
 pub use wasi_common::*;
 use wasmtime::{
-    Config, Engine, Linker, Module, Precompiled, Store, StoreLimits, StoreLimitsBuilder, Trap,
-    component::Component,
+    Config, Engine, Instance, InstancePre, Linker, Module, Precompiled, Store, StoreLimits,
+    StoreLimitsBuilder, Trap, component::Component,
 };
 use wasmtime_wasi::IoView;
-use wasmtime_wasi::{DirPerms, FilePerms};
+use wasmtime_wasi::bindings::CommandPre;
+use wasmtime_wasi::{DirPerms, FilePerms, SocketAddrUse};
 use wasmtime_wasi_nn::wit::WasiNnView;
-use wasmtime_wasi_threads::WasiThreadsCtx;
 
 // the default wasm entry name.
 const ENTRY: &str = "_start";
 
-pub struct ExitStatus {
-    pub fuel: Option<u64>,
-    pub code: i32,
-}
-
 pub enum BlsRunTarget {
     Module(Module),
     Component(Component),
@@ -59,7 +78,9 @@ trait BlocklessConfig2Preview1WasiBuilder {
     fn preview1_builder(&self) -> anyhow::Result<WasiCtxBuilder>;
     fn preview2_builder(&self) -> anyhow::Result<wasmtime_wasi::WasiCtxBuilder>;
     fn preview1_set_stdio(&self, builder: &mut WasiCtxBuilder);
-    fn preview1_engine_config(&self) -> Config;
+    fn preview2_set_stdio(&self, builder: &mut wasmtime_wasi::WasiCtxBuilder);
+    fn open_stdio_file(&self, file_name: &str) -> Option<File>;
+    fn preview1_engine_config(&self) -> AnyResult<Config>;
     fn store_limits(&self) -> StoreLimits;
 }
 
@@ -98,35 +119,27 @@ impl BlocklessConfig2Preview1WasiBuilder for BlocklessConfig {
                 //$out_ref is b_conf.stdout_ref() or b_conf.stderr_ref()
                 match $out_ref {
                     &$out_expr::FileName(ref file_name) => {
-                        let mut is_set_fileout = false;
-                        if let Some(r) = b_conf.fs_root_path_ref() {
-                            let root = Path::new(r);
-                            let file_name = root.join(file_name);
-                            if let Some(f) = {
-                                let mut file_opts = std::fs::File::options();
-                                file_opts.create(true);
-                                file_opts.append(true);
-                                file_opts.write(true);
-                                file_opts.open(file_name).ok().map(|file| {
-                                    let file = cap_std::fs::File::from_std(file);
-                                    let f = wasi_common::sync::file::File::from_cap_std(file);
-                                    Box::new(f)
-                                })
-                            } {
-                                is_set_fileout = true;
+                        match b_conf.open_stdio_file(file_name) {
+                            Some(file) => {
+                                let file = cap_std::fs::File::from_std(file);
+                                let f = wasi_common::sync::file::File::from_cap_std(file);
                                 //builder.stdout() or builder.stderr()
-                                builder.$stdout(f);
+                                builder.$stdout(Box::new(f));
                             }
-                        }
-                        if !is_set_fileout {
                             //$inherit_stdout is inherit_stdout() or inherit_stderr()
-                            builder.$inherit_stdout();
+                            None => {
+                                builder.$inherit_stdout();
+                            }
                         }
                     }
                     &$out_expr::Inherit => {
                         builder.$inherit_stdout();
                     }
-                    &$out_expr::Null => {}
+                    &$out_expr::Pipe(ref pipe) => {
+                        builder.$stdout(Box::new(pipe.write_pipe()));
+                    }
+                    // the buffer is replaced with the pipe stored in the context by the setup.
+                    &$out_expr::Buffer(_) | &$out_expr::Null => {}
                 }
             };
         }
@@ -167,6 +180,13 @@ impl BlocklessConfig2Preview1WasiBuilder for BlocklessConfig {
             builder.preopened_dir(d, "/")?;
             max_fd += 1;
         }
+        // the listeners are denied in the deterministic mode, same as the socket extension.
+        let deterministic = b_conf.deterministic_ref();
+        if !b_conf.tcp_listens.is_empty()
+            && !deterministic::is_extension_allowed(deterministic, "socket")
+        {
+            bail!("the tcp listeners are not allowed in the deterministic mode.");
+        }
         //set the tcp listener.
         for (l, fd) in b_conf.tcp_listens.iter() {
             let fd = if let Some(fd) = fd {
@@ -183,11 +203,16 @@ impl BlocklessConfig2Preview1WasiBuilder for BlocklessConfig {
             let l = TcpListener::from_std(l);
             builder.preopened_socket(fd, l)?;
         }
+        // the virtual clocks and the seeded random generator of the deterministic mode.
+        if let Some(deterministic) = b_conf.deterministic_ref() {
+            builder.clocks(VirtualClock::new(deterministic).wasi_clocks());
+            builder.random(Box::new(deterministic::seeded_random(deterministic)));
+        }
         anyhow::Ok(builder)
     }
 
     /// convert the blockless configure  to wasmtime configure.
-    fn preview1_engine_config(&self) -> Config {
+    fn preview1_engine_config(&self) -> AnyResult<Config> {
         let mut conf = Config::new();
 
         if let Some(max) = self.opts.memory_reservation {
@@ -222,7 +247,39 @@ impl BlocklessConfig2Preview1WasiBuilder for BlocklessConfig {
             conf.memory_reservation_for_growth(size);
         }
 
-        if !self.opts.is_empty() {
+        self.opts.validate_features()?;
+        if let Some(enable) = self.opts.wasm_simd {
+            conf.wasm_simd(enable);
+        }
+        if let Some(enable) = self.opts.wasm_relaxed_simd {
+            conf.wasm_relaxed_simd(enable);
+        }
+        if let Some(enable) = self.opts.wasm_tail_call {
+            conf.wasm_tail_call(enable);
+        }
+        if let Some(enable) = self.opts.wasm_function_references {
+            conf.wasm_function_references(enable);
+        }
+        if let Some(enable) = self.opts.wasm_gc {
+            conf.wasm_gc(enable);
+        }
+        if let Some(enable) = self.opts.wasm_memory64 {
+            conf.wasm_memory64(enable);
+        }
+        if let Some(enable) = self.opts.wasm_multi_memory {
+            conf.wasm_multi_memory(enable);
+        }
+        if let Some(enable) = self.opts.wasm_component_model {
+            conf.wasm_component_model(enable);
+        }
+        if let Some(enable) = self.opts.wasm_component_model_more_flags {
+            conf.wasm_component_model_more_flags(enable);
+        }
+        if let Some(enable) = self.opts.wasm_component_model_multiple_returns {
+            conf.wasm_component_model_multiple_returns(enable);
+        }
+
+        if self.opts.has_engine_tuning() {
             if let Some(s) = self.opts.opt_level {
                 conf.cranelift_opt_level(s);
             }
@@ -284,24 +341,147 @@ impl BlocklessConfig2Preview1WasiBuilder for BlocklessConfig {
             // fuel is enable.
             conf.consume_fuel(true);
         }
+        if self.get_limited_time().is_some()
+            || self.profile_ref().is_some()
+            || self.feature_thread()
+        {
+            // the wall-clock deadline, the profiling and stopping the run when a thread
+            // failed are implemented by the epoch interruption.
+            conf.epoch_interruption(true);
+        }
+        if self.deterministic_ref().is_some() {
+            // the NaN results of the floating-point operations are platform-dependent.
+            conf.cranelift_nan_canonicalization(true);
+            // the relaxed SIMD results are platform-dependent too.
+            conf.relaxed_simd_deterministic(true);
+        }
         conf.async_support(true);
-        if self.feature_thread() {
-            conf.wasm_threads(true);
+        match self.opts.wasm_threads {
+            Some(false) if self.feature_thread() => {
+                bail!("the threads can't be disabled when the thread feature is enabled.")
+            }
+            Some(enable) => {
+                conf.wasm_threads(enable);
+            }
+            None if self.feature_thread() => {
+                conf.wasm_threads(true);
+            }
+            None => {}
         }
-        conf.cache_config_load_default().unwrap();
-        conf
+        cache::cache_config(&mut conf, self.cache_ref())?;
+        Ok(conf)
     }
 
+    /// create the preview2 builder by the configure, the directories and sockets
+    /// are governed by the same permissions as the core modules.
     fn preview2_builder(&self) -> anyhow::Result<wasmtime_wasi::WasiCtxBuilder> {
         let mut builder = wasmtime_wasi::WasiCtxBuilder::new();
-        for (host_path, guest_path) in self.dirs.iter() {
-            builder.preopened_dir(host_path, guest_path, DirPerms::all(), FilePerms::all())?;
+        let perms = BlsRuntimePermissionsContainer::new_with_env_cwd(None);
+        perms.set_permissions_config(&self.permissions_config)?;
+        let root_dir = self.fs_root_path_ref().map(|root| (root, "/"));
+        let dirs = self.dirs.iter().map(|(h, g)| (h.as_str(), g.as_str()));
+        for (host_path, guest_path) in dirs.chain(root_dir) {
+            match preview2_dir_perms(&perms, guest_path) {
+                Some((dir_perms, file_perms)) => {
+                    builder.preopened_dir(host_path, guest_path, dir_perms, file_perms)?;
+                }
+                None => debug!("the directory {guest_path} is not permitted, skip it."),
+            }
         }
-        if let Some(root_dir) = self.fs_root_path_ref() {
-            builder.preopened_dir(root_dir, "/", DirPerms::all(), FilePerms::all())?;
+        self.preview2_set_stdio(&mut builder);
+        // the tcp listeners of configure can be bound by the component.
+        let listens: Vec<SocketAddr> = self.tcp_listens.iter().map(|(addr, _)| *addr).collect();
+        builder.socket_addr_check(move |addr, addr_use| {
+            let allowed = match addr_use {
+                SocketAddrUse::TcpBind if listens.contains(&addr) => true,
+                // the check runs on the async runtime, so it never prompts.
+                _ => perms.is_net_granted(&addr.ip().to_string(), Some(addr.port())),
+            };
+            Box::pin(async move { allowed })
+        });
+        let perms_conf = &self.permissions_config;
+        builder.allow_ip_name_lookup(perms_conf.allow_all || perms_conf.allow_net.is_some());
+        if let Some(deterministic) = self.deterministic_ref() {
+            let clock = VirtualClock::new(deterministic);
+            builder.wall_clock(clock.clone());
+            builder.monotonic_clock(clock);
+            builder.secure_random(deterministic::seeded_random(deterministic));
+            builder.insecure_random(deterministic::seeded_random(deterministic));
+            builder.insecure_random_seed(deterministic.seed as u128);
+            // the sockets are nondeterministic, same as the socket extension.
+            if !deterministic.is_extension_allowed("socket") {
+                builder.socket_addr_check(|_, _| Box::pin(async { false }));
+                builder.allow_ip_name_lookup(false);
+            }
         }
         Ok(builder)
     }
+
+    /// set the stdio of the component same as the core modules.
+    fn preview2_set_stdio(&self, builder: &mut wasmtime_wasi::WasiCtxBuilder) {
+        macro_rules! process_output {
+            ($out_ref: expr, $out_expr: ident, $stdout: ident, $inherit_stdout: ident) => {
+                match $out_ref {
+                    &$out_expr::FileName(ref file_name) => match self.open_stdio_file(file_name) {
+                        Some(file) => {
+                            builder.$stdout(wasmtime_wasi::OutputFile::new(file));
+                        }
+                        None => {
+                            builder.$inherit_stdout();
+                        }
+                    },
+                    &$out_expr::Inherit => {
+                        builder.$inherit_stdout();
+                    }
+                    &$out_expr::Pipe(ref pipe) => {
+                        builder.$stdout(CaptureStream(pipe.clone()));
+                    }
+                    // the buffer is replaced with the pipe stored in the context by the setup,
+                    // the default of builder is the sink.
+                    &$out_expr::Buffer(_) | &$out_expr::Null => {}
+                }
+            };
+        }
+        process_output!(self.stdout_ref(), Stdout, stdout, inherit_stdout);
+        process_output!(self.stderr_ref(), Stderr, stderr, inherit_stderr);
+
+        match self.stdio.stdin {
+            Stdin::Inherit => {
+                builder.inherit_stdin();
+            }
+            Stdin::Fixed(ref input) => {
+                let input = input.clone().into_bytes();
+                builder.stdin(wasmtime_wasi::pipe::MemoryInputPipe::new(input));
+            }
+        }
+    }
+
+    /// open the stdout or stderr file in the root path, `None` if the file can't be opened.
+    fn open_stdio_file(&self, file_name: &str) -> Option<File> {
+        let root = Path::new(self.fs_root_path_ref()?);
+        let mut file_opts = File::options();
+        file_opts.create(true);
+        file_opts.append(true);
+        file_opts.write(true);
+        file_opts.open(root.join(file_name)).ok()
+    }
+}
+
+/// the permissions of the preopened directory for the component, the wasi of components
+/// can't be checked by every file, so the permissions are applied to the whole directory.
+fn preview2_dir_perms(
+    perms: &BlsRuntimePermissionsContainer,
+    guest_path: &str,
+) -> Option<(DirPerms, FilePerms)> {
+    // the setup never prompts, the directory not granted is skipped.
+    let read = perms.is_read_granted(guest_path);
+    let write = perms.is_write_granted(guest_path);
+    match (read, write) {
+        (true, true) => Some((DirPerms::all(), FilePerms::all())),
+        (true, false) => Some((DirPerms::READ, FilePerms::READ)),
+        (false, true) => Some((DirPerms::MUTATE, FilePerms::WRITE)),
+        (false, false) => None,
+    }
 }
 
 enum BlsLinker {
@@ -309,128 +489,540 @@ enum BlsLinker {
     Component(wasmtime::component::Linker<BlocklessContext>),
 }
 
-impl BlsLinker {
-    fn unwrap_core(&mut self) -> &mut wasmtime::Linker<BlocklessContext> {
-        match self {
-            BlsLinker::Core(linker) => linker,
-            BlsLinker::Component(_) => panic!("expected a core linker, not a component linker."),
-        }
+/// the run target prepared by the engine.
+enum PreparedTarget {
+    /// the linker with the host functions, the items bound to the store
+    /// (the library modules and the threads) are linked when run.
+    Linker(Linker<BlocklessContext>),
+    Module(InstancePre<BlocklessContext>),
+    Component(CommandPre<BlocklessContext>),
+    /// the component invoked by the export, it's not required to be a command.
+    ComponentInstance(wasmtime::component::InstancePre<BlocklessContext>),
+}
+
+/// The blockless engine, it can be shared by many runners and runs.
+///
+/// The engine options(`opts`, `feature_thread`, `debug_info`) are taken from the configure
+/// when the engine created, the fuel and time limits of runs only work when the limits
+/// are enabled in the configure of the engine.
+#[derive(Clone)]
+pub struct BlocklessEngine {
+    engine: Engine,
+    consume_fuel: bool,
+    epoch_interruption: bool,
+    deterministic: bool,
+    // the ticker must be alive while the engine is used.
+    _ticker: Option<Arc<EpochTicker>>,
+}
+
+impl BlocklessEngine {
+    pub fn new(b_conf: &BlocklessConfig) -> AnyResult<Self> {
+        let conf = b_conf.preview1_engine_config()?;
+        let engine = Engine::new(&conf)?;
+        let epoch_interruption = b_conf.get_limited_time().is_some()
+            || b_conf.profile_ref().is_some()
+            || b_conf.feature_thread();
+        let ticker = epoch_interruption.then(|| Arc::new(EpochTicker::start(&engine)));
+        Ok(Self {
+            engine,
+            consume_fuel: b_conf.get_limited_fuel().is_some(),
+            epoch_interruption,
+            deterministic: b_conf.deterministic_ref().is_some(),
+            _ticker: ticker,
+        })
     }
 
-    #[allow(dead_code)]
-    fn unwrap_component(&mut self) -> &mut wasmtime::component::Linker<BlocklessContext> {
-        match self {
-            BlsLinker::Core(_) => panic!("expected a component linker, not a component linker."),
-            BlsLinker::Component(linker) => linker,
+    #[inline(always)]
+    pub fn engine(&self) -> &Engine {
+        &self.engine
+    }
+
+    /// compile the modules of the configure and prepare the linker,
+    /// the runner can run the modules many times.
+    pub fn prepare(&self, b_conf: &BlocklessConfig) -> AnyResult<BlocklessRunner> {
+        let deterministic = b_conf.deterministic_ref();
+        if deterministic.is_some() {
+            if !self.deterministic {
+                bail!("the deterministic mode is not enabled in the engine.");
+            }
+            if b_conf.feature_thread() {
+                bail!("the threads are not supported in the deterministic mode.");
+            }
+        }
+        BlocklessRunner::init_drivers(b_conf);
+        let (target, modules) = match b_conf.version() {
+            // this is older configure for bls-runtime, this only run single wasm.
+            BlocklessConfigVersion::Version0 => (self.load_module(b_conf.entry_ref())?, Vec::new()),
+            BlocklessConfigVersion::Version1 => self.load_modules(b_conf)?,
+        };
+        let mut linker = match target {
+            BlsRunTarget::Module(_) => BlsLinker::Core(Linker::new(&self.engine)),
+            BlsRunTarget::Component(_) => {
+                BlsLinker::Component(wasmtime::component::Linker::new(&self.engine))
+            }
+        };
+        if b_conf.nn {
+            BlocklessRunner::nn_linker_setup(&mut linker)?;
         }
+        let prepared = match linker {
+            BlsLinker::Core(mut linker) => {
+                BlocklessRunner::preview1_linker_setup(&mut linker, |name| {
+                    deterministic::is_extension_allowed(deterministic, name)
+                });
+                let module = target.unwrap_core();
+                if let Some(deterministic) = deterministic {
+                    let mut all = vec![module];
+                    all.extend(modules.iter().map(|(_, m)| m));
+                    deterministic::deny_core_extensions(&mut linker, deterministic, &all)?;
+                }
+                let is_version1 = matches!(b_conf.version(), BlocklessConfigVersion::Version1);
+                if is_version1 {
+                    modules::validate_imports(&linker, b_conf, module, &modules)?;
+                }
+                // the library modules and the threads must be linked with the store.
+                if is_version1 || b_conf.feature_thread() {
+                    PreparedTarget::Linker(linker)
+                } else {
+                    // The main module might be allowed to have unknown imports, which
+                    // should be defined as traps:
+                    if b_conf.unknown_imports_trap {
+                        linker.define_unknown_imports_as_traps(module)?;
+                    }
+                    PreparedTarget::Module(linker.instantiate_pre(module)?)
+                }
+            }
+            BlsLinker::Component(mut linker) => {
+                let component = target.unwrap_component();
+                self.component_linker_setup(&mut linker, b_conf, component)?;
+                let instance_pre = linker.instantiate_pre(component)?;
+                if b_conf.invoke_ref().is_some() {
+                    PreparedTarget::ComponentInstance(instance_pre)
+                } else {
+                    PreparedTarget::Component(CommandPre::new(instance_pre)?)
+                }
+            }
+        };
+        Ok(BlocklessRunner {
+            engine: self.clone(),
+            conf: b_conf.clone(),
+            target,
+            modules,
+            prepared,
+        })
     }
-}
 
-struct BlocklessRunner(BlocklessConfig);
+    /// link the wasi and the blockless extensions for the component.
+    fn component_linker_setup(
+        &self,
+        linker: &mut wasmtime::component::Linker<BlocklessContext>,
+        b_conf: &BlocklessConfig,
+        component: &Component,
+    ) -> AnyResult<()> {
+        let deterministic = b_conf.deterministic_ref();
+        wasmtime_wasi::add_to_linker_async(linker)?;
+        blockless_drivers::wit::add_to_linker(
+            linker,
+            |name| deterministic::is_extension_allowed(deterministic, name),
+            |ctx: &mut BlocklessContext| ctx.blockless_wit_ctx(),
+        )?;
+        if let Some(deterministic) = deterministic {
+            deterministic::deny_component_extensions(
+                linker,
+                &self.engine,
+                deterministic,
+                component,
+            )?;
+        }
+        if b_conf.unknown_imports_trap {
+            linker.define_unknown_imports_as_traps(component)?;
+        }
+        Ok(())
+    }
 
-impl BlocklessRunner {
-    /// blockless run method, it execute the wasm program with configure file.
-    async fn run(self) -> AnyResult<ExitStatus> {
-        let b_conf = &self.0;
+    /// create the store with the fuel, memory and time limits of the configure,
+    /// the initial fuel is returned to calculate the fuel consumed.
+    fn limited_store(
+        &self,
+        b_conf: &BlocklessConfig,
+    ) -> AnyResult<(Store<BlocklessContext>, Option<u64>)> {
         let max_fuel = b_conf.get_limited_fuel();
-        // set the drivers root path, if not setting use exe file path.
-        let drivers_root_path = b_conf
-            .drivers_root_path_ref()
-            .map(|p| p.into())
-            .unwrap_or_else(|| {
-                let mut current_exe_path = env::current_exe().unwrap();
-                current_exe_path.pop();
-                String::from(current_exe_path.to_str().unwrap())
-            });
-        DriverConetxt::init_built_in_drivers(drivers_root_path);
-        let conf = b_conf.preview1_engine_config();
-        let engine = Engine::new(&conf)?;
-        let support_thread = b_conf.feature_thread();
-
-        let drivers = b_conf.drivers_ref();
-        Self::load_driver(drivers);
-        let entry: String = b_conf.entry_ref().into();
-        let store_limits = b_conf.store_limits();
-        let fule = b_conf.get_limited_fuel();
-
+        if max_fuel.is_some() && !self.consume_fuel {
+            bail!("the fuel limit is not enabled in the engine.");
+        }
+        if b_conf.get_limited_time().is_some() && !self.epoch_interruption {
+            bail!("the time limit is not enabled in the engine.");
+        }
+        // the run without the time limit still has the deadline, so it can be stopped.
+        let deadline = self.epoch_interruption.then(|| {
+            b_conf
+                .get_limited_time()
+                .map_or_else(Deadline::default, Deadline::after_millis)
+        });
+        let limiter = BlocklessLimiter::new(
+            b_conf.store_limits(),
+            b_conf.store_limited().max_memory_size,
+        );
         let ctx = BlocklessContext {
-            store_limits,
+            limiter,
+            deadline: deadline.clone(),
             ..Default::default()
         };
 
-        let mut store: Store<BlocklessContext> = Store::new(&engine, ctx);
-        store.limiter(|ctx| &mut ctx.store_limits);
-        // set the fule in store.
-        if let Some(f) = fule {
-            store.set_fuel(f).unwrap();
+        let mut store: Store<BlocklessContext> = Store::new(&self.engine, ctx);
+        store.limiter(|ctx| ctx.running_limiter());
+        // set the fule in store, the run without the limit can use all fuel.
+        let initial_fuel = self.consume_fuel.then(|| max_fuel.unwrap_or(u64::MAX));
+        if let Some(fuel) = initial_fuel {
+            store.set_fuel(fuel)?;
         }
-        let (mut linker, run_target, entry) =
-            self.module_linker(entry, &engine, &mut store).await?;
-        let mut is_component = false;
-        if b_conf.nn {
-            self.nn_setup(&mut linker, &mut store)?;
+        if self.epoch_interruption {
+            set_store_deadline(&mut store, deadline);
         }
-        // prepare linker.
-        match linker {
-            BlsLinker::Core(ref mut linker) => {
-                Self::preview1_linker_setup(linker);
-                //preview1 setup the permissions with options
-                store.data_mut().set_permisions(&b_conf.permissions_config);
+        Ok((store, initial_fuel))
+    }
+
+    /// compile the modules of Version1 configure, return the entry and the library modules
+    /// in the load order.
+    fn load_modules(
+        &self,
+        b_conf: &BlocklessConfig,
+    ) -> AnyResult<(BlsRunTarget, Vec<(String, Module)>)> {
+        let mut entry = None;
+        let mut libs = Vec::new();
+        for m in b_conf.modules_ref() {
+            // the modules can be precompiled by `bls-runtime compile`.
+            let module = match self.load_module(&m.file)? {
+                BlsRunTarget::Module(module) => module,
+                BlsRunTarget::Component(_) => {
+                    bail!("the component {} can't be linked as the module", m.file)
+                }
+            };
+            match m.module_type {
+                // the entry is always loaded after the library modules.
+                ModuleType::Entry if !m.depends.is_empty() => {
+                    bail!("the entry can't depend on the modules, they are all loaded before it.")
+                }
+                ModuleType::Entry => entry = Some(module),
+                ModuleType::Module => libs.push((m, module)),
             }
-            BlsLinker::Component(ref mut linker) => {
-                is_component = true;
-                wasmtime_wasi::add_to_linker_async(linker)?;
-                self.preview2_setup(store.data_mut())?;
+        }
+        let entry = entry.ok_or(anyhow!("can't find the entry"))?;
+        Ok((BlsRunTarget::Module(entry), modules::sort_modules(libs)?))
+    }
+
+    pub fn load_module<T: AsRef<Path>>(&self, path: T) -> AnyResult<BlsRunTarget> {
+        let engine = &self.engine;
+        let path: &Path = match path.as_ref().to_str() {
+            #[cfg(unix)]
+            Some("-") => "/dev/stdin".as_ref(),
+            _ => path.as_ref(),
+        };
+        let file = File::open(path)?;
+        match wasmtime::_internal::MmapVec::from_file(file) {
+            Ok(map) => self.load_module_contents(
+                path,
+                &map,
+                || unsafe { Module::deserialize_file(engine, path) },
+                || unsafe { Component::deserialize_file(engine, path) },
+            ),
+            Err(_) => {
+                let bytes = std::fs::read(path)
+                    .with_context(|| format!("failed to read file: {}", path.display()))?;
+                self.load_module_contents(
+                    path,
+                    &bytes,
+                    || unsafe { Module::deserialize(engine, &bytes) },
+                    || unsafe { Component::deserialize(engine, &bytes) },
+                )
+            }
+        }
+    }
+
+    pub fn load_module_contents(
+        &self,
+        path: &Path,
+        bytes: &[u8],
+        deserialize_module: impl FnOnce() -> AnyResult<Module>,
+        deserialize_component: impl FnOnce() -> AnyResult<Component>,
+    ) -> AnyResult<BlsRunTarget> {
+        let engine = &self.engine;
+        // the precompiled artifact must be compiled with the same target and engine options.
+        let incompatible = || {
+            format!(
+                "the precompiled file {} is incompatible with the engine, \
+                please recompile it with the same target and options",
+                path.display()
+            )
+        };
+        Ok(match engine.detect_precompiled(bytes) {
+            Some(Precompiled::Module) => {
+                BlsRunTarget::Module(deserialize_module().with_context(incompatible)?)
+            }
+            Some(Precompiled::Component) => {
+                BlsRunTarget::Component(deserialize_component().with_context(incompatible)?)
+            }
+            None => {
+                let mut code = wasmtime::CodeBuilder::new(engine);
+                code.wasm_binary_or_text(bytes, Some(path))?;
+                match code.hint() {
+                    Some(wasmtime::CodeHint::Component) => {
+                        BlsRunTarget::Component(code.compile_component()?)
+                    }
+                    Some(wasmtime::CodeHint::Module) | None => {
+                        BlsRunTarget::Module(code.compile_module()?)
+                    }
+                }
+            }
+        })
+    }
+}
+
+/// The runner holds the compiled modules and the prepared linker,
+/// it can run many times with the different configures.
+pub struct BlocklessRunner {
+    engine: BlocklessEngine,
+    // the configure used to prepare the runner.
+    conf: BlocklessConfig,
+    target: BlsRunTarget,
+    // the library modules of Version1 configure.
+    modules: Vec<(String, Module)>,
+    prepared: PreparedTarget,
+}
+
+impl BlocklessRunner {
+    /// run the prepared modules, every run has the new store.
+    /// The compiled modules, drivers and nn linker are from the configure of prepare,
+    /// the entry, invoke, library modules, stdio, args, envs, dirs, permissions and
+    /// limits are from the `b_conf`.
+    pub async fn run(&self, b_conf: BlocklessConfig) -> AnyResult<RunReport> {
+        let started = Instant::now();
+        let max_fuel = b_conf.get_limited_fuel();
+        if b_conf.profile_ref().is_some() && !self.engine.epoch_interruption {
+            bail!("the profiling is not enabled in the engine.");
+        }
+        // the virtual clocks and random generator of the run are from the `b_conf`.
+        match (b_conf.deterministic_ref(), self.conf.deterministic_ref()) {
+            (Some(deterministic), _) => self.check_deterministic(deterministic)?,
+            (None, Some(_)) => {
+                bail!("the deterministic mode of the prepare is required by the run.")
+            }
+            (None, None) => {}
+        }
+        let (mut store, initial_fuel) = self.engine.limited_store(&b_conf)?;
+        let deadline = store.data().deadline.clone();
+        let is_component = matches!(self.target, BlsRunTarget::Component(_));
+        if is_component {
+            Self::preview2_setup(&b_conf, store.data_mut())?;
+        } else {
+            // must setup before link the modules.
+            Self::preview1_setup(&b_conf, store.data_mut())?;
+            store.data_mut().entry_module = b_conf.entry_module().map(Arc::from);
+            //preview1 setup the permissions with options
+            store.data_mut().set_permisions(&b_conf.permissions_config);
+            if matches!(b_conf.version(), BlocklessConfigVersion::Version1) {
+                sandbox::sandboxes_setup(&b_conf, store.data_mut(), self.engine.consume_fuel)?;
             }
         }
-        // support thread.
-        if support_thread {
-            Self::preview1_setup_thread_support(
-                linker.unwrap_core(),
-                &mut store,
-                run_target.unwrap_core(),
-            );
+        if self.conf.nn {
+            Self::nn_store_setup(&b_conf, &mut store, is_component)?;
         }
+        let profiler = match b_conf.profile_ref() {
+            Some(_) if is_component => bail!("the profiling is not supported for components."),
+            Some(_) => {
+                let profiler = Profiler::new(b_conf.entry_ref(), self.profiling_modules(&b_conf));
+                profiler.install(&mut store, deadline.clone());
+                Some(profiler)
+            }
+            None => None,
+        };
+        let audit_trace = match b_conf.audit_ref() {
+            Some(_) if is_component => bail!("the audit trace is not supported for components."),
+            Some(audit) => {
+                let trace = Arc::new(AuditTrace::open(audit)?);
+                let ctx = store.data().preview1_ctx.as_ref().unwrap();
+                ctx.set_audit_trace(Some(trace.clone()));
+                for sandbox in store.data().sandboxes.iter() {
+                    sandbox.preview1_ctx.set_audit_trace(Some(trace.clone()));
+                }
+                Some(trace)
+            }
+            None => None,
+        };
 
-        let result =
-            Self::load_main_module(b_conf, &mut store, &mut linker, &run_target, &entry).await;
-        let exit_code = match result {
-            Err(ref t) => {
-                Self::error_process(is_component, t, || store.get_fuel().unwrap(), max_fuel)
+        let mut result = self.instantiate_and_run(&b_conf, &mut store).await;
+        // the first failed thread stops the run, its error is the result of the run.
+        if let Some(e) = store
+            .data()
+            .wasi_threads
+            .as_ref()
+            .and_then(|t| t.take_error())
+        {
+            result = Err(e);
+        }
+        if let Some((profiler, path)) = profiler.zip(b_conf.profile_ref()) {
+            if let Err(e) = profiler.finish(path) {
+                error!("{e:#}");
             }
+        }
+        if let Some(Err(e)) = audit_trace.map(|t| t.flush()) {
+            error!("failed to flush the audit trace: {e}");
+        }
+        let timeout = deadline.is_some_and(|d| d.is_timeout());
+        let fuel = store.get_fuel().ok();
+        let fuel_consumed = initial_fuel.zip(fuel).map(|(i, f)| i.saturating_sub(f));
+        let outcome = match result {
+            Err(ref e) => Self::error_process(
+                is_component,
+                e,
+                || fuel_consumed.unwrap_or_default(),
+                max_fuel,
+                timeout,
+            ),
             Ok(_) => {
                 debug!("program exit normal.");
-                0
+                RunOutcome::default()
             }
         };
-        Ok(ExitStatus {
-            fuel: store.get_fuel().ok(),
-            code: exit_code,
+        // the memories of the sandboxed modules are recorded by their limiters.
+        let limiters = std::iter::once(&store.data().limiter)
+            .chain(store.data().sandboxes.iter().map(|s| &s.limiter));
+        let (peak_memory, memory_limit_hit) = limiters.fold((0, false), |(peak, hit), l| {
+            (peak.max(l.peak_memory), hit || l.memory_limit_hit)
+        });
+        let limit_hit = match outcome.trap {
+            Some(RunTrap::OutOfFuel) => Some(LimitKind::Fuel),
+            Some(RunTrap::Timeout) => Some(LimitKind::Time),
+            Some(RunTrap::MemoryLimitExceeded) => Some(LimitKind::Memory),
+            _ if memory_limit_hit => Some(LimitKind::Memory),
+            _ => None,
+        };
+        let (results, error) = match result {
+            Ok(results) => (results, None),
+            Err(e) if outcome.guest_exit_code.is_none() => (None, Some(format!("{e:#}"))),
+            Err(_) => (None, None),
+        };
+        Ok(RunReport {
+            code: outcome.code,
+            guest_exit_code: outcome.guest_exit_code,
+            trap: outcome.trap,
+            fuel,
+            fuel_consumed,
+            peak_memory,
+            wall_time: started.elapsed(),
+            limit_hit,
+            error,
+            results,
+            stdout: store.data().stdout_capture.as_ref().map(|p| p.captured()),
+            stderr: store.data().stderr_capture.as_ref().map(|p| p.captured()),
         })
     }
 
-    fn preview1_setup(&self, ctx: &mut BlocklessContext) -> AnyResult<()> {
-        let mut builder = self.0.preview1_builder()?;
+    /// check the prepared modules can run in the deterministic mode of the run.
+    fn check_deterministic(&self, deterministic: &DeterministicConfig) -> AnyResult<()> {
+        if !self.engine.deterministic {
+            bail!("the deterministic mode is not enabled in the engine.");
+        }
+        if self.conf.feature_thread() {
+            bail!("the threads are not supported in the deterministic mode.");
+        }
+        let imports = match &self.target {
+            BlsRunTarget::Module(module) => {
+                let mut all = vec![module];
+                all.extend(self.modules.iter().map(|(_, m)| m));
+                deterministic::core_extension_imports(&all)
+            }
+            BlsRunTarget::Component(component) => {
+                deterministic::component_extension_imports(self.engine.engine(), component)
+            }
+        };
+        deterministic::check_run_extensions(self.conf.deterministic_ref(), deterministic, &imports)
+    }
+
+    /// the modules in the profile, the entry module and the library modules.
+    fn profiling_modules(&self, cfg: &BlocklessConfig) -> Vec<(String, Module)> {
+        let entry = self.target.unwrap_core();
+        let name = entry.name().unwrap_or(cfg.entry_ref()).to_string();
+        let mut modules = vec![(name, entry.clone())];
+        modules.extend(self.run_modules(cfg).unwrap_or_default());
+        modules
+    }
+
+    /// the prepared library modules of the run in the load order, the run can only
+    /// choose the modules compiled in the prepare.
+    fn run_modules(&self, cfg: &BlocklessConfig) -> AnyResult<Vec<(String, Module)>> {
+        let libs: Vec<_> = cfg
+            .modules_ref()
+            .iter()
+            .filter(|m| m.module_type == ModuleType::Module)
+            .collect();
+        if let Some(m) = libs
+            .iter()
+            .find(|m| !self.modules.iter().any(|(name, _)| name == &m.name))
+        {
+            bail!("the module `{}` is not compiled in the prepare.", m.name);
+        }
+        Ok(self
+            .modules
+            .iter()
+            .filter(|(name, _)| libs.iter().any(|m| &m.name == name))
+            .cloned()
+            .collect())
+    }
+
+    fn init_drivers(b_conf: &BlocklessConfig) {
+        // set the drivers root path, if not setting use exe file path.
+        let drivers_root_path = b_conf
+            .drivers_root_path_ref()
+            .map(|p| p.into())
+            .unwrap_or_else(|| {
+                let mut current_exe_path = env::current_exe().unwrap();
+                current_exe_path.pop();
+                String::from(current_exe_path.to_str().unwrap())
+            });
+        DriverConetxt::init_built_in_drivers(drivers_root_path);
+        Self::load_driver(b_conf.drivers_ref());
+    }
+
+    /// the stdout and stderr captured in memory are replaced with the pipes,
+    /// the pipes are stored in the context to read the captured output.
+    fn capture_stdio(b_conf: &BlocklessConfig, ctx: &mut BlocklessContext) -> BlocklessConfig {
+        let mut b_conf = b_conf.clone();
+        ctx.stdout_capture = b_conf.stdio.stdout.capture_pipe();
+        ctx.stderr_capture = b_conf.stdio.stderr.capture_pipe();
+        b_conf
+    }
+
+    fn preview1_setup(b_conf: &BlocklessConfig, ctx: &mut BlocklessContext) -> AnyResult<()> {
+        let b_conf = &Self::capture_stdio(b_conf, ctx);
+        let mut builder = b_conf.preview1_builder()?;
         let mut preview1_ctx = builder.build();
-        preview1_ctx.set_blockless_config(Some(self.0.clone()));
+        preview1_ctx.set_blockless_config(Some(b_conf.clone()));
         ctx.preview1_ctx = Some(preview1_ctx);
         Ok(())
     }
 
-    fn preview2_setup(&self, ctx: &mut BlocklessContext) -> AnyResult<()> {
-        let mut builder = self.0.preview2_builder()?;
-        builder.inherit_stdio().args(&self.0.stdin_args);
-        builder.envs(&self.0.envs);
+    fn preview2_setup(b_conf: &BlocklessConfig, ctx: &mut BlocklessContext) -> AnyResult<()> {
+        let b_conf = &Self::capture_stdio(b_conf, ctx);
+        let mut builder = b_conf.preview2_builder()?;
+        builder.args(&b_conf.stdin_args);
+        builder.envs(&b_conf.envs);
         let preview2_ctx = builder.build_p1();
         ctx.preview2_ctx = Some(Arc::new(Mutex::new(preview2_ctx)));
+        // the blockless extensions of components.
+        let perms = BlsRuntimePermissionsContainer::new_with_env_cwd(None);
+        perms.set_permissions_config(&b_conf.permissions_config)?;
+        let stdin = b_conf.fix_stdin_ref().unwrap_or_default().to_string();
+        let drivers_root_path = b_conf.drivers_root_path_ref().map(String::from);
+        ctx.http_perms = Some(perms.clone());
+        let wit_ctx = BlocklessWitCtx::new(perms, stdin, drivers_root_path);
+        ctx.blockless_wit = Some(Arc::new(Mutex::new(wit_ctx)));
         Ok(())
     }
 
     fn collect_preloaded_nn_graphs(
-        &self,
+        b_conf: &BlocklessConfig,
     ) -> AnyResult<(Vec<wasmtime_wasi_nn::Backend>, wasmtime_wasi_nn::Registry)> {
-        let graphs = self
-            .0
+        let graphs = b_conf
             .nn_graph
             .iter()
             .map(|g| (g.format.clone(), g.dir.clone()))
@@ -444,7 +1036,6 @@ impl BlocklessRunner {
         name: &str,
         path: &str,
     ) -> AnyResult<()> {
-        use std::fs::File;
         use std::io::Write;
 
         let core_dump = err
@@ -461,59 +1052,49 @@ impl BlocklessRunner {
         Ok(())
     }
 
-    async fn load_main_module(
+    /// instantiate the prepared target in the store and call the entry,
+    /// return the results of the export if the export is invoked.
+    async fn instantiate_and_run(
+        &self,
         cfg: &BlocklessConfig,
         store: &mut Store<BlocklessContext>,
-        linker: &mut BlsLinker,
-        module: &BlsRunTarget,
-        entry: &str,
-    ) -> AnyResult<()> {
-        // The main module might be allowed to have unknown imports, which
-        // should be defined as traps:
-        if cfg.unknown_imports_trap {
-            match linker {
-                BlsLinker::Core(linker) => {
-                    linker.define_unknown_imports_as_traps(module.unwrap_core())?;
+    ) -> AnyResult<Option<Vec<String>>> {
+        match &self.prepared {
+            PreparedTarget::Module(instance_pre) => {
+                let instance = instance_pre.instantiate_async(&mut *store).await?;
+                self.call_entry(cfg, store, instance).await
+            }
+            PreparedTarget::Linker(linker) => {
+                let mut linker = linker.clone();
+                let module = self.target.unwrap_core();
+                let modules = match cfg.version() {
+                    BlocklessConfigVersion::Version1 => Some(self.run_modules(cfg)?),
+                    BlocklessConfigVersion::Version0 => None,
+                };
+                // support thread, the threads link the library modules in their own stores.
+                if self.conf.feature_thread() {
+                    self.preview1_setup_thread_support(&mut linker, store, module, &modules)?;
                 }
-                BlsLinker::Component(linker) => {
-                    linker.define_unknown_imports_as_traps(module.unwrap_component())?;
+                if let Some(modules) = modules {
+                    let mut module_linker = ModuleLinker::new(&mut linker, store);
+                    module_linker
+                        .link_modules(&modules)
+                        .await
+                        .context("link the modules fail.")?;
                 }
-            }
-        }
-
-        let result = match linker {
-            BlsLinker::Core(linker) => {
-                let module = module.unwrap_core();
-                let instance = linker.instantiate_async(&mut *store, module).await?;
-
-                // If `_initialize` is present, meaning a reactor, then invoke the function.
-                if let Some(func) = instance.get_func(&mut *store, "_initialize") {
-                    let init = func.typed::<(), ()>(&store)?;
-                    init.call_async(&mut *store, ()).await?;
+                // The main module might be allowed to have unknown imports, which
+                // should be defined as traps:
+                if self.conf.unknown_imports_trap {
+                    linker.define_unknown_imports_as_traps(module)?;
                 }
-                // Look for the specific function provided or otherwise look for
-                // "" or "_start" exports to run as a "main" function.
-                let func = match cfg.version {
-                    BlocklessConfigVersion::Version0 => instance
-                        .get_typed_func(&mut *store, entry)
-                        .or_else(|_| instance.get_typed_func::<(), ()>(&mut *store, ""))
-                        .or_else(|_| instance.get_typed_func::<(), ()>(&mut *store, ENTRY))?,
-                    BlocklessConfigVersion::Version1 => {
-                        instance.get_typed_func::<(), ()>(&mut *store, entry)?
-                    }
-                };
-                // if thread multi thread use sync model.
-                // The multi-thread model is used for the cpu intensive program.
-                func.call_async(&mut *store, ()).await
+                let instance = linker.instantiate_async(&mut *store, module).await?;
+                self.call_entry(cfg, store, instance).await
             }
-            BlsLinker::Component(linker) => {
-                let component = module.unwrap_component();
-                let command = wasmtime_wasi::bindings::Command::instantiate_async(
-                    &mut *store,
-                    component,
-                    linker,
-                )
-                .await?;
+            PreparedTarget::Component(_) if cfg.invoke_ref().is_some() => {
+                bail!("the invoke of the component must be set in the prepare.")
+            }
+            PreparedTarget::Component(command_pre) => {
+                let command = command_pre.instantiate_async(&mut *store).await?;
                 let result = command
                     .wasi_cli_run()
                     .call_run(&mut *store)
@@ -523,29 +1104,70 @@ impl BlocklessRunner {
                 // Translate the `Result<(),()>` produced by wasm into a feigned
                 // explicit exit here with status 1 if `Err(())` is returned.
                 result.and_then(|wasm_result| match wasm_result {
-                    Ok(()) => Ok(()),
+                    Ok(()) => Ok(None),
                     Err(()) => Err(wasmtime_wasi::I32Exit(1).into()),
                 })
             }
-        };
-        result
+            PreparedTarget::ComponentInstance(instance_pre) => {
+                let instance = instance_pre.instantiate_async(&mut *store).await?;
+                // the component is only prepared as the instance when invoking.
+                let invoke = cfg
+                    .invoke_ref()
+                    .context("the component is prepared to invoke, but the run has no invoke.")?;
+                invoke::invoke_component(store, &instance, invoke)
+                    .await
+                    .with_context(|| format!("failed to invoke `{}`", invoke.name))
+                    .map(Some)
+                    .map_err(|e| Self::handle_core_dump(cfg, &mut *store, e))
+            }
+        }
     }
 
-    fn nn_setup(
+    async fn call_entry(
         &self,
-        linker: &mut BlsLinker,
+        cfg: &BlocklessConfig,
         store: &mut Store<BlocklessContext>,
-    ) -> AnyResult<()> {
-        let (backends, registry) = self.collect_preloaded_nn_graphs()?;
+        instance: Instance,
+    ) -> AnyResult<Option<Vec<String>>> {
+        // If `_initialize` is present, meaning a reactor, then invoke the function.
+        if let Some(func) = instance.get_func(&mut *store, "_initialize") {
+            let init = func.typed::<(), ()>(&store)?;
+            init.call_async(&mut *store, ()).await?;
+        }
+        // invoke the export instead of the entry.
+        if let Some(invoke) = cfg.invoke_ref() {
+            return invoke::invoke_core(store, instance, invoke)
+                .await
+                .with_context(|| format!("failed to invoke `{}`", invoke.name))
+                .map(Some);
+        }
+        // Look for the specific function provided or otherwise look for
+        // "" or "_start" exports to run as a "main" function.
+        let func = match cfg.version() {
+            BlocklessConfigVersion::Version0 => instance
+                .get_typed_func::<(), ()>(&mut *store, ENTRY)
+                .or_else(|_| instance.get_typed_func::<(), ()>(&mut *store, ""))?,
+            BlocklessConfigVersion::Version1 => {
+                let entry = match cfg.entry_ref() {
+                    "" => ENTRY,
+                    entry => entry,
+                };
+                instance.get_typed_func::<(), ()>(&mut *store, entry)?
+            }
+        };
+        // if thread multi thread use sync model.
+        // The multi-thread model is used for the cpu intensive program.
+        func.call_async(&mut *store, ()).await?;
+        Ok(None)
+    }
+
+    fn nn_linker_setup(linker: &mut BlsLinker) -> AnyResult<()> {
         match linker {
             BlsLinker::Core(linker) => {
                 wasmtime_wasi_nn::witx::add_to_linker(linker, |host| {
                     Arc::get_mut(host.wasi_nn_witx.as_mut().unwrap())
                         .expect("wasi-nn is not implemented with multi-threading support")
                 })?;
-                store.data_mut().wasi_nn_witx = Some(Arc::new(
-                    wasmtime_wasi_nn::witx::WasiNnCtx::new(backends, registry),
-                ));
             }
             BlsLinker::Component(linker) => {
                 wasmtime_wasi_nn::wit::add_to_linker(linker, |h: &mut BlocklessContext| {
@@ -558,14 +1180,29 @@ impl BlocklessRunner {
                         .expect("wasi-nn is not implemented with multi-threading support");
                     WasiNnView::new(preview2_ctx.table(), nn_ctx)
                 })?;
-                store.data_mut().wasi_nn_wit = Some(Arc::new(
-                    wasmtime_wasi_nn::wit::WasiNnCtx::new(backends, registry),
-                ));
             }
         }
         Ok(())
     }
 
+    fn nn_store_setup(
+        b_conf: &BlocklessConfig,
+        store: &mut Store<BlocklessContext>,
+        is_component: bool,
+    ) -> AnyResult<()> {
+        let (backends, registry) = Self::collect_preloaded_nn_graphs(b_conf)?;
+        if is_component {
+            store.data_mut().wasi_nn_wit = Some(Arc::new(wasmtime_wasi_nn::wit::WasiNnCtx::new(
+                backends, registry,
+            )));
+        } else {
+            store.data_mut().wasi_nn_witx = Some(Arc::new(wasmtime_wasi_nn::witx::WasiNnCtx::new(
+                backends, registry,
+            )));
+        }
+        Ok(())
+    }
+
     fn handle_core_dump(
         cfg: &BlocklessConfig,
         store: &mut Store<BlocklessContext>,
@@ -578,7 +1215,11 @@ impl BlocklessRunner {
         if !err.is::<wasmtime::Trap>() {
             return err;
         }
-        let source_name = cfg.modules[0].file.as_str();
+        let source_name = cfg
+            .modules
+            .first()
+            .map(|m| m.file.as_str())
+            .unwrap_or(cfg.entry_ref());
 
         if let Err(coredump_err) = Self::write_core_dump(store, &err, source_name, coredump_path) {
             eprintln!("warning: coredump failed to generate: {coredump_err}");
@@ -588,128 +1229,48 @@ impl BlocklessRunner {
         }
     }
 
-    pub fn load_module<T: AsRef<Path>>(engine: &Engine, path: T) -> AnyResult<BlsRunTarget> {
-        let path: &Path = match path.as_ref().to_str() {
-            #[cfg(unix)]
-            Some("-") => "/dev/stdin".as_ref(),
-            _ => path.as_ref(),
-        };
-        let file = File::open(path)?;
-        match wasmtime::_internal::MmapVec::from_file(file) {
-            Ok(map) => Self::load_module_contents(
-                engine,
-                path,
-                &map,
-                || unsafe { Module::deserialize_file(engine, path) },
-                || unsafe { Component::deserialize_file(engine, path) },
-            ),
-            Err(_) => {
-                let bytes = std::fs::read(path)
-                    .with_context(|| format!("failed to read file: {}", path.display()))?;
-                Self::load_module_contents(
-                    engine,
-                    path,
-                    &bytes,
-                    || unsafe { Module::deserialize(engine, &bytes) },
-                    || unsafe { Component::deserialize(engine, &bytes) },
-                )
-            }
-        }
-    }
-
-    pub fn load_module_contents(
-        engine: &Engine,
-        path: &Path,
-        bytes: &[u8],
-        deserialize_module: impl FnOnce() -> AnyResult<Module>,
-        deserialize_component: impl FnOnce() -> AnyResult<Component>,
-    ) -> AnyResult<BlsRunTarget> {
-        Ok(match engine.detect_precompiled(bytes) {
-            Some(Precompiled::Module) => BlsRunTarget::Module(deserialize_module()?),
-            Some(Precompiled::Component) => BlsRunTarget::Component(deserialize_component()?),
-            None => {
-                let mut code = wasmtime::CodeBuilder::new(engine);
-                code.wasm_binary_or_text(bytes, Some(path))?;
-                match code.hint() {
-                    Some(wasmtime::CodeHint::Component) => {
-                        BlsRunTarget::Component(code.compile_component()?)
-                    }
-                    Some(wasmtime::CodeHint::Module) | None => {
-                        BlsRunTarget::Module(code.compile_module()?)
-                    }
-                }
-            }
-        })
-    }
-
-    fn preview1_linker_setup(linker: &mut Linker<BlocklessContext>) {
+    /// link the wasi and the extensions accepted by `allowed`, e.g. `http`.
+    fn preview1_linker_setup(
+        linker: &mut Linker<BlocklessContext>,
+        allowed: impl Fn(&str) -> bool,
+    ) {
         // define the macro of extends.
         macro_rules! add_to_linker {
-            ($method:expr) => {
-                $method(linker, |s| s.preview1_ctx.as_mut().unwrap()).unwrap()
+            ($name:literal, $method:expr) => {
+                if allowed($name) {
+                    $method(linker, |s| s.running_preview1_ctx()).unwrap()
+                }
             };
         }
-        add_to_linker!(blockless_env::add_drivers_to_linker);
-        add_to_linker!(blockless_env::add_http_to_linker);
-        add_to_linker!(blockless_env::add_llm_to_linker);
-        add_to_linker!(blockless_env::add_ipfs_to_linker);
-        add_to_linker!(blockless_env::add_s3_to_linker);
-        add_to_linker!(blockless_env::add_memory_to_linker);
-        add_to_linker!(blockless_env::add_cgi_to_linker);
-        add_to_linker!(blockless_env::add_socket_to_linker);
-        wasi_common::sync::add_to_linker(linker, |host| host.preview1_ctx.as_mut().unwrap())
-            .unwrap();
+        add_to_linker!("drivers", blockless_env::add_drivers_to_linker);
+        add_to_linker!("http", blockless_env::add_http_to_linker);
+        add_to_linker!("llm", blockless_env::add_llm_to_linker);
+        add_to_linker!("ipfs", blockless_env::add_ipfs_to_linker);
+        add_to_linker!("s3", blockless_env::add_s3_to_linker);
+        add_to_linker!("memory", blockless_env::add_memory_to_linker);
+        add_to_linker!("cgi", blockless_env::add_cgi_to_linker);
+        add_to_linker!("socket", blockless_env::add_socket_to_linker);
+        wasi_common::sync::add_to_linker(linker, |host| host.running_preview1_ctx()).unwrap();
     }
 
+    /// the `linker` must not have the library modules linked, the threads link them.
     fn preview1_setup_thread_support(
+        &self,
         linker: &mut Linker<BlocklessContext>,
         store: &mut Store<BlocklessContext>,
         module: &Module,
-    ) {
-        wasmtime_wasi_threads::add_to_linker(linker, store, module, |ctx| {
-            ctx.wasi_threads.as_ref().unwrap()
-        })
-        .unwrap();
-        store.data_mut().wasi_threads = Some(Arc::new(
-            WasiThreadsCtx::new(module.clone(), Arc::new(linker.clone()))
-                .expect("wasi thread ctx new fail."),
-        ));
-    }
-
-    async fn module_linker(
-        &self,
-        mut entry: String,
-        engine: &Engine,
-        store: &'_ mut Store<BlocklessContext>,
-    ) -> anyhow::Result<(BlsLinker, BlsRunTarget, String)> {
-        let version = self.0.version();
-        match version {
-            // this is older configure for bls-runtime, this only run single wasm.
-            BlocklessConfigVersion::Version0 => {
-                let module = Self::load_module(engine, &entry)?;
-                let linker = match module {
-                    BlsRunTarget::Module(_) => {
-                        self.preview1_setup(store.data_mut())?;
-                        BlsLinker::Core(wasmtime::Linker::new(engine))
-                    }
-                    BlsRunTarget::Component(_) => {
-                        BlsLinker::Component(wasmtime::component::Linker::new(engine))
-                    }
-                };
-                Ok((linker, module, ENTRY.to_string()))
-            }
-            BlocklessConfigVersion::Version1 => {
-                if entry.is_empty() {
-                    entry = ENTRY.to_string();
-                }
-                // must setup before link_modules.
-                self.preview1_setup(store.data_mut())?;
-                let mut linker = wasmtime::Linker::new(engine);
-                let mut module_linker = ModuleLinker::new(&mut linker, store);
-                let module = module_linker.link_modules().await.context("")?;
-                Ok((BlsLinker::Core(linker), BlsRunTarget::Module(module), entry))
-            }
-        }
+        modules: &Option<Vec<(String, Module)>>,
+    ) -> AnyResult<()> {
+        threads::add_to_linker(linker, store, module)?;
+        let threads_ctx = BlocklessThreadsCtx::new(
+            module,
+            linker,
+            modules.clone(),
+            self.conf.unknown_imports_trap,
+            self.conf.max_threads(),
+        );
+        store.data_mut().wasi_threads = Some(Arc::new(threads_ctx));
+        Ok(())
     }
 
     fn load_driver(cfs: &[DriverConfig]) {
@@ -719,54 +1280,42 @@ impl BlocklessRunner {
         });
     }
 
-    /// the error code process.
+    /// the error code process, classify the error of the run.
+    /// the guest exit is not an error, the exit code is returned to caller.
     fn error_process<F>(
         is_component: bool,
         e: &anyhow::Error,
         used_fuel: F,
         max_fuel: Option<u64>,
-    ) -> i32
+        timeout: bool,
+    ) -> RunOutcome
     where
         F: FnOnce() -> u64,
     {
-        if is_component {
-            if let Some(exit) = e.downcast_ref::<wasmtime_wasi::I32Exit>() {
-                std::process::exit(exit.0);
-            }
-        } else if let Some(exit) = e.downcast_ref::<wasi_common::I32Exit>() {
-            std::process::exit(exit.0);
-        }
-        let trap_code_2_exit_code = |trap_code: &Trap| -> Option<i32> {
-            match *trap_code {
-                Trap::OutOfFuel => Some(1),
-                Trap::StackOverflow => Some(2),
-                Trap::MemoryOutOfBounds => Some(3),
-                Trap::HeapMisaligned => Some(4),
-                Trap::TableOutOfBounds => Some(5),
-                Trap::IndirectCallToNull => Some(6),
-                Trap::BadSignature => Some(7),
-                Trap::IntegerOverflow => Some(8),
-                Trap::IntegerDivisionByZero => Some(9),
-                Trap::BadConversionToInteger => Some(10),
-                Trap::UnreachableCodeReached => Some(11),
-                Trap::Interrupt => Some(12),
-                Trap::AlwaysTrapAdapter => Some(13),
-                _ => None,
-            }
+        let guest_exit_code = if is_component {
+            e.downcast_ref::<wasmtime_wasi::I32Exit>()
+                .map(|exit| exit.0)
+        } else {
+            e.downcast_ref::<wasi_common::I32Exit>().map(|exit| exit.0)
         };
-        let trap = e.downcast_ref::<Trap>();
-        let rs = trap
-            .and_then(trap_code_2_exit_code)
+        if let Some(code) = guest_exit_code {
+            debug!("program exit with code {code}.");
+            return RunOutcome {
+                code,
+                guest_exit_code,
+                trap: None,
+            };
+        }
+        let trap = e
+            .downcast_ref::<Trap>()
+            .map(|trap| RunTrap::from_trap(trap, timeout))
             .or_else(|| {
-                if e.to_string().starts_with("memory minimum size of") {
-                    Some(16)
-                } else {
-                    None
-                }
-            })
-            .unwrap_or(-1);
+                e.to_string()
+                    .starts_with("memory minimum size of")
+                    .then_some(RunTrap::MemoryLimitExceeded)
+            });
         match trap {
-            Some(Trap::OutOfFuel) => {
+            Some(RunTrap::OutOfFuel) => {
                 let used_fuel = used_fuel();
                 let max_fuel = max_fuel.unwrap_or_default();
                 error!(
@@ -774,14 +1323,31 @@ impl BlocklessRunner {
                     used_fuel, max_fuel
                 );
             }
+            Some(RunTrap::Timeout) => {
+                error!("The execution time limit is reached, the app exited.");
+            }
             _ => error!("error: {}", e),
         };
-        rs
+        RunOutcome {
+            code: trap.map_or(-1, |t| t.exit_code()),
+            guest_exit_code: None,
+            trap,
+        }
     }
 }
 
-pub async fn blockless_run(b_conf: BlocklessConfig) -> anyhow::Result<ExitStatus> {
-    BlocklessRunner(b_conf).run().await
+/// the outcome of the run, the guest exit code or the trap.
+#[derive(Default)]
+struct RunOutcome {
+    code: i32,
+    guest_exit_code: Option<i32>,
+    trap: Option<RunTrap>,
+}
+
+pub async fn blockless_run(b_conf: BlocklessConfig) -> anyhow::Result<RunReport> {
+    let engine = BlocklessEngine::new(&b_conf)?;
+    let runner = engine.prepare(&b_conf)?;
+    runner.run(b_conf).await
 }
 
 #[cfg(test)]
@@ -793,7 +1359,76 @@ mod test {
     #[test]
     fn test_exit_code() {
         let err = Trap::OutOfFuel.into();
-        let rs = BlocklessRunner::error_process(false, &err, || 20u64, Some(30));
-        assert_eq!(rs, 1);
+        let rs = BlocklessRunner::error_process(false, &err, || 20u64, Some(30), false);
+        assert_eq!(rs.code, 1);
+        assert_eq!(rs.trap, Some(RunTrap::OutOfFuel));
+    }
+
+    #[test]
+    fn test_timeout_exit_code() {
+        let err = Trap::Interrupt.into();
+        let rs = BlocklessRunner::error_process(false, &err, || 0u64, None, true);
+        assert_eq!(rs.code, 15);
+        assert_eq!(rs.trap, Some(RunTrap::Timeout));
+        let rs = BlocklessRunner::error_process(false, &err, || 0u64, None, false);
+        assert_eq!(rs.code, 12);
+        assert_eq!(rs.trap, Some(RunTrap::Interrupt));
+    }
+
+    #[test]
+    fn test_guest_exit_code() {
+        let err = wasi_common::I32Exit(42).into();
+        let rs = BlocklessRunner::error_process(false, &err, || 0u64, None, false);
+        assert_eq!(rs.code, 42);
+        assert_eq!(rs.guest_exit_code, Some(42));
+        assert_eq!(rs.trap, None);
+        let err = wasmtime_wasi::I32Exit(3).into();
+        let rs = BlocklessRunner::error_process(true, &err, || 0u64, None, false);
+        assert_eq!(rs.guest_exit_code, Some(3));
+    }
+
+    #[test]
+    fn test_preview2_dir_perms() {
+        let perms = BlsRuntimePermissionsContainer::new_with_env_cwd(None);
+        // the permission to prompt is not granted.
+        assert!(preview2_dir_perms(&perms, "/").is_none());
+        let mut config = PermissionsConfig {
+            allow_read: Some(PermissionGrant::All),
+            deny_write: Some(PermissionGrant::All),
+            ..Default::default()
+        };
+        perms.set_permissions_config(&config).unwrap();
+        let (dir_perms, file_perms) = preview2_dir_perms(&perms, "/").unwrap();
+        assert_eq!(dir_perms, DirPerms::READ);
+        assert_eq!(file_perms, FilePerms::READ);
+
+        config.allow_read = None;
+        config.deny_read = Some(PermissionGrant::All);
+        perms.set_permissions_config(&config).unwrap();
+        assert!(preview2_dir_perms(&perms, "/").is_none());
+
+        perms.allow_all();
+        let (dir_perms, file_perms) = preview2_dir_perms(&perms, "/").unwrap();
+        assert_eq!(dir_perms, DirPerms::all());
+        assert_eq!(file_perms, FilePerms::all());
+    }
+
+    #[test]
+    fn test_net_granted() {
+        let perms = BlsRuntimePermissionsContainer::new_with_env_cwd(None);
+        // the permission to prompt is not granted.
+        assert!(!perms.is_net_granted("127.0.0.1", Some(8080)));
+        let config = PermissionsConfig {
+            allow_net: Some(PermissionGrant::List(vec![
+                "127.0.0.1:8080".into(),
+                "[::1]:8080".into(),
+            ])),
+            ..Default::default()
+        };
+        perms.set_permissions_config(&config).unwrap();
+        assert!(perms.is_net_granted("127.0.0.1", Some(8080)));
+        assert!(!perms.is_net_granted("127.0.0.1", Some(8081)));
+        assert!(perms.is_net_granted("::1", Some(8080)));
+        assert!(perms.is_net_granted("[::1]", Some(8080)));
     }
 }
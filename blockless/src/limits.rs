@@ -0,0 +1,72 @@
+use anyhow::Result;
+use wasmtime::{ResourceLimiter, StoreLimits};
+
+/// The store limiter, it apply the `StoreLimits` and record the
+/// memory usage of the store for the run report.
+#[derive(Clone, Default)]
+pub(crate) struct BlocklessLimiter {
+    limits: StoreLimits,
+    max_memory_size: Option<usize>,
+    /// the peak size in bytes of the linear memories.
+    pub(crate) peak_memory: usize,
+    /// the memory growth is refused by the `max_memory_size`.
+    pub(crate) memory_limit_hit: bool,
+}
+
+impl BlocklessLimiter {
+    pub(crate) fn new(limits: StoreLimits, max_memory_size: Option<usize>) -> Self {
+        Self {
+            limits,
+            max_memory_size,
+            peak_memory: 0,
+            memory_limit_hit: false,
+        }
+    }
+}
+
+impl ResourceLimiter for BlocklessLimiter {
+    fn memory_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> Result<bool> {
+        if self.max_memory_size.is_some_and(|max| desired > max) {
+            self.memory_limit_hit = true;
+        }
+        let allow = self.limits.memory_growing(current, desired, maximum);
+        if let Ok(true) = allow {
+            self.peak_memory = self.peak_memory.max(desired);
+        }
+        allow
+    }
+
+    fn memory_grow_failed(&mut self, error: anyhow::Error) -> Result<()> {
+        self.limits.memory_grow_failed(error)
+    }
+
+    fn table_growing(
+        &mut self,
+        current: usize,
+        desired: usize,
+        maximum: Option<usize>,
+    ) -> Result<bool> {
+        self.limits.table_growing(current, desired, maximum)
+    }
+
+    fn table_grow_failed(&mut self, error: anyhow::Error) -> Result<()> {
+        self.limits.table_grow_failed(error)
+    }
+
+    fn instances(&self) -> usize {
+        self.limits.instances()
+    }
+
+    fn tables(&self) -> usize {
+        self.limits.tables()
+    }
+
+    fn memories(&self) -> usize {
+        self.limits.memories()
+    }
+}
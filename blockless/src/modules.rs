@@ -1,26 +1,22 @@
-use anyhow::{Context, anyhow};
+use anyhow::{Context, bail};
 use json::JsonValue;
-use lazy_static::lazy_static;
 use std::future::Future;
 use std::sync::Arc;
 use std::{cmp::min, collections::HashMap};
-use tokio::sync::Mutex;
-use wasi_common::{BlocklessModule, ModuleType};
+use wasi_common::{BlocklessConfig, BlocklessModule};
 use wasmtime::{
-    AsContext, AsContextMut, Caller, Extern, Func, Linker, Memory, Module, Store, StoreContextMut,
-    TypedFunc,
+    AsContext, AsContextMut, Caller, Extern, ExternType, Func, ImportType, Linker, Memory,
+    MemoryAccessError, Module, Store, StoreContextMut, TypedFunc,
 };
 
 use crate::context::BlocklessContext as BSContext;
 use crate::error::McallError;
+use crate::sandbox;
 
-lazy_static! {
-    static ref INS_CTX: Mutex<InstanceCtx> = Mutex::new(InstanceCtx::new());
-}
-
-struct InstanceCtx {
-    //key is mem, value is the register module name,
-    modules: HashMap<usize, String>,
+/// The linked modules of the run, it's kept in the store data, so the registrations
+/// of the run are invisible to the other runs and released with the store.
+#[derive(Clone, Default)]
+pub(crate) struct InstanceCtx {
     //key is module::method.
     module_caller: HashMap<String, InstanceCaller>,
     //key is module name.
@@ -28,12 +24,13 @@ struct InstanceCtx {
 }
 
 impl InstanceCtx {
-    fn new() -> Self {
-        Self {
-            modules: HashMap::new(),
-            module_caller: HashMap::new(),
-            instance_infos: HashMap::new(),
-        }
+    /// the exported function of the linked library module, the function enters the module when called.
+    pub(crate) fn export_func(&self, module: &str, name: &str) -> Option<Func> {
+        self.instance_infos
+            .get(module)?
+            .export_funcs
+            .get(name)
+            .copied()
     }
 }
 
@@ -41,6 +38,39 @@ type AllocTypedFunc = TypedFunc<u32, i32>;
 type DeallocTypedFunc = TypedFunc<(i32, u32), ()>;
 type CallerTypedFunc = TypedFunc<(i32, u32, i32, u32), u32>;
 
+/// the size of the result slot `{ptr: u32, len: u32}` and the iovec `{buf: u32, buf_len: u32}`.
+const MCALL_SLOT_SIZE: u32 = 8;
+
+/// the max result buffers of the binary mcall.
+const MCALL_MAX_RESULTS: u32 = 64;
+
+/// The calling convention of the registered method, it's selected by the
+/// `convention` of the registration.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+enum McallConvention {
+    /// the JSON text through `blockless.mcall`.
+    #[default]
+    Json,
+    /// the CBOR encoded params through `blockless.mcall_bin`.
+    Cbor,
+    /// the raw bytes through `blockless.mcall_bin`.
+    Raw,
+}
+
+impl std::str::FromStr for McallConvention {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(McallConvention::Json),
+            "cbor" => Ok(McallConvention::Cbor),
+            "raw" => Ok(McallConvention::Raw),
+            _ => anyhow::bail!("unknown mcall convention: {s}"),
+        }
+    }
+}
+
+#[derive(Clone)]
 struct InstanceInfo {
     mem: Option<Memory>,
     alloc: Option<Arc<AllocTypedFunc>>,
@@ -52,6 +82,7 @@ impl InstanceInfo {
     fn instance_caller(
         &self,
         method: &str,
+        convention: McallConvention,
         store: impl AsContext<Data = BSContext>,
     ) -> anyhow::Result<InstanceCaller> {
         let export_func = self
@@ -78,15 +109,84 @@ impl InstanceInfo {
             func,
             alloc,
             dealloc,
+            convention,
         })
     }
 }
 
+#[derive(Clone)]
 struct InstanceCaller {
     mem: Memory,
     alloc: Arc<AllocTypedFunc>,
     dealloc: Arc<DeallocTypedFunc>,
     func: Arc<CallerTypedFunc>,
+    convention: McallConvention,
+}
+
+/// The failure of the binary mcall, it's written to the error buffer of the caller.
+#[derive(Debug)]
+struct McallFailure {
+    code: McallError,
+    message: String,
+}
+
+impl McallFailure {
+    fn new(code: McallError, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// write the code in little endian and the message to the error buffer,
+    /// the message is truncated to the buffer.
+    fn response(
+        &self,
+        mem: &Memory,
+        store: impl AsContextMut<Data = BSContext>,
+        ptr: u32,
+        len: u32,
+    ) {
+        if len < 4 {
+            return;
+        }
+        let mut bs = u32::from(self.code).to_le_bytes().to_vec();
+        bs.extend_from_slice(self.message.as_bytes());
+        bs.truncate(len as usize);
+        let _ = mem.write(store, ptr as usize, &bs);
+    }
+}
+
+/// the arguments of `blockless.mcall_bin`.
+struct McallBinArgs {
+    name: u32,
+    name_len: u32,
+    params: u32,
+    params_len: u32,
+    iovs: u32,
+    iovs_len: u32,
+    nwritten: u32,
+    err: u32,
+    err_len: u32,
+}
+
+/// borrow the `len` bytes at `ptr` of the memory data, `None` if it's out of the memory.
+fn guest_bytes(data: &[u8], ptr: u32, len: u32) -> Option<&[u8]> {
+    let start = ptr as usize;
+    data.get(start..start.checked_add(len as usize)?)
+}
+
+fn out_of_memory(code: McallError, what: &str) -> McallFailure {
+    McallFailure::new(code, format!("the {what} is out of the memory."))
+}
+
+/// read the `(u32, u32)` pairs of the result slots or the iovecs.
+fn u32_pairs(bs: &[u8]) -> impl Iterator<Item = (u32, u32)> + '_ {
+    bs.chunks_exact(MCALL_SLOT_SIZE as usize).map(|pair| {
+        let first = u32::from_le_bytes([pair[0], pair[1], pair[2], pair[3]]);
+        let second = u32::from_le_bytes([pair[4], pair[5], pair[6], pair[7]]);
+        (first, second)
+    })
 }
 
 struct MemBuf<'a> {
@@ -199,11 +299,144 @@ impl InstanceCaller {
         }
         result.into()
     }
+
+    /// call the module function registered with the binary convention, the params
+    /// are copied to the callee as is, the callee fills at most `results` slots
+    /// `{ptr: u32, len: u32}` with the buffers of its own alloc and returns 0,
+    /// the buffers are released by the dealloc after copied out.
+    async fn call_bin(
+        &self,
+        mut store: impl AsContextMut<Data = BSContext>,
+        params: &[u8],
+        results: u32,
+    ) -> Result<Vec<Vec<u8>>, McallFailure> {
+        let params_len = params.len() as u32;
+        let slots_len = results
+            .checked_mul(MCALL_SLOT_SIZE)
+            .ok_or_else(|| McallFailure::new(McallError::Fail, "too many result buffers."))?;
+        let params_ptr = self
+            .alloc
+            .call_async(store.as_context_mut(), params_len)
+            .await
+            .map_err(|e| McallFailure::new(McallError::AllocError, e.to_string()))?;
+        let slots_ptr = match self
+            .alloc
+            .call_async(store.as_context_mut(), slots_len)
+            .await
+        {
+            Ok(ptr) => ptr,
+            Err(e) => {
+                let _ = self
+                    .dealloc
+                    .call_async(store.as_context_mut(), (params_ptr, params_len))
+                    .await;
+                return Err(McallFailure::new(McallError::AllocError, e.to_string()));
+            }
+        };
+        let mut result = self
+            .invoke_bin(
+                store.as_context_mut(),
+                params,
+                params_ptr,
+                slots_ptr,
+                slots_len,
+            )
+            .await;
+        for (ptr, len) in [(params_ptr, params_len), (slots_ptr, slots_len)] {
+            let rs = self
+                .dealloc
+                .call_async(store.as_context_mut(), (ptr, len))
+                .await;
+            if let (Err(e), Ok(_)) = (rs, &result) {
+                result = Err(McallFailure::new(McallError::DeallocError, e.to_string()));
+            }
+        }
+        result
+    }
+
+    async fn invoke_bin(
+        &self,
+        mut store: impl AsContextMut<Data = BSContext>,
+        params: &[u8],
+        params_ptr: i32,
+        slots_ptr: i32,
+        slots_len: u32,
+    ) -> Result<Vec<Vec<u8>>, McallFailure> {
+        let mem_err = |e: MemoryAccessError| {
+            McallFailure::new(McallError::MCallError, format!("the callee memory: {e}"))
+        };
+        let results = slots_len / MCALL_SLOT_SIZE;
+        self.mem
+            .write(store.as_context_mut(), params_ptr as usize, params)
+            .map_err(mem_err)?;
+        self.mem
+            .write(
+                store.as_context_mut(),
+                slots_ptr as usize,
+                &vec![0u8; slots_len as usize],
+            )
+            .map_err(mem_err)?;
+        let status = self
+            .func
+            .call_async(
+                store.as_context_mut(),
+                (params_ptr, params.len() as u32, slots_ptr, results),
+            )
+            .await
+            .map_err(|e| McallFailure::new(McallError::MCallError, e.to_string()))?;
+        // the result buffers are released even if the method fails.
+        let slots = guest_bytes(
+            self.mem.data(store.as_context()),
+            slots_ptr as u32,
+            slots_len,
+        )
+        .ok_or_else(|| out_of_memory(McallError::MCallError, "result slots"))?
+        .to_vec();
+        let mut bufs = Vec::with_capacity(results as usize);
+        // every result buffer is released, the first failure is returned after all.
+        let mut failure = None;
+        for (ptr, len) in u32_pairs(&slots) {
+            if len == 0 {
+                bufs.push(Vec::new());
+                continue;
+            }
+            let buf = guest_bytes(self.mem.data(store.as_context()), ptr, len).map(<[u8]>::to_vec);
+            let rs = self
+                .dealloc
+                .call_async(store.as_context_mut(), (ptr as i32, len))
+                .await;
+            let failed = match (buf, rs) {
+                (Some(buf), Ok(())) => {
+                    bufs.push(buf);
+                    continue;
+                }
+                (None, _) => out_of_memory(McallError::MCallError, "result buffer"),
+                (_, Err(e)) => McallFailure::new(McallError::DeallocError, e.to_string()),
+            };
+            failure = failure.or(Some(failed));
+        }
+        if let Some(failure) = failure {
+            return Err(failure);
+        }
+        if status != 0 {
+            // the first result of the failed method is the error message.
+            let msg = bufs
+                .first()
+                .map(|b| String::from_utf8_lossy(b).into_owned())
+                .unwrap_or_default();
+            return Err(McallFailure::new(
+                McallError::MCallError,
+                format!("the method returned {status}: {msg}"),
+            ));
+        }
+        Ok(bufs)
+    }
 }
 
 struct RegisterReq {
     module: String,
     methods: Vec<String>,
+    convention: McallConvention,
 }
 
 fn process_register_req(module: &str, json_str: &str) -> anyhow::Result<RegisterReq> {
@@ -213,7 +446,15 @@ fn process_register_req(module: &str, json_str: &str) -> anyhow::Result<Register
         .members()
         .map(|m| m.to_string())
         .collect::<Vec<_>>();
-    Ok(RegisterReq { module, methods })
+    let convention = match json_obj["convention"].as_str() {
+        Some(c) => c.parse()?,
+        None => McallConvention::Json,
+    };
+    Ok(RegisterReq {
+        module,
+        methods,
+        convention,
+    })
 }
 
 struct ResponseErrorJson<'a> {
@@ -307,11 +548,20 @@ impl<'a> ModuleLinker<'a> {
                         responseError!(&emsg);
                     }
                 };
-                let ctx = INS_CTX.lock().await;
-                let mcaller = ctx.module_caller.get(&mcall_name);
+                let mcaller = caller
+                    .data()
+                    .instance_ctx
+                    .module_caller
+                    .get(&mcall_name)
+                    .cloned();
                 let Some(mcaller) = mcaller else {
                     responseError!("no mcall register.");
                 };
+                if mcaller.convention != McallConvention::Json {
+                    ResponseErrorJson::new(&mem, caller.as_context_mut(), buf, buf_len)
+                        .response("the method is registered with the binary convention.");
+                    return McallError::Encoding.into();
+                }
                 let dest_mem = MemBuf::new(&mem, buf, buf_len);
                 return mcaller
                     .call(caller.as_context_mut(), &params, dest_mem)
@@ -321,6 +571,99 @@ impl<'a> ModuleLinker<'a> {
         })
     }
 
+    /// async function for invoke the mcall of the binary convention.
+    #[inline]
+    fn mcall_bin_fn<'b>(
+        mut caller: Caller<'b, BSContext>,
+        args: McallBinArgs,
+    ) -> Box<dyn Future<Output = u32> + Send + 'b> {
+        Box::new(async move {
+            let Some(Extern::Memory(mem)) = caller.get_export("memory") else {
+                return McallError::MemoryNotFound.into();
+            };
+            match Self::mcall_bin(&mut caller, &mem, &args).await {
+                Ok(()) => McallError::None.into(),
+                Err(failure) => {
+                    failure.response(&mem, caller.as_context_mut(), args.err, args.err_len);
+                    failure.code.into()
+                }
+            }
+        })
+    }
+
+    /// the result `i` is copied to the iovec `i` of the caller, and the full
+    /// length of the result is written to `nwritten[i]`.
+    async fn mcall_bin(
+        caller: &mut Caller<'_, BSContext>,
+        mem: &Memory,
+        args: &McallBinArgs,
+    ) -> Result<(), McallFailure> {
+        let mem_err =
+            |e: MemoryAccessError| McallFailure::new(McallError::Fail, format!("the memory: {e}"));
+        if args.iovs_len > MCALL_MAX_RESULTS {
+            return Err(McallFailure::new(
+                McallError::Fail,
+                format!("at most {MCALL_MAX_RESULTS} result buffers."),
+            ));
+        }
+        // the buffers are borrowed from the memory first, so the lengths are bounded
+        // by the memory before they are copied.
+        let data = mem.data(caller.as_context());
+        let name = guest_bytes(data, args.name, args.name_len)
+            .ok_or_else(|| out_of_memory(McallError::Fail, "method name"))?;
+        let name = std::str::from_utf8(name)
+            .map_err(|_| McallFailure::new(McallError::Fail, "the method name is not UTF-8."))?
+            .to_string();
+        let params = guest_bytes(data, args.params, args.params_len)
+            .ok_or_else(|| out_of_memory(McallError::Fail, "params"))?
+            .to_vec();
+        // the `iovs_len` is capped by `MCALL_MAX_RESULTS`, so the size never overflows.
+        let iovs_size = args.iovs_len * MCALL_SLOT_SIZE;
+        let iovs = guest_bytes(data, args.iovs, iovs_size)
+            .ok_or_else(|| out_of_memory(McallError::Fail, "iovecs"))?
+            .to_vec();
+
+        let mcaller = caller
+            .data()
+            .instance_ctx
+            .module_caller
+            .get(&name)
+            .cloned()
+            .ok_or_else(|| {
+                McallFailure::new(McallError::Fail, format!("no mcall register: {name}"))
+            })?;
+        match mcaller.convention {
+            McallConvention::Json => {
+                return Err(McallFailure::new(
+                    McallError::Encoding,
+                    format!("{name} is registered with the json convention."),
+                ));
+            }
+            McallConvention::Cbor => {
+                if let Err(e) = ciborium::from_reader::<ciborium::Value, _>(params.as_slice()) {
+                    return Err(McallFailure::new(
+                        McallError::Encoding,
+                        format!("the params are not CBOR: {e}"),
+                    ));
+                }
+            }
+            McallConvention::Raw => {}
+        }
+        let results = mcaller
+            .call_bin(caller.as_context_mut(), &params, args.iovs_len)
+            .await?;
+        let mut nwritten = Vec::with_capacity(iovs.len() / 2);
+        for (result, (buf, buf_len)) in results.iter().zip(u32_pairs(&iovs)) {
+            let len = min(buf_len as usize, result.len());
+            mem.write(caller.as_context_mut(), buf as usize, &result[..len])
+                .map_err(mem_err)?;
+            nwritten.extend_from_slice(&(result.len() as u32).to_le_bytes());
+        }
+        mem.write(caller.as_context_mut(), args.nwritten as usize, &nwritten)
+            .map_err(mem_err)?;
+        Ok(())
+    }
+
     /// async function for register the mcall for modules.
     #[inline]
     fn register_fn<'b>(
@@ -349,10 +692,9 @@ impl<'a> ModuleLinker<'a> {
                         return McallError::Fail.into();
                     };
                 }
-                let mem_ptr = mem_slice.as_ptr() as usize;
-                let module = INS_CTX.lock().await.modules.get(&mem_ptr).map(String::from);
-                let module = match module {
-                    Some(m) => m,
+                // the registering module is the running library module, the entry can't register.
+                let module = match caller.data().module_stack.last() {
+                    Some(m) => m.to_string(),
                     None => return McallError::MCallMemoryNotFound.into(),
                 };
                 let req = match process_register_req(&module, str) {
@@ -361,23 +703,24 @@ impl<'a> ModuleLinker<'a> {
                         responseError!("error parse json");
                     }
                 };
-                let mut ctx = INS_CTX.lock().await;
                 for method in req.methods.iter() {
-                    let module = ctx.instance_infos.get_mut(&req.module);
-                    if module.is_none() {
+                    let ctx = &caller.data().instance_ctx;
+                    let Some(module) = ctx.instance_infos.get(&req.module) else {
                         responseError!("no module found");
-                    }
-                    let module = module.unwrap();
-                    let caller = module.instance_caller(method, caller.as_context_mut());
-                    let caller = match caller {
-                        Ok(c) => c,
-                        Err(e) => {
-                            let e = format!("caller instance fail, {}", e);
-                            responseError!(&e);
-                        }
                     };
-                    ctx.module_caller
-                        .insert(format!("{}::{method}", &req.module), caller);
+                    let mcaller =
+                        match module.instance_caller(method, req.convention, caller.as_context()) {
+                            Ok(c) => c,
+                            Err(e) => {
+                                let e = format!("caller instance fail, {}", e);
+                                responseError!(&e);
+                            }
+                        };
+                    caller
+                        .data_mut()
+                        .instance_ctx
+                        .module_caller
+                        .insert(format!("{}::{method}", &req.module), mcaller);
                 }
 
                 McallError::None.into()
@@ -387,22 +730,14 @@ impl<'a> ModuleLinker<'a> {
         })
     }
 
-    /// export the ```blockless.mcall``` and ```blockless.register``` in the runtime.
-    /// The modules can be use the register to register the moudle's function for mcall.
-    pub(crate) async fn link_modules(&mut self) -> anyhow::Result<Module> {
-        let mut modules: Vec<BlocklessModule> = {
-            let preview1 = self
-                .store
-                .data()
-                .preview1_ctx
-                .as_ref()
-                .ok_or(anyhow!("get preview1_ctx fail"))?;
-            let lock = preview1.blockless_config.lock().unwrap();
-            let cfg = lock.as_ref().ok_or(anyhow!("get the lock fail"))?;
-            cfg.modules_ref().iter().map(|m| (*m).clone()).collect()
-        };
-        modules.sort_by(|a, b| a.module_type.partial_cmp(&b.module_type).unwrap());
-        let mut entry = None;
+    /// export the ```blockless.mcall```, ```blockless.mcall_bin``` and ```blockless.register``` in the runtime.
+    /// The modules can be use the register to register the moudle's function for mcall,
+    /// the `convention` of the registration selects the json, cbor or raw convention.
+    /// the `modules` is the compiled library modules with the module name.
+    pub(crate) async fn link_modules(
+        &mut self,
+        modules: &[(String, Module)],
+    ) -> anyhow::Result<()> {
         self.linker.func_wrap_async(
             "blockless",
             "mcall",
@@ -411,6 +746,35 @@ impl<'a> ModuleLinker<'a> {
                 Self::mcall_fn(caller, addr, addr_len, buf, buf_len)
             },
         )?;
+        self.linker.func_wrap_async(
+            "blockless",
+            "mcall_bin",
+            |caller: Caller<'_, BSContext>,
+             (name, name_len, params, params_len, iovs, iovs_len, nwritten, err, err_len): (
+                u32,
+                u32,
+                u32,
+                u32,
+                u32,
+                u32,
+                u32,
+                u32,
+                u32,
+            )| {
+                let args = McallBinArgs {
+                    name,
+                    name_len,
+                    params,
+                    params_len,
+                    iovs,
+                    iovs_len,
+                    nwritten,
+                    err,
+                    err_len,
+                };
+                Self::mcall_bin_fn(caller, args)
+            },
+        )?;
         self.linker.func_wrap_async(
             "blockless",
             "register",
@@ -419,34 +783,57 @@ impl<'a> ModuleLinker<'a> {
                 Self::register_fn(caller, addr, addr_len, buf, buf_len)
             },
         )?;
-        for m in modules {
-            let (m_name, is_entry) = match m.module_type {
-                ModuleType::Module => (m.name.as_str(), false),
-                ModuleType::Entry => ("", true),
-            };
-            let module = Module::from_file(self.store.engine(), &m.file)?;
-            if is_entry {
-                entry = Some(module);
-            } else {
-                self.instance_module(m_name, &module).await?;
-            }
+        for (m_name, module) in modules {
+            self.instance_module(m_name, module).await?;
         }
-        entry.ok_or(anyhow!("can't find the entry"))
+        Ok(())
     }
 
     ///instance module and inital the context.
     async fn instance_module(&mut self, m_name: &str, module: &Module) -> anyhow::Result<()> {
+        let name: Arc<str> = m_name.into();
+        // the sandboxed module is instantiated in its sandbox.
+        let sandbox = self.store.data().sandbox_index(m_name);
+        let entered = match sandbox {
+            Some(index) => Some(sandbox::enter_sandbox(self.store.as_context_mut(), index)?),
+            None => None,
+        };
+        self.store.data_mut().module_stack.push(name.clone());
         let instance = self
             .linker
             .instantiate_async(self.store.as_context_mut(), module)
-            .await?;
+            .await;
+        self.store.data_mut().module_stack.pop();
+        if let Some(entered) = entered {
+            sandbox::leave_sandbox(self.store.as_context_mut(), entered)?;
+        }
+        let instance = instance?;
+        let exports: Vec<_> = instance
+            .exports(self.store.as_context_mut())
+            .map(|export| (export.name().to_string(), export.into_extern()))
+            .collect();
         let mut initial = None;
         let mut funcs = HashMap::<String, Func>::new();
         let mut alloc = None;
         let mut dealloc = None;
         let mut mem = None;
-        for export in instance.exports(self.store.as_context_mut()) {
-            let name = export.name().to_string();
+        for (name, export) in exports {
+            // the functions of the module enter the module when called.
+            let export = match (export, sandbox) {
+                (Extern::Func(func), sandbox) => Extern::Func(module_func(
+                    self.store.as_context_mut(),
+                    func,
+                    &name,
+                    sandbox,
+                )),
+                // the functions in the table would be called out of the sandbox.
+                (Extern::Table(_), Some(_)) => {
+                    bail!("the sandboxed module `{m_name}` can't export the table `{name}`.")
+                }
+                (export, _) => export,
+            };
+            self.linker
+                .define(self.store.as_context(), m_name, &name, export.clone())?;
             if &name == "memory" {
                 mem = export.into_memory();
                 continue;
@@ -470,15 +857,6 @@ impl<'a> ModuleLinker<'a> {
             }
         }
 
-        let mem_ptr = mem.map(|m| m.data_ptr(self.store.as_context_mut()) as usize);
-        if let Some(mem_ptr) = mem_ptr {
-            INS_CTX
-                .lock()
-                .await
-                .modules
-                .insert(mem_ptr, m_name.to_string());
-        }
-
         let alloc: Option<Arc<AllocTypedFunc>> = match alloc.map(|alloc| {
             alloc
                 .typed(self.store.as_context_mut())
@@ -498,18 +876,16 @@ impl<'a> ModuleLinker<'a> {
             Some(Err(e)) => return Err(e),
             None => None,
         };
-        self.linker
-            .instance(self.store.as_context_mut(), m_name, instance)?;
         let mod_info = InstanceInfo {
             alloc,
             export_funcs: funcs,
             dealloc,
             mem,
         };
-        //must release the lock, the initial method will access the modules.
-        INS_CTX
-            .lock()
-            .await
+        // the initial method may register the mcall of the module.
+        self.store
+            .data_mut()
+            .instance_ctx
             .instance_infos
             .insert(m_name.to_string(), mod_info);
         if let Some(func) = initial {
@@ -523,3 +899,181 @@ impl<'a> ModuleLinker<'a> {
         Ok(())
     }
 }
+
+/// wrap the function of the library module, the module is the running module while
+/// the function runs, and the function of the sandboxed module runs in the sandbox.
+fn module_func(
+    mut store: impl AsContextMut<Data = BSContext>,
+    func: Func,
+    name: &Arc<str>,
+    sandbox: Option<usize>,
+) -> Func {
+    let ty = func.ty(store.as_context_mut());
+    let name = name.clone();
+    Func::new_async(store, ty, move |mut caller, params, results| {
+        let name = name.clone();
+        Box::new(async move {
+            caller.data_mut().module_stack.push(name);
+            let result = match sandbox {
+                Some(index) => {
+                    sandbox::call_sandboxed(&mut caller, &func, index, params, results).await
+                }
+                None => func.call_async(&mut caller, params, results).await,
+            };
+            caller.data_mut().module_stack.pop();
+            result
+        })
+    })
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    Visiting,
+    Done,
+}
+
+/// visit the module after the modules it depends on, the cycle is returned as the path.
+fn visit(
+    i: usize,
+    deps: &[Vec<usize>],
+    state: &mut [Visit],
+    path: &mut Vec<usize>,
+    order: &mut Vec<usize>,
+) -> Result<(), Vec<usize>> {
+    match state[i] {
+        Visit::Done => return Ok(()),
+        Visit::Visiting => {
+            let start = path.iter().position(|p| *p == i).unwrap_or(0);
+            let mut cycle = path[start..].to_vec();
+            cycle.push(i);
+            return Err(cycle);
+        }
+        Visit::New => {}
+    }
+    state[i] = Visit::Visiting;
+    path.push(i);
+    for dep in deps[i].iter() {
+        visit(*dep, deps, state, path, order)?;
+    }
+    path.pop();
+    state[i] = Visit::Done;
+    order.push(i);
+    Ok(())
+}
+
+/// sort the library modules in the load order, the module is loaded after the modules
+/// it depends on. The dependencies are the `depends` of the module and the library
+/// modules it imports from, the modules without dependencies keep the declared order.
+pub(crate) fn sort_modules(
+    libs: Vec<(&BlocklessModule, Module)>,
+) -> anyhow::Result<Vec<(String, Module)>> {
+    let mut index = HashMap::new();
+    for (i, (m, _)) in libs.iter().enumerate() {
+        if index.insert(m.name.as_str(), i).is_some() {
+            bail!("the module `{}` is declared more than once.", m.name);
+        }
+    }
+    let mut deps = Vec::with_capacity(libs.len());
+    for (m, module) in libs.iter() {
+        let mut m_deps = Vec::new();
+        for dep in m.depends.iter() {
+            match index.get(dep.as_str()) {
+                Some(i) => m_deps.push(*i),
+                None => bail!(
+                    "the module `{}` depends on the unknown module `{dep}`.",
+                    m.name
+                ),
+            }
+        }
+        for import in module.imports() {
+            if let Some(i) = index.get(import.module()) {
+                if !m_deps.contains(i) {
+                    m_deps.push(*i);
+                }
+            }
+        }
+        deps.push(m_deps);
+    }
+    let mut order = Vec::with_capacity(libs.len());
+    let mut state = vec![Visit::New; libs.len()];
+    for i in 0..libs.len() {
+        visit(i, &deps, &mut state, &mut Vec::new(), &mut order).map_err(|cycle| {
+            let names: Vec<_> = cycle.iter().map(|i| libs[*i].0.name.as_str()).collect();
+            anyhow::anyhow!(
+                "the modules have the dependency cycle: {}",
+                names.join(" -> ")
+            )
+        })?;
+    }
+    let mut libs: Vec<_> = libs
+        .into_iter()
+        .map(|(m, module)| Some((m.name.clone(), module)))
+        .collect();
+    Ok(order.into_iter().filter_map(|i| libs[i].take()).collect())
+}
+
+/// the export matches the import, the function type must be the subtype.
+fn extern_matches(export: &ExternType, import: &ExternType) -> bool {
+    match (export, import) {
+        (ExternType::Func(e), ExternType::Func(i)) => e.matches(i),
+        (ExternType::Global(_), ExternType::Global(_))
+        | (ExternType::Table(_), ExternType::Table(_))
+        | (ExternType::Memory(_), ExternType::Memory(_)) => true,
+        _ => false,
+    }
+}
+
+/// the imports linked when run, the mcall of the modules, the thread spawn
+/// and the shared memory of the threads.
+fn is_linked_when_run(import: &ImportType, is_entry: bool, feature_thread: bool) -> bool {
+    match (import.module(), import.name()) {
+        ("blockless", "mcall" | "mcall_bin" | "register") => true,
+        ("wasi", "thread-spawn") => is_entry && feature_thread,
+        _ => is_entry && feature_thread && import.ty().memory().is_some_and(|m| m.is_shared()),
+    }
+}
+
+/// check the imports of the library modules and the entry before instantiation,
+/// the imports from the library modules must be exported by them, the other
+/// imports must be defined in the linker or linked when run.
+pub(crate) fn validate_imports(
+    linker: &Linker<BSContext>,
+    b_conf: &BlocklessConfig,
+    entry: &Module,
+    libs: &[(String, Module)],
+) -> anyhow::Result<()> {
+    // the store is only used to get the types of the definitions.
+    let mut store = Store::new(linker.engine(), BSContext::default());
+    let lib_map: HashMap<&str, &Module> = libs.iter().map(|(n, m)| (n.as_str(), m)).collect();
+    let modules = libs
+        .iter()
+        .map(|(n, m)| (format!("the module `{n}`"), m, false))
+        .chain(std::iter::once(("the entry".to_string(), entry, true)));
+    for (who, module, is_entry) in modules {
+        // the unknown imports of the entry are defined as traps.
+        let traps = is_entry && b_conf.unknown_imports_trap;
+        for import in module.imports() {
+            let (m_name, name) = (import.module(), import.name());
+            let export = match lib_map.get(m_name) {
+                Some(lib) => match lib.get_export(name) {
+                    Some(export) => export,
+                    None if traps => continue,
+                    None => bail!(
+                        "{who} imports `{m_name}::{name}`, but the module `{m_name}` doesn't export `{name}`."
+                    ),
+                },
+                None if is_linked_when_run(&import, is_entry, b_conf.feature_thread()) => continue,
+                None => match linker.get(&mut store, m_name, name) {
+                    Some(ext) => ext.ty(&store),
+                    None if traps => continue,
+                    None => bail!("{who} imports `{m_name}::{name}`, but it's not defined."),
+                },
+            };
+            if !extern_matches(&export, &import.ty()) {
+                bail!("{who} imports `{m_name}::{name}` with the mismatched type.");
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,62 @@
+use std::fs::File;
+use std::io::BufWriter;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::{Context, bail};
+use wasmtime::{GuestProfiler, Module, Store, Trap, UpdateDeadline};
+
+use crate::AnyResult;
+use crate::deadline::{Deadline, EPOCH_TICK};
+
+/// The guest profiler of the run, the guest stacks are sampled every epoch tick,
+/// and the host calls are recorded by the call hook.
+#[derive(Clone)]
+pub(crate) struct Profiler(Arc<Mutex<Option<GuestProfiler>>>);
+
+impl Profiler {
+    /// the `modules` are the entry module and the library modules of Version1,
+    /// only the frames of the modules are in the profile.
+    pub(crate) fn new(name: &str, modules: Vec<(String, Module)>) -> Self {
+        let profiler = GuestProfiler::new(name, EPOCH_TICK, modules);
+        Self(Arc::new(Mutex::new(Some(profiler))))
+    }
+
+    /// install the profiler to the store, the epoch deadline callback
+    /// replaces the deadline trap, so the deadline is checked at every tick.
+    pub(crate) fn install<T: 'static>(&self, store: &mut Store<T>, deadline: Option<Deadline>) {
+        let profiler = self.clone();
+        let mut last_sample = Instant::now();
+        store.epoch_deadline_callback(move |ctx| {
+            let now = Instant::now();
+            if let Some(p) = profiler.0.lock().unwrap().as_mut() {
+                p.sample(&ctx, now.duration_since(last_sample));
+            }
+            last_sample = now;
+            if deadline.as_ref().is_some_and(|d| d.is_expired()) {
+                return Err(Trap::Interrupt.into());
+            }
+            Ok(UpdateDeadline::Continue(1))
+        });
+        let profiler = self.clone();
+        store.call_hook(move |ctx, kind| {
+            if let Some(p) = profiler.0.lock().unwrap().as_mut() {
+                p.call_hook(&ctx, kind);
+            }
+            Ok(())
+        });
+        store.set_epoch_deadline(1);
+    }
+
+    /// write the profile in the Firefox profiler format, the profiler can be finished once.
+    pub(crate) fn finish(&self, path: &str) -> AnyResult<()> {
+        let Some(profiler) = self.0.lock().unwrap().take() else {
+            bail!("the profiler is finished.");
+        };
+        let file =
+            File::create(path).with_context(|| format!("failed to create the profile `{path}`"))?;
+        profiler
+            .finish(BufWriter::new(file))
+            .with_context(|| format!("failed to write the profile `{path}`"))
+    }
+}
@@ -0,0 +1,164 @@
+use std::time::Duration;
+
+use json::JsonValue;
+use wasi_common::CapturedOutput;
+use wasmtime::Trap;
+
+/// The trap classification of the run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RunTrap {
+    OutOfFuel,
+    StackOverflow,
+    MemoryOutOfBounds,
+    HeapMisaligned,
+    TableOutOfBounds,
+    IndirectCallToNull,
+    BadSignature,
+    IntegerOverflow,
+    IntegerDivisionByZero,
+    BadConversionToInteger,
+    UnreachableCodeReached,
+    Interrupt,
+    /// the epoch interruption raised by the wall-clock deadline.
+    Timeout,
+    AlwaysTrapAdapter,
+    /// the memory required by the module exceeds the memory limits.
+    MemoryLimitExceeded,
+    /// the trap not classified.
+    Other,
+}
+
+impl RunTrap {
+    pub(crate) fn from_trap(trap: &Trap, timeout: bool) -> Self {
+        match *trap {
+            Trap::OutOfFuel => RunTrap::OutOfFuel,
+            Trap::StackOverflow => RunTrap::StackOverflow,
+            Trap::MemoryOutOfBounds => RunTrap::MemoryOutOfBounds,
+            Trap::HeapMisaligned => RunTrap::HeapMisaligned,
+            Trap::TableOutOfBounds => RunTrap::TableOutOfBounds,
+            Trap::IndirectCallToNull => RunTrap::IndirectCallToNull,
+            Trap::BadSignature => RunTrap::BadSignature,
+            Trap::IntegerOverflow => RunTrap::IntegerOverflow,
+            Trap::IntegerDivisionByZero => RunTrap::IntegerDivisionByZero,
+            Trap::BadConversionToInteger => RunTrap::BadConversionToInteger,
+            Trap::UnreachableCodeReached => RunTrap::UnreachableCodeReached,
+            // the epoch interruption is only used for the wall-clock deadline.
+            Trap::Interrupt if timeout => RunTrap::Timeout,
+            Trap::Interrupt => RunTrap::Interrupt,
+            Trap::AlwaysTrapAdapter => RunTrap::AlwaysTrapAdapter,
+            _ => RunTrap::Other,
+        }
+    }
+
+    /// the exit code of the trap, see the exit codes in README.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            RunTrap::OutOfFuel => 1,
+            RunTrap::StackOverflow => 2,
+            RunTrap::MemoryOutOfBounds => 3,
+            RunTrap::HeapMisaligned => 4,
+            RunTrap::TableOutOfBounds => 5,
+            RunTrap::IndirectCallToNull => 6,
+            RunTrap::BadSignature => 7,
+            RunTrap::IntegerOverflow => 8,
+            RunTrap::IntegerDivisionByZero => 9,
+            RunTrap::BadConversionToInteger => 10,
+            RunTrap::UnreachableCodeReached => 11,
+            RunTrap::Interrupt => 12,
+            RunTrap::AlwaysTrapAdapter => 13,
+            RunTrap::Timeout => 15,
+            RunTrap::MemoryLimitExceeded => 16,
+            RunTrap::Other => -1,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunTrap::OutOfFuel => "out_of_fuel",
+            RunTrap::StackOverflow => "stack_overflow",
+            RunTrap::MemoryOutOfBounds => "memory_out_of_bounds",
+            RunTrap::HeapMisaligned => "heap_misaligned",
+            RunTrap::TableOutOfBounds => "table_out_of_bounds",
+            RunTrap::IndirectCallToNull => "indirect_call_to_null",
+            RunTrap::BadSignature => "bad_signature",
+            RunTrap::IntegerOverflow => "integer_overflow",
+            RunTrap::IntegerDivisionByZero => "integer_division_by_zero",
+            RunTrap::BadConversionToInteger => "bad_conversion_to_integer",
+            RunTrap::UnreachableCodeReached => "unreachable_code_reached",
+            RunTrap::Interrupt => "interrupt",
+            RunTrap::Timeout => "timeout",
+            RunTrap::AlwaysTrapAdapter => "always_trap_adapter",
+            RunTrap::MemoryLimitExceeded => "memory_limit_exceeded",
+            RunTrap::Other => "other",
+        }
+    }
+}
+
+/// The limit hit by the run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    Fuel,
+    Time,
+    Memory,
+}
+
+impl LimitKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LimitKind::Fuel => "fuel",
+            LimitKind::Time => "time",
+            LimitKind::Memory => "memory",
+        }
+    }
+}
+
+/// The report of the run.
+#[derive(Debug, Clone)]
+pub struct RunReport {
+    /// the exit code of the run, it is the guest exit code if the guest exited,
+    /// otherwise the exit code mapped from the trap.
+    pub code: i32,
+    /// the exit code passed by the guest to `proc_exit`.
+    pub guest_exit_code: Option<i32>,
+    pub trap: Option<RunTrap>,
+    /// the fuel remaining, `None` if the fuel is not enabled.
+    pub fuel: Option<u64>,
+    /// the fuel consumed, `None` if the fuel is not enabled.
+    pub fuel_consumed: Option<u64>,
+    /// the peak size in bytes of the linear memories.
+    pub peak_memory: usize,
+    pub wall_time: Duration,
+    pub limit_hit: Option<LimitKind>,
+    /// the error message if the run is failed.
+    pub error: Option<String>,
+    /// the formatted results of the invoked export, `None` if the entry is called.
+    pub results: Option<Vec<String>>,
+    /// the stdout captured in memory, when the stdout is `Buffer` or `Pipe`.
+    pub stdout: Option<CapturedOutput>,
+    /// the stderr captured in memory, when the stderr is `Buffer` or `Pipe`.
+    pub stderr: Option<CapturedOutput>,
+}
+
+impl RunReport {
+    pub fn to_json(&self) -> JsonValue {
+        let mut obj = JsonValue::new_object();
+        obj["code"] = self.code.into();
+        obj["guest_exit_code"] = self.guest_exit_code.into();
+        obj["trap"] = self.trap.map(|t| t.as_str()).into();
+        obj["fuel_remaining"] = self.fuel.into();
+        obj["fuel_consumed"] = self.fuel_consumed.into();
+        obj["peak_memory"] = self.peak_memory.into();
+        obj["wall_time_ms"] = (self.wall_time.as_secs_f64() * 1000.0).into();
+        obj["limit_hit"] = self.limit_hit.map(|l| l.as_str()).into();
+        obj["error"] = self.error.clone().into();
+        obj["results"] = self.results.clone().into();
+        let captured = |out: &Option<CapturedOutput>| -> JsonValue {
+            out.as_ref()
+                .map(|o| String::from_utf8_lossy(&o.data).into_owned())
+                .into()
+        };
+        obj["stdout"] = captured(&self.stdout);
+        obj["stderr"] = captured(&self.stderr);
+        obj
+    }
+}
@@ -0,0 +1,158 @@
+use anyhow::bail;
+use wasi_common::{BlocklessConfig, ModuleSandbox, ModuleType, Stdio};
+use wasmtime::{AsContextMut, Caller, Func, Val};
+
+use crate::context::BlocklessContext;
+use crate::limits::BlocklessLimiter;
+use crate::{AnyResult, BlocklessConfig2Preview1WasiBuilder};
+
+/// The context of the sandboxed library module, the module runs with its own
+/// wasi context, permissions, fuel and memory budgets.
+#[derive(Clone)]
+pub(crate) struct ModuleSandboxCtx {
+    pub(crate) name: String,
+    pub(crate) preview1_ctx: wasi_common::WasiCtx,
+    pub(crate) limiter: BlocklessLimiter,
+    /// the fuel left of the module, `None` is unlimited.
+    fuel: Option<u64>,
+}
+
+impl ModuleSandboxCtx {
+    /// create the context of the module, the stdio is shared with the entry.
+    fn new(
+        b_conf: &BlocklessConfig,
+        name: &str,
+        sandbox: &ModuleSandbox,
+        entry_ctx: &wasi_common::WasiCtx,
+    ) -> AnyResult<Self> {
+        let conf = sandbox_config(b_conf, sandbox);
+        let mut preview1_ctx = conf.preview1_builder()?.build();
+        preview1_ctx.share_stdio(entry_ctx);
+        preview1_ctx.set_blockless_config(Some(conf.clone()));
+        preview1_ctx.set_permissions_config(&sandbox.permissions_config)?;
+        let limiter =
+            BlocklessLimiter::new(conf.store_limits(), conf.store_limited().max_memory_size);
+        Ok(Self {
+            name: name.to_string(),
+            preview1_ctx,
+            limiter,
+            fuel: sandbox.limited_fuel,
+        })
+    }
+}
+
+/// the configure of the sandbox, the args, envs, dirs, permissions and limits
+/// of the entry are replaced by the sandbox.
+fn sandbox_config(b_conf: &BlocklessConfig, sandbox: &ModuleSandbox) -> BlocklessConfig {
+    let mut conf = b_conf.clone();
+    conf.stdio = Stdio::default();
+    conf.stdin_args = Vec::new();
+    conf.envs = sandbox.envs.clone();
+    conf.dirs = sandbox.dirs.clone();
+    conf.fs_root_path = None;
+    conf.tcp_listens = Vec::new();
+    conf.permisions = sandbox.permissions.clone();
+    conf.permissions_config = sandbox.permissions_config.clone();
+    conf.limited_fuel = sandbox.limited_fuel;
+    if let Some(max) = sandbox.max_memory_size {
+        let max = conf
+            .store_limited
+            .max_memory_size
+            .map_or(max, |m| m.min(max));
+        conf.store_limited.max_memory_size = Some(max);
+    }
+    conf
+}
+
+/// create the contexts of the sandboxed library modules, the entry context must be setup.
+pub(crate) fn sandboxes_setup(
+    b_conf: &BlocklessConfig,
+    ctx: &mut BlocklessContext,
+    consume_fuel: bool,
+) -> AnyResult<()> {
+    let Some(entry_ctx) = ctx.preview1_ctx.as_ref() else {
+        bail!("the sandboxes must be setup after the entry.");
+    };
+    let mut sandboxes = Vec::new();
+    for m in b_conf.modules_ref() {
+        let Some(sandbox) = m.sandbox.as_ref() else {
+            continue;
+        };
+        if matches!(m.module_type, ModuleType::Entry) {
+            bail!("the entry module can't have the sandbox.");
+        }
+        if sandbox.limited_fuel.is_some() && !consume_fuel {
+            bail!(
+                "the fuel budget of the module `{}` needs the `limited_fuel` of the configure.",
+                m.name
+            );
+        }
+        sandboxes.push(ModuleSandboxCtx::new(b_conf, &m.name, sandbox, entry_ctx)?);
+    }
+    ctx.sandboxes = sandboxes;
+    Ok(())
+}
+
+/// The sandbox entered, the fuel of the store is restored when left.
+pub(crate) struct SandboxEntered {
+    index: usize,
+    // the fuel of the store and the fuel when entered.
+    fuel: Option<(u64, u64)>,
+}
+
+/// enter the sandbox of the module, the fuel of the store is limited to the fuel
+/// left of the module.
+pub(crate) fn enter_sandbox(
+    mut store: impl AsContextMut<Data = BlocklessContext>,
+    index: usize,
+) -> AnyResult<SandboxEntered> {
+    let mut store = store.as_context_mut();
+    let budget = store.data().sandboxes[index].fuel;
+    let fuel = match (budget, store.get_fuel().ok()) {
+        (Some(budget), Some(outer)) => {
+            let start = outer.min(budget);
+            store.set_fuel(start)?;
+            Some((outer, start))
+        }
+        _ => None,
+    };
+    store.data_mut().sandbox_stack.push(index);
+    Ok(SandboxEntered { index, fuel })
+}
+
+/// leave the sandbox, the fuel consumed in the sandbox is charged to the module
+/// and the store.
+pub(crate) fn leave_sandbox(
+    mut store: impl AsContextMut<Data = BlocklessContext>,
+    entered: SandboxEntered,
+) -> AnyResult<()> {
+    let mut store = store.as_context_mut();
+    store.data_mut().sandbox_stack.pop();
+    if let Some((outer, start)) = entered.fuel {
+        let used = start.saturating_sub(store.get_fuel()?);
+        let sandbox = &mut store.data_mut().sandboxes[entered.index];
+        sandbox.fuel = sandbox.fuel.map(|f| f.saturating_sub(used));
+        store.set_fuel(outer.saturating_sub(used))?;
+    }
+    Ok(())
+}
+
+/// call the function of the sandboxed module in the sandbox, the callers (the entry,
+/// the other modules and the mcall) can't bypass it.
+pub(crate) async fn call_sandboxed(
+    caller: &mut Caller<'_, BlocklessContext>,
+    func: &Func,
+    index: usize,
+    params: &[Val],
+    results: &mut [Val],
+) -> AnyResult<()> {
+    let entered = enter_sandbox(&mut *caller, index)?;
+    let result = func.call_async(&mut *caller, params, results).await;
+    leave_sandbox(&mut *caller, entered)?;
+    if result.is_err() && caller.data().sandboxes[index].fuel == Some(0) {
+        let name = &caller.data().sandboxes[index].name;
+        return result
+            .map_err(|e| e.context(format!("the module `{name}` used out the fuel budget")));
+    }
+    result
+}
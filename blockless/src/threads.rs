@@ -0,0 +1,285 @@
+//! The implementation of the `wasi-threads` proposal for blockless.
+//!
+//! It is based on `wasmtime-wasi-threads`, but every new thread store is
+//! created with the limits of the run(the deadline, the store limits),
+//! otherwise the thread store will never be interrupted.
+//!
+//! The instances of the library modules belong to the store of the parent, so
+//! every thread links the library modules in its own store, the mcalls of the
+//! thread are registered by its own instances and call them.
+//!
+//! The thread that traps, exits or panics stops the whole run like the upstream,
+//! the first error of the threads is kept and reported as the result of the run.
+use std::panic::{AssertUnwindSafe, catch_unwind};
+use std::sync::atomic::{AtomicI32, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::{Result, anyhow};
+use log::{error, trace};
+use wasmtime::{Caller, Instance, Linker, Module, SharedMemory, Store};
+
+use crate::context::BlocklessContext;
+use crate::deadline::{self, Deadline};
+use crate::modules::ModuleLinker;
+
+// the thread entry point name defined by wasi-threads.
+const WASI_ENTRY_POINT: &str = "wasi_thread_start";
+
+// the thread ID must be in range [1, 0x1FFFFFFF].
+const MAX_THREAD_ID: i32 = 0x1FFFFFFF;
+
+/// The slots of the running threads, the spawn fails when all slots are taken.
+struct ThreadSlots {
+    max: Option<u32>,
+    running: Arc<AtomicU32>,
+}
+
+impl ThreadSlots {
+    fn new(max: Option<u32>) -> Self {
+        Self {
+            max,
+            running: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// take a slot, the slot is released when the thread exits.
+    fn acquire(&self) -> Option<ThreadSlot> {
+        let max = self.max.unwrap_or(u32::MAX);
+        self.running
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()?;
+        Some(ThreadSlot(self.running.clone()))
+    }
+}
+
+struct ThreadSlot(Arc<AtomicU32>);
+
+impl Drop for ThreadSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+pub(crate) struct BlocklessThreadsCtx {
+    // the linker before the library modules are linked.
+    linker: Linker<BlocklessContext>,
+    module: Module,
+    // the library modules of the Version1 configure.
+    modules: Option<Vec<(String, Module)>>,
+    unknown_imports_trap: bool,
+    tid: AtomicI32,
+    slots: ThreadSlots,
+    // the first error of the threads.
+    error: Mutex<Option<anyhow::Error>>,
+}
+
+impl BlocklessThreadsCtx {
+    /// the `max_threads` limits the threads spawned by the app running at the same time.
+    /// the `linker` must not have the library modules linked, they are linked
+    /// in the store of every thread.
+    pub(crate) fn new(
+        module: &Module,
+        linker: &Linker<BlocklessContext>,
+        modules: Option<Vec<(String, Module)>>,
+        unknown_imports_trap: bool,
+        max_threads: Option<u32>,
+    ) -> Self {
+        Self {
+            linker: linker.clone(),
+            module: module.clone(),
+            modules,
+            unknown_imports_trap,
+            tid: AtomicI32::new(0),
+            slots: ThreadSlots::new(max_threads),
+            error: Mutex::new(None),
+        }
+    }
+
+    /// keep the first error of the threads and stop the other stores of the run.
+    fn fail(&self, error: anyhow::Error, deadline: Option<&Deadline>) {
+        let mut first = self.error.lock().unwrap();
+        if first.is_none() {
+            *first = Some(error);
+        }
+        if let Some(deadline) = deadline {
+            deadline.expire();
+        }
+    }
+
+    /// take the first error of the threads, `None` if all threads exit normally.
+    pub(crate) fn take_error(&self) -> Option<anyhow::Error> {
+        self.error.lock().unwrap().take()
+    }
+
+    /// link the library modules in the thread store and instantiate the module.
+    async fn instantiate(&self, store: &mut Store<BlocklessContext>) -> Result<Instance> {
+        let mut linker = self.linker.clone();
+        if let Some(modules) = &self.modules {
+            ModuleLinker::new(&mut linker, store)
+                .link_modules(modules)
+                .await?;
+        }
+        if self.unknown_imports_trap {
+            linker.define_unknown_imports_as_traps(&self.module)?;
+        }
+        linker.instantiate_async(store, &self.module).await
+    }
+
+    fn next_thread_id(&self) -> Option<i32> {
+        match self
+            .tid
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |v| {
+                (v < MAX_THREAD_ID).then_some(v + 1)
+            }) {
+            Ok(v) => Some(v + 1),
+            Err(_) => None,
+        }
+    }
+
+    /// spawn the thread, the negative result means spawn fail.
+    /// the thread store use the `fuel` split from the parent as budget when fuel is enabled.
+    fn spawn(
+        self: &Arc<Self>,
+        host: BlocklessContext,
+        thread_start_arg: i32,
+        fuel: Option<u64>,
+    ) -> Result<i32> {
+        let has_entry = self
+            .module
+            .get_export(WASI_ENTRY_POINT)
+            .is_some_and(|t| t.func().is_some());
+        if !has_entry {
+            error!("failed to find the wasi-threads entry point function: {WASI_ENTRY_POINT}");
+            return Ok(-1);
+        }
+        if host.deadline.as_ref().is_some_and(|d| d.is_expired()) {
+            error!("the run is timeout, no more thread can be spawned.");
+            return Ok(-1);
+        }
+        let Some(slot) = self.slots.acquire() else {
+            error!("the maximum number of the running threads is reached.");
+            return Ok(-1);
+        };
+        let Some(wasi_thread_id) = self.next_thread_id() else {
+            error!("ran out of valid thread IDs");
+            return Ok(-1);
+        };
+        let threads_ctx = self.clone();
+        thread::Builder::new()
+            .name(format!("wasi-thread-{wasi_thread_id}"))
+            .spawn(move || {
+                let _slot = slot;
+                let deadline = host.deadline.clone();
+                let result = catch_unwind(AssertUnwindSafe(|| {
+                    let engine = threads_ctx.module.engine().clone();
+                    let mut store = Store::new(&engine, host);
+                    store.limiter(|ctx| ctx.running_limiter());
+                    deadline::set_store_deadline(&mut store, deadline.clone());
+                    let rs = wasmtime_wasi::runtime::in_tokio(async {
+                        if let Some(fuel) = fuel {
+                            store.set_fuel(fuel)?;
+                        }
+                        let instance = threads_ctx.instantiate(&mut store).await?;
+                        let entry = instance
+                            .get_typed_func::<(i32, i32), ()>(&mut store, WASI_ENTRY_POINT)?;
+                        entry
+                            .call_async(&mut store, (wasi_thread_id, thread_start_arg))
+                            .await
+                    });
+                    match rs {
+                        Ok(_) => trace!("exiting thread id = {wasi_thread_id} normally"),
+                        Err(e) => {
+                            error!("thread id = {wasi_thread_id} exit with error: {e}");
+                            threads_ctx.fail(e, deadline.as_ref());
+                        }
+                    }
+                }));
+                if let Err(e) = result {
+                    error!("wasi-thread-{wasi_thread_id} panicked: {e:?}");
+                    let e = anyhow!("wasi-thread-{wasi_thread_id} panicked");
+                    threads_ctx.fail(e, deadline.as_ref());
+                }
+            })?;
+        Ok(wasi_thread_id)
+    }
+}
+
+/// add the `wasi.thread-spawn` to the linker and define the shared memory imported by module.
+pub(crate) fn add_to_linker(
+    linker: &mut Linker<BlocklessContext>,
+    store: &Store<BlocklessContext>,
+    module: &Module,
+) -> Result<()> {
+    linker.func_wrap(
+        "wasi",
+        "thread-spawn",
+        |mut caller: Caller<'_, BlocklessContext>, start_arg: i32| -> i32 {
+            trace!("new thread requested via `wasi::thread_spawn` call");
+            let mut host = caller.data().clone();
+            // the linked modules belong to the store of the parent, the thread
+            // registers the mcalls of the library modules linked in its own store.
+            host.instance_ctx = Default::default();
+            host.sandbox_stack = Vec::new();
+            host.module_stack = Vec::new();
+            // the fuel is not enabled if get fuel fail, otherwise the thread takes
+            // the half of the remaining fuel, so all threads share the budget of the run.
+            let remaining = caller.get_fuel().ok();
+            let fuel = remaining.map(|f| f / 2);
+            if let Some((remaining, fuel)) = remaining.zip(fuel) {
+                if let Err(e) = caller.set_fuel(remaining - fuel) {
+                    error!("failed to split the fuel for the thread: {e}");
+                    return -1;
+                }
+            }
+            let ctx = caller.data().wasi_threads.clone().unwrap();
+            let thread_id = match ctx.spawn(host, start_arg, fuel) {
+                Ok(thread_id) => thread_id,
+                Err(e) => {
+                    error!("failed to spawn thread: {e}");
+                    -1
+                }
+            };
+            // the fuel is given back if the thread is not spawned.
+            if let Some(remaining) = remaining.filter(|_| thread_id < 0) {
+                let _ = caller.set_fuel(remaining);
+            }
+            thread_id
+        },
+    )?;
+    for import in module.imports() {
+        if let Some(m) = import.ty().memory() {
+            if m.is_shared() {
+                let mem = SharedMemory::new(module.engine(), m.clone())?;
+                linker.define(store, import.module(), import.name(), mem)?;
+            } else {
+                return Err(anyhow!(
+                    "memory was not shared; a `wasi-threads` must import a shared memory as \"memory\""
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_thread_slots() {
+        let slots = ThreadSlots::new(Some(2));
+        let first = slots.acquire().unwrap();
+        let _second = slots.acquire().unwrap();
+        assert!(slots.acquire().is_none());
+        // the slot is released when the thread exits.
+        drop(first);
+        assert!(slots.acquire().is_some());
+
+        let slots = ThreadSlots::new(None);
+        let taken: Vec<_> = (0..64).map(|_| slots.acquire().unwrap()).collect();
+        assert_eq!(taken.len(), 64);
+    }
+}
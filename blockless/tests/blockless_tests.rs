@@ -2,9 +2,14 @@ mod common;
 
 use std::fs;
 
+use blockless::{BlocklessEngine, LimitKind, RunTrap, precompile};
 use common::run_blockless;
 use tempdir::TempDir;
-use wasi_common::{BlocklessConfig, BlocklessConfigVersion};
+use tokio::runtime::Builder;
+use wasi_common::{
+    AuditConfig, BlocklessConfig, BlocklessConfigVersion, DeterministicConfig, Invoke,
+    OutputCapture, Stdin, Stdout,
+};
 
 #[test]
 fn test_outof_fuel() {
@@ -62,3 +67,538 @@ fn test_outof_memory() {
     let code = run_blockless(config).unwrap();
     assert_eq!(code.code, 16);
 }
+
+#[test]
+fn test_limited_time() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_blockless_timeout_run.wasm");
+    let code = r#"
+    (module
+        (func (export "_start")
+            (loop $forever
+                (br $forever)
+            )
+        )
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.limited_time(Some(100));
+    config.set_version(BlocklessConfigVersion::Version0);
+    let code = run_blockless(config).unwrap();
+    assert_eq!(code.code, 15);
+}
+
+#[test]
+fn test_runner_reuse() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_blockless_reuse_run.wasm");
+    let code = r#"
+    (module
+        (func (export "_start")
+            (local $i i32)
+            (loop $continue
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br_if $continue (i32.lt_u (local.get $i) (i32.const 100)))
+            )
+        )
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    config.limited_fuel(Some(100_000));
+    let engine = BlocklessEngine::new(&config).unwrap();
+    let runner = engine.prepare(&config).unwrap();
+    let rt = Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()
+        .unwrap();
+    // the runner can be used many times with different limits.
+    let mut low_fuel = config.clone();
+    low_fuel.limited_fuel(Some(10));
+    let code = rt.block_on(runner.run(low_fuel)).unwrap();
+    assert_eq!(code.code, 1);
+    let code = rt.block_on(runner.run(config.clone())).unwrap();
+    assert_eq!(code.code, 0);
+    let mut no_limit = config.clone();
+    no_limit.limited_fuel(None);
+    let code = rt.block_on(runner.run(no_limit)).unwrap();
+    assert_eq!(code.code, 0);
+}
+
+#[test]
+fn test_runner_limit_not_enabled() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_blockless_run.wasm");
+    let code = r#"
+    (module
+        (func (export "_start"))
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    let engine = BlocklessEngine::new(&config).unwrap();
+    let runner = engine.prepare(&config).unwrap();
+    let rt = Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()
+        .unwrap();
+    let mut limited = config.clone();
+    limited.limited_time(Some(100));
+    assert!(rt.block_on(runner.run(limited)).is_err());
+}
+
+#[test]
+fn test_guest_exit_report() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_blockless_exit_run.wasm");
+    let code = r#"
+    (module
+        (import "wasi_snapshot_preview1" "proc_exit" (func $exit (param i32)))
+        (func (export "_start")
+            (call $exit (i32.const 42))
+        )
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 42);
+    assert_eq!(report.guest_exit_code, Some(42));
+    assert_eq!(report.trap, None);
+    assert_eq!(report.error, None);
+    assert_eq!(report.peak_memory, 65536);
+}
+
+#[test]
+fn test_limit_hit_report() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_blockless_run.wasm");
+    let code = r#"
+    (module
+        (func (export "_start"))
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.limited_fuel(Some(1));
+    config.set_version(BlocklessConfigVersion::Version0);
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 1);
+    assert_eq!(report.trap, Some(RunTrap::OutOfFuel));
+    assert_eq!(report.limit_hit, Some(LimitKind::Fuel));
+    assert_eq!(report.fuel_consumed, Some(1));
+    let json = report.to_json();
+    assert_eq!(json["trap"], "out_of_fuel");
+    assert_eq!(json["limit_hit"], "fuel");
+}
+
+#[test]
+fn test_capture_stdout() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_blockless_capture_run.wasm");
+    let code = r#"
+    (module
+        (import "wasi_snapshot_preview1" "fd_write"
+            (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 8) "hello world")
+        (func (export "_start")
+            (i32.store (i32.const 0) (i32.const 8))
+            (i32.store (i32.const 4) (i32.const 11))
+            (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 100)))
+        )
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    config
+        .stdio
+        .stdout(Stdout::Buffer(OutputCapture::new(5, "...")));
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 0);
+    let stdout = report.stdout.unwrap();
+    assert!(stdout.truncated);
+    assert_eq!(stdout.data, b"hello...");
+    assert_eq!(report.stderr, None);
+}
+
+#[test]
+fn test_precompiled_run() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_precompiled.cwasm");
+    let code = r#"
+    (module
+        (func (export "_start"))
+        (memory (export "memory") 1)
+    )
+    "#;
+    let mut compile_conf = BlocklessConfig::new("");
+    compile_conf.limited_fuel(Some(u64::MAX));
+    let artifact = precompile(&compile_conf, None, code.as_bytes()).unwrap();
+    fs::write(&file_path, artifact.bytes).unwrap();
+    let path = file_path.to_str().unwrap();
+
+    let mut config = BlocklessConfig::new(path);
+    config.limited_fuel(Some(100));
+    config.set_version(BlocklessConfigVersion::Version0);
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 0);
+
+    // the engine without fuel is incompatible with the artifact.
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    let err = run_blockless(config).unwrap_err();
+    assert!(format!("{err:#}").contains("incompatible with the engine"));
+}
+
+#[test]
+fn test_invoke_export() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_invoke.wasm");
+    let code = r#"
+    (module
+        (global $base (mut i64) (i64.const 0))
+        (func (export "_initialize")
+            (global.set $base (i64.const 100))
+        )
+        (func (export "add") (param i32 i64) (result i64 f32)
+            (i64.add (i64.add (i64.extend_i32_s (local.get 0)) (local.get 1)) (global.get $base))
+            (f32.const 0.5)
+        )
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    config.set_invoke(Some(Invoke::new(
+        "add".into(),
+        vec!["-1".into(), "2".into()],
+    )));
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 0);
+    assert_eq!(
+        report.results,
+        Some(vec!["101".to_string(), "0.5".to_string()])
+    );
+
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    config.set_invoke(Some(Invoke::new("add".into(), vec!["a".into()])));
+    let report = run_blockless(config).unwrap();
+    assert_ne!(report.code, 0);
+    assert!(report.error.unwrap().contains("failed to invoke `add`"));
+}
+
+#[test]
+fn test_runner_run_invoke() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_run_invoke.wasm");
+    let code = r#"
+    (module
+        (func (export "_start"))
+        (func (export "double") (param i32) (result i32)
+            (i32.mul (local.get 0) (i32.const 2))
+        )
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    let engine = BlocklessEngine::new(&config).unwrap();
+    let runner = engine.prepare(&config).unwrap();
+    let rt = Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()
+        .unwrap();
+    // the invoke of the run overrides the entry of the prepare.
+    let mut invoke = config.clone();
+    invoke.set_invoke(Some(Invoke::new("double".into(), vec!["21".into()])));
+    let report = rt.block_on(runner.run(invoke)).unwrap();
+    assert_eq!(report.results, Some(vec!["42".to_string()]));
+    let report = rt.block_on(runner.run(config)).unwrap();
+    assert_eq!(report.code, 0);
+    assert_eq!(report.results, None);
+}
+
+#[test]
+fn test_guest_profile() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_profile.wasm");
+    let profile_path = temp_dir.path().join("profile.json");
+    let code = r#"
+    (module
+        (func $spin (param $n i64)
+            (loop $l
+                (local.set $n (i64.sub (local.get $n) (i64.const 1)))
+                (br_if $l (i64.ne (local.get $n) (i64.const 0)))
+            )
+        )
+        (func (export "_start")
+            (call $spin (i64.const 100000000))
+        )
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    config.set_profile(Some(profile_path.to_str().unwrap().into()));
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 0);
+    let profile = json::parse(&fs::read_to_string(&profile_path).unwrap()).unwrap();
+    assert!(profile["meta"].is_object());
+    assert!(profile["threads"].is_array());
+}
+
+#[test]
+fn test_deterministic_run() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_deterministic.wasm");
+    let code = r#"
+    (module
+        (import "wasi_snapshot_preview1" "clock_time_get"
+            (func $clock_time_get (param i32 i64 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "random_get"
+            (func $random_get (param i32 i32) (result i32)))
+        (import "blockless_http" "http_req" (func $http_req (param i32) (result i32)))
+        (func (export "now") (result i64)
+            (drop (call $clock_time_get (i32.const 0) (i64.const 1) (i32.const 0)))
+            (i64.load (i32.const 0))
+        )
+        (func (export "random") (result i64)
+            (drop (call $random_get (i32.const 8) (i32.const 8)))
+            (i64.load (i32.const 8))
+        )
+        (func (export "fetch") (result i32)
+            (call $http_req (i32.const 0))
+        )
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let config = |name: &str| {
+        let mut config = BlocklessConfig::new(path);
+        config.set_version(BlocklessConfigVersion::Version0);
+        config.set_deterministic(Some(DeterministicConfig {
+            seed: 7,
+            start_time: 1000,
+            allowed_extensions: Vec::new(),
+        }));
+        config.set_invoke(Some(Invoke::new(name.into(), Vec::new())));
+        config
+    };
+    // the first read of the virtual wall clock is the start time and a step.
+    let report = run_blockless(config("now")).unwrap();
+    assert_eq!(report.results, Some(vec!["1001000000".to_string()]));
+
+    let first = run_blockless(config("random")).unwrap();
+    let second = run_blockless(config("random")).unwrap();
+    assert_eq!(first.code, 0);
+    assert_eq!(first.results, second.results);
+
+    let report = run_blockless(config("fetch")).unwrap();
+    assert_ne!(report.code, 0);
+    assert!(
+        report
+            .error
+            .unwrap()
+            .contains("is not allowed in the deterministic mode")
+    );
+
+    // the deterministic mode can be turned on by the run.
+    let engine = BlocklessEngine::new(&config("now")).unwrap();
+    let mut prepare_conf = config("now");
+    prepare_conf.set_deterministic(None);
+    let runner = engine.prepare(&prepare_conf).unwrap();
+    let rt = Builder::new_current_thread()
+        .enable_io()
+        .enable_time()
+        .build()
+        .unwrap();
+    let mut allow_http = config("now");
+    allow_http.deterministic.as_mut().unwrap().allowed_extensions = vec!["http".into()];
+    let report = rt.block_on(runner.run(allow_http)).unwrap();
+    assert_eq!(report.results, Some(vec!["1001000000".to_string()]));
+    // the extension linked by the prepare is rejected by the run.
+    let err = rt.block_on(runner.run(config("now"))).err().unwrap();
+    assert!(err.to_string().contains("is not allowed in the deterministic mode"));
+
+    // the threads are rejected in the deterministic mode.
+    let mut config = config("now");
+    config.set_feature_thread(true);
+    let engine = BlocklessEngine::new(&config).unwrap();
+    assert!(engine.prepare(&config).is_err());
+}
+
+#[test]
+fn test_audit_trace() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_audit.wasm");
+    let trace_path = temp_dir.path().join("audit.jsonl");
+    let code = r#"
+    (module
+        (import "blockless_memory" "memory_read"
+            (func $memory_read (param i32 i32 i32) (result i32)))
+        (func (export "_start")
+            (drop (call $memory_read (i32.const 16) (i32.const 64) (i32.const 0)))
+        )
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    config.stdio.stdin(Stdin::Fixed("hello".into()));
+    config.set_audit(Some(AuditConfig {
+        path: trace_path.to_str().unwrap().into(),
+        redact: vec!["body_buf".into()],
+    }));
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 0);
+    let trace = fs::read_to_string(&trace_path).unwrap();
+    let lines: Vec<_> = trace.lines().map(|l| json::parse(l).unwrap()).collect();
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0]["module"], "test_audit.wasm");
+    assert_eq!(lines[0]["call"], "blockless_memory.memory_read");
+    assert_eq!(lines[0]["args"]["body_buf"], "<redacted>");
+    assert_eq!(lines[0]["args"]["body_buf_len"], 64);
+    assert_eq!(lines[0]["errno"], 0);
+}
+
+#[test]
+fn test_audit_trace_wasi_calls() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_audit_wasi.wasm");
+    let trace_path = temp_dir.path().join("audit.jsonl");
+    let root_dir = temp_dir.path().join("root");
+    fs::create_dir(&root_dir).unwrap();
+    fs::write(root_dir.join("data.txt"), "hello").unwrap();
+    // open `data.txt` in the preopened root dir(fd 3) and read it by the opened fd.
+    let code = r#"
+    (module
+        (import "wasi_snapshot_preview1" "path_open"
+            (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+        (import "wasi_snapshot_preview1" "fd_read"
+            (func $fd_read (param i32 i32 i32 i32) (result i32)))
+        (func (export "_start")
+            (drop (call $path_open (i32.const 3) (i32.const 0) (i32.const 0) (i32.const 8)
+                (i32.const 0) (i64.const 2) (i64.const 0) (i32.const 0) (i32.const 16)))
+            (drop (call $fd_read (i32.load (i32.const 16)) (i32.const 32) (i32.const 1) (i32.const 48)))
+        )
+        (memory (export "memory") 1)
+        (data (i32.const 0) "data.txt")
+        (data (i32.const 32) "\20\00\00\00\08\00\00\00")
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    config.set_fs_root_path(Some(root_dir.to_str().unwrap().into()));
+    config.permissions_config.allow_all = true;
+    config.set_audit(Some(AuditConfig::new(trace_path.to_str().unwrap())));
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 0);
+    let trace = fs::read_to_string(&trace_path).unwrap();
+    let lines: Vec<_> = trace.lines().map(|l| json::parse(l).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["module"], "test_audit_wasi.wasm");
+    assert_eq!(lines[0]["call"], "path_open");
+    assert_eq!(lines[0]["args"]["dirfd"], 3);
+    assert_eq!(lines[0]["args"]["path"], "data.txt");
+    assert_eq!(lines[0]["errno"], 0);
+    assert_eq!(lines[1]["module"], "test_audit_wasi.wasm");
+    assert_eq!(lines[1]["call"], "fd_read");
+    assert_eq!(lines[1]["args"]["fd"], 4);
+    assert_eq!(lines[1]["args"]["path"], "/data.txt");
+    assert_eq!(lines[1]["errno"], 0);
+}
+
+#[test]
+fn test_wasm_features() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_tail_call.wasm");
+    let code = r#"
+    (module
+        (func $loop (param i32)
+            (if (local.get 0)
+                (then (return_call $loop (i32.sub (local.get 0) (i32.const 1))))))
+        (func (export "_start") (call $loop (i32.const 100000)))
+        (memory (export "memory") 1)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    config.opts.wasm_tail_call = Some(true);
+    let code = run_blockless(config.clone()).unwrap();
+    assert_eq!(code.code, 0);
+
+    // the module is rejected when the tail calls are disabled.
+    config.opts.wasm_tail_call = Some(false);
+    let engine = BlocklessEngine::new(&config).unwrap();
+    assert!(engine.prepare(&config).is_err());
+
+    // the threads can't be disabled with the thread feature.
+    config.opts.wasm_threads = Some(false);
+    config.set_feature_thread(true);
+    assert!(BlocklessEngine::new(&config).is_err());
+}
+
+#[test]
+fn test_shared_memory_extension() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_shared_memory.wasm");
+    let code = r#"
+    (module
+        (import "blockless_memory" "memory_read"
+            (func $memory_read (param i32 i32 i32) (result i32)))
+        (func (export "_start")
+            (if (call $memory_read (i32.const 16) (i32.const 64) (i32.const 0))
+                (then unreachable))
+            ;; the stdin is read into the shared memory.
+            (if (i32.ne (i32.load (i32.const 0)) (i32.const 5))
+                (then unreachable))
+            (if (i32.ne (i32.load8_u (i32.const 16)) (i32.const 104))
+                (then unreachable))
+        )
+        (memory (export "memory") 1 1 shared)
+    )
+    "#;
+    fs::write(&file_path, code).unwrap();
+    let path = file_path.to_str().unwrap();
+    let mut config = BlocklessConfig::new(path);
+    config.set_version(BlocklessConfigVersion::Version0);
+    config.set_feature_thread(true);
+    config.set_max_threads(Some(2));
+    config.stdio.stdin(Stdin::Fixed("hello".into()));
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 0);
+}
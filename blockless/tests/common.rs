@@ -1,10 +1,10 @@
-use blockless::{ExitStatus, blockless_run};
+use blockless::{RunReport, blockless_run};
 use tokio::runtime::Builder;
 use wasi_common::BlocklessConfig;
 
 /// runing environment for test.
 #[allow(dead_code)]
-pub fn run_blockless(config: BlocklessConfig) -> anyhow::Result<ExitStatus> {
+pub fn run_blockless(config: BlocklessConfig) -> anyhow::Result<RunReport> {
     let rt = Builder::new_current_thread()
         .enable_io()
         .enable_time()
@@ -16,7 +16,7 @@ pub fn run_blockless(config: BlocklessConfig) -> anyhow::Result<ExitStatus> {
 
 /// multi-thread runing environment for test.
 #[allow(dead_code)]
-pub fn multi_threads_run_blockless(config: BlocklessConfig) -> anyhow::Result<ExitStatus> {
+pub fn multi_threads_run_blockless(config: BlocklessConfig) -> anyhow::Result<RunReport> {
     let rt = Builder::new_multi_thread()
         .enable_io()
         .enable_time()
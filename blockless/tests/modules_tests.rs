@@ -4,8 +4,8 @@ use std::fs;
 use common::run_blockless;
 use tempdir::TempDir;
 use wasi_common::{
-    BlocklessConfig, BlocklessConfigVersion, BlocklessModule, ModuleType, PermissionGrant,
-    PermissionsConfig,
+    AuditConfig, BlocklessConfig, BlocklessConfigVersion, BlocklessModule, Invoke, ModuleSandbox,
+    ModuleType, PermissionGrant, PermissionsConfig, Stdin,
 };
 
 #[test]
@@ -57,6 +57,8 @@ fn test_linker_module() {
         name: "".to_string(),
         file: guest_path.to_str().unwrap().to_string(),
         md5: format!("{:x}", md5::compute(guest_wasm)),
+        depends: Vec::new(),
+        sandbox: None,
     }];
     let mut config = BlocklessConfig::new("_start");
     config.set_version(BlocklessConfigVersion::Version1);
@@ -110,6 +112,8 @@ fn test_blockless_extension_http_req() {
         name: "".to_string(),
         file: guest_path.to_str().unwrap().to_string(),
         md5: format!("{:x}", md5::compute(guest_wasm)),
+        depends: Vec::new(),
+        sandbox: None,
     }];
     let mut config = BlocklessConfig::new("_start");
     config.set_version(BlocklessConfigVersion::Version1);
@@ -160,12 +164,16 @@ fn test_blockless_run_primary_module_can_call_reactor_module() {
             name: "".to_string(),
             file: primary_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(primary_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
         BlocklessModule {
             module_type: ModuleType::Module,
             name: "reactor1".to_string(),
             file: reactor_1_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(reactor_1_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
     ];
     let mut config = BlocklessConfig::new("_start");
@@ -227,18 +235,24 @@ fn test_blockless_primary_module_can_call_multiple_reactor_modules() {
             name: "".to_string(),
             file: primary_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(primary_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
         BlocklessModule {
             module_type: ModuleType::Module,
             name: "reactor1".to_string(),
             file: reactor_1_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(reactor_1_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
         BlocklessModule {
             module_type: ModuleType::Module,
             name: "reactor2".to_string(),
             file: reactor_2_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(reactor_2_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
     ];
     let mut config = BlocklessConfig::new("_start");
@@ -295,6 +309,8 @@ fn test_blockless_reactor_module_can_call_reactor_module() {
             name: "".to_string(),
             file: primary_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(primary_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
         // ensure we load/link reactor2 before reactor1 since reactor1 depends on it
         BlocklessModule {
@@ -302,12 +318,16 @@ fn test_blockless_reactor_module_can_call_reactor_module() {
             name: "reactor2".to_string(),
             file: reactor_2_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(reactor_2_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
         BlocklessModule {
             module_type: ModuleType::Module,
             name: "reactor1".to_string(),
             file: reactor_1_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(reactor_1_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
     ];
     let mut config = BlocklessConfig::new("_start");
@@ -370,18 +390,24 @@ fn test_blockless_reactor_module_can_call_reactor_module_with_callback_support()
             name: "".to_string(),
             file: primary_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(primary_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
         BlocklessModule {
             module_type: ModuleType::Module,
             name: "reactor1".to_string(),
             file: reactor_1_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(reactor_1_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
         BlocklessModule {
             module_type: ModuleType::Module,
             name: "reactor2".to_string(),
             file: reactor_2_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(reactor_2_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
     ];
     let mut config = BlocklessConfig::new("_start");
@@ -439,18 +465,24 @@ fn test_blockless_reactor_module_can_call_reactor_module_with_callback_endless_l
             name: "".to_string(),
             file: primary_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(primary_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
         BlocklessModule {
             module_type: ModuleType::Module,
             name: "reactor1".to_string(),
             file: reactor_1_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(reactor_1_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
         BlocklessModule {
             module_type: ModuleType::Module,
             name: "reactor2".to_string(),
             file: reactor_2_path.to_str().unwrap().to_string(),
             md5: format!("{:x}", md5::compute(reactor_2_code)),
+            depends: Vec::new(),
+            sandbox: None,
         },
     ];
     let mut config = BlocklessConfig::new("_start");
@@ -459,3 +491,779 @@ fn test_blockless_reactor_module_can_call_reactor_module_with_callback_endless_l
     let code = run_blockless(config).unwrap();
     assert_eq!(code.code, 0);
 }
+
+/// the library module registers the `hello` method writing "ok" to the result buffer.
+const HELLO_LIB: &str = r#"
+(module
+    (import "blockless" "register" (func $register (param i32 i32 i32 i32) (result i32)))
+    (memory (export "memory") 1)
+    (global $heap (mut i32) (i32.const 4096))
+    (func (export "alloc") (param i32) (result i32)
+        (local $ptr i32)
+        (local.set $ptr (global.get $heap))
+        (global.set $heap (i32.add (global.get $heap) (local.get 0)))
+        (local.get $ptr)
+    )
+    (func (export "dealloc") (param i32 i32))
+    (func (export "hello") (param i32 i32 i32 i32) (result i32)
+        (i32.store16 (local.get 2) (i32.const 0x6b6f))
+        (i32.const 2)
+    )
+    (func (export "_initialize")
+        (drop (call $register (i32.const 0) (i32.const 21) (i32.const 64) (i32.const 64)))
+    )
+    (data (i32.const 0) "{\"methods\":[\"hello\"]}")
+)
+"#;
+
+fn blockless_module(
+    module_type: ModuleType,
+    name: &str,
+    path: &std::path::Path,
+    code: &str,
+) -> BlocklessModule {
+    BlocklessModule {
+        module_type,
+        name: name.to_string(),
+        file: path.to_str().unwrap().to_string(),
+        md5: format!("{:x}", md5::compute(code)),
+        depends: Vec::new(),
+        sandbox: None,
+    }
+}
+
+#[test]
+fn test_mcall_registry_per_run() {
+    let lib_code = HELLO_LIB;
+    let entry_code = r#"
+    (module
+        (import "blockless" "mcall" (func $mcall (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (func (export "_start")
+            (if (call $mcall (i32.const 0) (i32.const 34) (i32.const 256) (i32.const 16))
+                (then unreachable))
+            (if (i32.ne (i32.load16_u (i32.const 256)) (i32.const 0x6b6f))
+                (then unreachable))
+        )
+        (data (i32.const 0) "{\"mcall\":\"lib::hello\",\"params\":{}}")
+    )
+    "#;
+    // the mcall fails without the library, the registration of the other run is invisible.
+    let unregistered_code = r#"
+    (module
+        (import "blockless" "mcall" (func $mcall (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (func (export "_start")
+            (if (i32.ne
+                    (call $mcall (i32.const 0) (i32.const 34) (i32.const 256) (i32.const 128))
+                    (i32.const 5))
+                (then unreachable))
+        )
+        (data (i32.const 0) "{\"mcall\":\"lib::hello\",\"params\":{}}")
+    )
+    "#;
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let lib_path = temp_dir.path().join("lib.wasm");
+    let entry_path = temp_dir.path().join("run.wasm");
+    let unregistered_path = temp_dir.path().join("unregistered.wasm");
+    fs::write(&lib_path, lib_code).unwrap();
+    fs::write(&entry_path, entry_code).unwrap();
+    fs::write(&unregistered_path, unregistered_code).unwrap();
+
+    let registered = || {
+        let mut config = BlocklessConfig::new("_start");
+        config.set_version(BlocklessConfigVersion::Version1);
+        config.set_modules(vec![
+            blockless_module(ModuleType::Entry, "", &entry_path, entry_code),
+            blockless_module(ModuleType::Module, "lib", &lib_path, lib_code),
+        ]);
+        config
+    };
+    let unregistered = || {
+        let mut config = BlocklessConfig::new("_start");
+        config.set_version(BlocklessConfigVersion::Version1);
+        config.set_modules(vec![blockless_module(
+            ModuleType::Entry,
+            "",
+            &unregistered_path,
+            unregistered_code,
+        )]);
+        config
+    };
+    // the runs are concurrent, so the registrations of the runs are alive at the same time.
+    std::thread::scope(|scope| {
+        let runs: Vec<_> = (0..8)
+            .map(|i| {
+                let config = if i % 2 == 0 {
+                    registered()
+                } else {
+                    unregistered()
+                };
+                scope.spawn(move || run_blockless(config).unwrap())
+            })
+            .collect();
+        for run in runs {
+            assert_eq!(run.join().unwrap().code, 0);
+        }
+    });
+}
+
+#[test]
+fn test_mcall_from_thread() {
+    // the thread calls the library method, the result is passed by the shared memory.
+    let entry_code = r#"
+    (module
+        (import "env" "shared" (memory $shared 1 1 shared))
+        (import "wasi" "thread-spawn" (func $spawn (param i32) (result i32)))
+        (import "blockless" "mcall" (func $mcall (param i32 i32 i32 i32) (result i32)))
+        (memory $mem (export "memory") 1)
+        (func (export "_start")
+            (if (i32.lt_s (call $spawn (i32.const 0)) (i32.const 0))
+                (then unreachable))
+            (drop (memory.atomic.wait32 $shared
+                (i32.const 0) (i32.const 0) (i64.const 10000000000)))
+            (if (i32.ne (i32.atomic.load $shared (i32.const 0)) (i32.const 1))
+                (then unreachable))
+        )
+        (func (export "wasi_thread_start") (param i32 i32)
+            (local $status i32)
+            (local.set $status (i32.const 2))
+            (if (i32.eqz (call $mcall (i32.const 0) (i32.const 34) (i32.const 256) (i32.const 16)))
+                (then
+                    (if (i32.eq (i32.load16_u $mem (i32.const 256)) (i32.const 0x6b6f))
+                        (then (local.set $status (i32.const 1))))))
+            (i32.atomic.store $shared (i32.const 0) (local.get $status))
+            (drop (memory.atomic.notify $shared (i32.const 0) (i32.const 1)))
+        )
+        (data (memory $mem) (i32.const 0) "{\"mcall\":\"lib::hello\",\"params\":{}}")
+    )
+    "#;
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let lib_path = temp_dir.path().join("lib.wasm");
+    let entry_path = temp_dir.path().join("run.wasm");
+    fs::write(&lib_path, HELLO_LIB).unwrap();
+    fs::write(&entry_path, entry_code).unwrap();
+
+    let mut config = BlocklessConfig::new("_start");
+    config.set_version(BlocklessConfigVersion::Version1);
+    config.set_feature_thread(true);
+    config.set_modules(vec![
+        blockless_module(ModuleType::Entry, "", &entry_path, entry_code),
+        blockless_module(ModuleType::Module, "lib", &lib_path, HELLO_LIB),
+    ]);
+    let code = run_blockless(config).unwrap();
+    assert_eq!(code.code, 0);
+}
+
+#[test]
+fn test_mcall_binary_convention() {
+    let lib_code = r#"
+    (module
+        (import "blockless" "register" (func $register (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (global $heap (mut i32) (i32.const 4096))
+        (func $alloc (export "alloc") (param i32) (result i32)
+            (local $ptr i32)
+            (local.set $ptr (global.get $heap))
+            (global.set $heap (i32.add (global.get $heap) (local.get 0)))
+            (local.get $ptr)
+        )
+        (global $freed (mut i32) (i32.const 0))
+        (func (export "dealloc") (param i32 i32)
+            (global.set $freed (i32.add (global.get $freed) (i32.const 1)))
+        )
+        ;; the results are the copy of the params and "ok".
+        (func (export "split") (param $ptr i32) (param $len i32) (param $slots i32) (param i32) (result i32)
+            (local $buf i32)
+            (local.set $buf (call $alloc (local.get $len)))
+            (memory.copy (local.get $buf) (local.get $ptr) (local.get $len))
+            (i32.store (local.get $slots) (local.get $buf))
+            (i32.store offset=4 (local.get $slots) (local.get $len))
+            (local.set $buf (call $alloc (i32.const 2)))
+            (i32.store16 (local.get $buf) (i32.const 0x6b6f))
+            (i32.store offset=8 (local.get $slots) (local.get $buf))
+            (i32.store offset=12 (local.get $slots) (i32.const 2))
+            (i32.const 0)
+        )
+        ;; fail with the message "bad".
+        (func (export "fail") (param i32 i32) (param $slots i32) (param i32) (result i32)
+            (local $buf i32)
+            (local.set $buf (call $alloc (i32.const 3)))
+            (memory.copy (local.get $buf) (i32.const 256) (i32.const 3))
+            (i32.store (local.get $slots) (local.get $buf))
+            (i32.store offset=4 (local.get $slots) (i32.const 3))
+            (i32.const 1)
+        )
+        ;; the first result is out of the memory, the second is "ok".
+        (func (export "huge") (param i32 i32) (param $slots i32) (param i32) (result i32)
+            (local $buf i32)
+            (i32.store (local.get $slots) (i32.const 0xfff0))
+            (i32.store offset=4 (local.get $slots) (i32.const -1))
+            (local.set $buf (call $alloc (i32.const 2)))
+            (i32.store16 (local.get $buf) (i32.const 0x6b6f))
+            (i32.store offset=8 (local.get $slots) (local.get $buf))
+            (i32.store offset=12 (local.get $slots) (i32.const 2))
+            (i32.const 0)
+        )
+        ;; the result is the count of the deallocs.
+        (func (export "freed") (param i32 i32) (param $slots i32) (param i32) (result i32)
+            (local $buf i32)
+            (local.set $buf (call $alloc (i32.const 4)))
+            (i32.store (local.get $buf) (global.get $freed))
+            (i32.store (local.get $slots) (local.get $buf))
+            (i32.store offset=4 (local.get $slots) (i32.const 4))
+            (i32.const 0)
+        )
+        (func (export "_initialize")
+            (drop (call $register (i32.const 0) (i32.const 40) (i32.const 512) (i32.const 64)))
+            (drop (call $register (i32.const 128) (i32.const 40) (i32.const 512) (i32.const 64)))
+            (drop (call $register (i32.const 320) (i32.const 47) (i32.const 512) (i32.const 64)))
+        )
+        (data (i32.const 0) "{\"methods\":[\"split\"],\"convention\":\"raw\"}")
+        (data (i32.const 128) "{\"methods\":[\"fail\"],\"convention\":\"cbor\"}")
+        (data (i32.const 256) "bad")
+        (data (i32.const 320) "{\"methods\":[\"huge\",\"freed\"],\"convention\":\"raw\"}")
+    )
+    "#;
+    let entry_code = r#"
+    (module
+        (import "blockless" "mcall" (func $mcall (param i32 i32 i32 i32) (result i32)))
+        (import "blockless" "mcall_bin"
+            (func $mcall_bin (param i32 i32 i32 i32 i32 i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (func (export "_start")
+            (local $freed i32)
+            ;; the second result is truncated to the iovec.
+            (if (call $mcall_bin (i32.const 0) (i32.const 10) (i32.const 32) (i32.const 3)
+                    (i32.const 48) (i32.const 2) (i32.const 320) (i32.const 384) (i32.const 64))
+                (then unreachable))
+            (if (i32.ne (i32.load16_u (i32.const 256)) (i32.const 0x6261))
+                (then unreachable))
+            (if (i32.ne (i32.load8_u (i32.const 258)) (i32.const 0x63))
+                (then unreachable))
+            (if (i32.ne (i32.load8_u (i32.const 272)) (i32.const 0x6f))
+                (then unreachable))
+            (if (i32.ne (i32.load (i32.const 320)) (i32.const 3))
+                (then unreachable))
+            (if (i32.ne (i32.load (i32.const 324)) (i32.const 2))
+                (then unreachable))
+            ;; the params of the cbor method are not CBOR.
+            (if (i32.ne
+                    (call $mcall_bin (i32.const 16) (i32.const 9) (i32.const 41) (i32.const 1)
+                        (i32.const 48) (i32.const 2) (i32.const 320) (i32.const 384) (i32.const 64))
+                    (i32.const 7))
+                (then unreachable))
+            (if (i32.ne (i32.load (i32.const 384)) (i32.const 7))
+                (then unreachable))
+            ;; the method fails with the message in the error buffer.
+            (if (i32.ne
+                    (call $mcall_bin (i32.const 16) (i32.const 9) (i32.const 40) (i32.const 1)
+                        (i32.const 48) (i32.const 2) (i32.const 320) (i32.const 384) (i32.const 64))
+                    (i32.const 4))
+                (then unreachable))
+            (if (i32.ne (i32.load (i32.const 384)) (i32.const 4))
+                (then unreachable))
+            ;; the json mcall rejects the binary method.
+            (if (i32.ne
+                    (call $mcall (i32.const 512) (i32.const 34) (i32.const 640) (i32.const 128))
+                    (i32.const 7))
+                (then unreachable))
+            ;; the params out of the memory.
+            (if (i32.ne
+                    (call $mcall_bin (i32.const 64) (i32.const 10) (i32.const 32) (i32.const -1)
+                        (i32.const 96) (i32.const 1) (i32.const 320) (i32.const 384) (i32.const 64))
+                    (i32.const 5))
+                (then unreachable))
+            (if (call $mcall_bin (i32.const 64) (i32.const 10) (i32.const 32) (i32.const 0)
+                    (i32.const 96) (i32.const 1) (i32.const 320) (i32.const 384) (i32.const 64))
+                (then unreachable))
+            (local.set $freed (i32.load (i32.const 448)))
+            ;; the result out of the memory fails the call.
+            (if (i32.ne
+                    (call $mcall_bin (i32.const 80) (i32.const 9) (i32.const 32) (i32.const 0)
+                        (i32.const 104) (i32.const 2) (i32.const 320) (i32.const 384) (i32.const 64))
+                    (i32.const 4))
+                (then unreachable))
+            (if (call $mcall_bin (i32.const 64) (i32.const 10) (i32.const 32) (i32.const 0)
+                    (i32.const 96) (i32.const 1) (i32.const 320) (i32.const 384) (i32.const 64))
+                (then unreachable))
+            ;; every buffer is released: the result, params and slots of the `freed`
+            ;; call, the two results, params and slots of the `huge` call.
+            (if (i32.ne (i32.sub (i32.load (i32.const 448)) (local.get $freed)) (i32.const 7))
+                (then unreachable))
+        )
+        (data (i32.const 0) "lib::split")
+        (data (i32.const 16) "lib::fail")
+        (data (i32.const 32) "abc")
+        ;; the CBOR null and the lone break.
+        (data (i32.const 40) "\f6\ff")
+        ;; the iovecs {256, 16} and {272, 1}.
+        (data (i32.const 48) "\00\01\00\00\10\00\00\00\10\01\00\00\01\00\00\00")
+        (data (i32.const 64) "lib::freed")
+        (data (i32.const 80) "lib::huge")
+        ;; the iovec {448, 4}.
+        (data (i32.const 96) "\c0\01\00\00\04\00\00\00")
+        ;; the iovecs {464, 16} and {480, 16}.
+        (data (i32.const 104) "\d0\01\00\00\10\00\00\00\e0\01\00\00\10\00\00\00")
+        (data (i32.const 512) "{\"mcall\":\"lib::split\",\"params\":{}}")
+    )
+    "#;
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let lib_path = temp_dir.path().join("lib.wasm");
+    let entry_path = temp_dir.path().join("run.wasm");
+    fs::write(&lib_path, lib_code).unwrap();
+    fs::write(&entry_path, entry_code).unwrap();
+
+    let mut config = BlocklessConfig::new("_start");
+    config.set_version(BlocklessConfigVersion::Version1);
+    config.set_modules(vec![
+        blockless_module(ModuleType::Entry, "", &entry_path, entry_code),
+        blockless_module(ModuleType::Module, "lib", &lib_path, lib_code),
+    ]);
+    let code = run_blockless(config).unwrap();
+    assert_eq!(code.code, 0);
+}
+
+#[test]
+fn test_module_depends_and_imports() {
+    let entry_code = r#"
+    (module
+        (import "mid" "quad" (func $quad (param i32) (result i32)))
+        (func (export "_start")
+            (if (i32.ne (call $quad (i32.const 2)) (i32.const 8))
+                (then unreachable))
+        )
+    )
+    "#;
+    let missing_code = r#"
+    (module
+        (import "mid" "nope" (func (param i32) (result i32)))
+        (func (export "_start"))
+    )
+    "#;
+    let mid_code = r#"
+    (module
+        (import "base" "double" (func $double (param i32) (result i32)))
+        (func (export "quad") (param i32) (result i32)
+            (call $double (call $double (local.get 0)))
+        )
+    )
+    "#;
+    let base_code = r#"
+    (module
+        (func (export "double") (param i32) (result i32)
+            (i32.mul (local.get 0) (i32.const 2))
+        )
+    )
+    "#;
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let write = |name: &str, code: &str| {
+        let path = temp_dir.path().join(name);
+        fs::write(&path, code).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let entry_path = write("run.wasm", entry_code);
+    let missing_path = write("missing.wasm", missing_code);
+    let mid_path = write("mid.wasm", mid_code);
+    let base_path = write("base.wasm", base_code);
+
+    let module = |module_type, name: &str, file: &str, depends: &[&str]| BlocklessModule {
+        module_type,
+        name: name.to_string(),
+        file: file.to_string(),
+        md5: String::new(),
+        depends: depends.iter().map(|d| d.to_string()).collect(),
+        sandbox: None,
+    };
+    let run = |modules: Vec<BlocklessModule>| {
+        let mut config = BlocklessConfig::new("_start");
+        config.set_version(BlocklessConfigVersion::Version1);
+        config.set_modules(modules);
+        run_blockless(config)
+    };
+
+    // the `mid` is declared before the `base` it depends on.
+    let code = run(vec![
+        module(ModuleType::Entry, "", &entry_path, &[]),
+        module(ModuleType::Module, "mid", &mid_path, &["base"]),
+        module(ModuleType::Module, "base", &base_path, &[]),
+    ])
+    .unwrap();
+    assert_eq!(code.code, 0);
+
+    let err = run(vec![
+        module(ModuleType::Entry, "", &entry_path, &[]),
+        module(ModuleType::Module, "mid", &mid_path, &[]),
+        module(ModuleType::Module, "base", &base_path, &["mid"]),
+    ])
+    .err()
+    .unwrap();
+    assert!(
+        err.to_string()
+            .contains("the modules have the dependency cycle: mid -> base -> mid")
+    );
+
+    let err = run(vec![
+        module(ModuleType::Entry, "", &entry_path, &[]),
+        module(ModuleType::Module, "mid", &mid_path, &["util"]),
+        module(ModuleType::Module, "base", &base_path, &[]),
+    ])
+    .err()
+    .unwrap();
+    assert!(
+        err.to_string()
+            .contains("the module `mid` depends on the unknown module `util`")
+    );
+
+    let err = run(vec![
+        module(ModuleType::Entry, "", &missing_path, &[]),
+        module(ModuleType::Module, "mid", &mid_path, &[]),
+        module(ModuleType::Module, "base", &base_path, &[]),
+    ])
+    .err()
+    .unwrap();
+    assert!(err.to_string().contains("the entry imports `mid::nope`"));
+
+    let err = run(vec![
+        module(ModuleType::Entry, "", &entry_path, &[]),
+        module(ModuleType::Module, "mid", &mid_path, &[]),
+    ])
+    .err()
+    .unwrap();
+    assert!(
+        err.to_string()
+            .contains("the module `mid` imports `base::double`, but it's not defined")
+    );
+}
+
+#[test]
+fn test_entry_depends() {
+    let entry_code = r#"
+    (module
+        (import "base" "double" (func $double (param i32) (result i32)))
+        (func (export "_start")
+            (drop (call $double (i32.const 2)))
+        )
+    )
+    "#;
+    let base_code = r#"
+    (module
+        (func (export "double") (param i32) (result i32)
+            (i32.mul (local.get 0) (i32.const 2))
+        )
+    )
+    "#;
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let entry_path = temp_dir.path().join("run.wasm");
+    let base_path = temp_dir.path().join("base.wasm");
+    fs::write(&entry_path, entry_code).unwrap();
+    fs::write(&base_path, base_code).unwrap();
+
+    let mut entry = blockless_module(ModuleType::Entry, "", &entry_path, entry_code);
+    entry.depends = vec!["base".to_string()];
+    let mut config = BlocklessConfig::new("_start");
+    config.set_version(BlocklessConfigVersion::Version1);
+    config.set_modules(vec![
+        entry,
+        blockless_module(ModuleType::Module, "base", &base_path, base_code),
+    ]);
+    let err = run_blockless(config).err().unwrap();
+    assert!(
+        err.to_string()
+            .contains("the entry can't depend on the modules")
+    );
+}
+
+#[test]
+fn test_module_sandbox() {
+    let lib_code = r#"
+    (module
+        (import "wasi_snapshot_preview1" "environ_sizes_get"
+            (func $environ_sizes_get (param i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (func (export "env_count") (result i32)
+            (drop (call $environ_sizes_get (i32.const 0) (i32.const 4)))
+            (i32.load (i32.const 0))
+        )
+        (func (export "spin")
+            (loop $l (br $l))
+        )
+    )
+    "#;
+    // the entry sees its own envs, the library sees the envs of the sandbox.
+    let entry_code = r#"
+    (module
+        (import "wasi_snapshot_preview1" "environ_sizes_get"
+            (func $environ_sizes_get (param i32 i32) (result i32)))
+        (import "lib" "env_count" (func $env_count (result i32)))
+        (memory (export "memory") 1)
+        (func (export "_start")
+            (drop (call $environ_sizes_get (i32.const 0) (i32.const 4)))
+            (if (i32.ne (i32.load (i32.const 0)) (i32.const 2))
+                (then unreachable))
+            (if (i32.ne (call $env_count) (i32.const 1))
+                (then unreachable))
+        )
+    )
+    "#;
+    let spin_code = r#"
+    (module
+        (import "lib" "spin" (func $spin))
+        (func (export "_start")
+            (call $spin)
+        )
+    )
+    "#;
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let write = |name: &str, code: &str| {
+        let path = temp_dir.path().join(name);
+        fs::write(&path, code).unwrap();
+        path.to_str().unwrap().to_string()
+    };
+    let lib_path = write("lib.wasm", lib_code);
+    let entry_path = write("run.wasm", entry_code);
+    let spin_path = write("spin.wasm", spin_code);
+
+    let config = |entry: &str, sandbox: ModuleSandbox| {
+        let mut config = BlocklessConfig::new("_start");
+        config.set_version(BlocklessConfigVersion::Version1);
+        config.set_modules(vec![
+            BlocklessModule {
+                module_type: ModuleType::Entry,
+                name: "".to_string(),
+                file: entry.to_string(),
+                md5: String::new(),
+                depends: Vec::new(),
+                sandbox: None,
+            },
+            BlocklessModule {
+                module_type: ModuleType::Module,
+                name: "lib".to_string(),
+                file: lib_path.clone(),
+                md5: String::new(),
+                depends: Vec::new(),
+                sandbox: Some(sandbox),
+            },
+        ]);
+        config
+    };
+
+    let mut entry_config = config(
+        &entry_path,
+        ModuleSandbox {
+            envs: vec![("LIB_KEY".to_string(), "lib".to_string())],
+            ..Default::default()
+        },
+    );
+    entry_config.set_envs(vec![
+        ("KEY1".to_string(), "1".to_string()),
+        ("KEY2".to_string(), "2".to_string()),
+    ]);
+    let code = run_blockless(entry_config).unwrap();
+    assert_eq!(code.code, 0);
+
+    // the library uses out its fuel budget before the fuel of the run.
+    let mut spin_config = config(
+        &spin_path,
+        ModuleSandbox {
+            limited_fuel: Some(10_000),
+            ..Default::default()
+        },
+    );
+    spin_config.limited_fuel(Some(100_000_000));
+    let report = run_blockless(spin_config).unwrap();
+    assert_eq!(report.code, 1);
+    assert!(report.fuel_consumed.unwrap() < 100_000_000);
+    assert!(
+        report
+            .error
+            .unwrap()
+            .contains("the module `lib` used out the fuel budget")
+    );
+}
+
+#[test]
+fn test_module_sandbox_table() {
+    // the function in the table would run out of the sandbox if called by the entry.
+    let lib_code = r#"
+    (module
+        (table (export "table") 1 funcref)
+        (func $one (result i32) (i32.const 1))
+        (elem (i32.const 0) $one)
+    )
+    "#;
+    let entry_code = r#"
+    (module
+        (import "lib" "table" (table 1 funcref))
+        (func (export "_start")
+            (drop (call_indirect (result i32) (i32.const 0)))
+        )
+    )
+    "#;
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let lib_path = temp_dir.path().join("lib.wasm");
+    let entry_path = temp_dir.path().join("run.wasm");
+    fs::write(&lib_path, lib_code).unwrap();
+    fs::write(&entry_path, entry_code).unwrap();
+
+    let mut lib = blockless_module(ModuleType::Module, "lib", &lib_path, lib_code);
+    lib.sandbox = Some(ModuleSandbox::default());
+    let mut config = BlocklessConfig::new("_start");
+    config.set_version(BlocklessConfigVersion::Version1);
+    config.set_modules(vec![
+        blockless_module(ModuleType::Entry, "", &entry_path, entry_code),
+        lib,
+    ]);
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, -1);
+    assert!(
+        report
+            .error
+            .unwrap()
+            .contains("the sandboxed module `lib` can't export the table `table`")
+    );
+}
+
+#[test]
+fn test_audit_trace_modules() {
+    // the library grows its memory before the call, the calls are audited
+    // with the module calling the host.
+    let lib_code = r#"
+    (module
+        (import "blockless_memory" "memory_read"
+            (func $memory_read (param i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (func (export "read")
+            (drop (memory.grow (i32.const 16)))
+            (drop (call $memory_read (i32.const 16) (i32.const 64) (i32.const 0)))
+        )
+    )
+    "#;
+    let entry_code = r#"
+    (module
+        (import "blockless_memory" "memory_read"
+            (func $memory_read (param i32 i32 i32) (result i32)))
+        (import "lib" "read" (func $lib_read))
+        (memory (export "memory") 1)
+        (func (export "_start")
+            (call $lib_read)
+            (drop (call $memory_read (i32.const 16) (i32.const 64) (i32.const 0)))
+        )
+    )
+    "#;
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let lib_path = temp_dir.path().join("lib.wasm");
+    let entry_path = temp_dir.path().join("run.wasm");
+    let trace_path = temp_dir.path().join("audit.jsonl");
+    fs::write(&lib_path, lib_code).unwrap();
+    fs::write(&entry_path, entry_code).unwrap();
+
+    let mut config = BlocklessConfig::new("_start");
+    config.set_version(BlocklessConfigVersion::Version1);
+    config.stdio.stdin(Stdin::Fixed("hello".into()));
+    config.set_audit(Some(AuditConfig::new(trace_path.to_str().unwrap())));
+    config.set_modules(vec![
+        blockless_module(ModuleType::Entry, "", &entry_path, entry_code),
+        blockless_module(ModuleType::Module, "lib", &lib_path, lib_code),
+    ]);
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 0);
+    let trace = fs::read_to_string(&trace_path).unwrap();
+    let lines: Vec<_> = trace.lines().map(|l| json::parse(l).unwrap()).collect();
+    assert_eq!(lines.len(), 2);
+    assert_eq!(lines[0]["module"], "lib");
+    assert_eq!(lines[0]["call"], "blockless_memory.memory_read");
+    assert_eq!(lines[1]["module"], "run.wasm");
+}
+
+#[test]
+fn test_mcall_register_after_grow() {
+    // the library registers after its memory grows, the entry can't register.
+    let lib_code = &HELLO_LIB.replace(
+        "(func (export \"_initialize\")",
+        "(func (export \"_initialize\")\n        (drop (memory.grow (i32.const 256)))",
+    );
+    let entry_code = r#"
+    (module
+        (import "blockless" "mcall" (func $mcall (param i32 i32 i32 i32) (result i32)))
+        (import "blockless" "register" (func $register (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (func (export "_start")
+            (if (i32.ne
+                    (call $register (i32.const 64) (i32.const 21) (i32.const 512) (i32.const 64))
+                    (i32.const 6))
+                (then unreachable))
+            (if (call $mcall (i32.const 0) (i32.const 34) (i32.const 256) (i32.const 16))
+                (then unreachable))
+            (if (i32.ne (i32.load16_u (i32.const 256)) (i32.const 0x6b6f))
+                (then unreachable))
+        )
+        (data (i32.const 0) "{\"mcall\":\"lib::hello\",\"params\":{}}")
+        (data (i32.const 64) "{\"methods\":[\"hello\"]}")
+    )
+    "#;
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let lib_path = temp_dir.path().join("lib.wasm");
+    let entry_path = temp_dir.path().join("run.wasm");
+    fs::write(&lib_path, lib_code).unwrap();
+    fs::write(&entry_path, entry_code).unwrap();
+
+    let mut config = BlocklessConfig::new("_start");
+    config.set_version(BlocklessConfigVersion::Version1);
+    config.set_modules(vec![
+        blockless_module(ModuleType::Entry, "", &entry_path, entry_code),
+        blockless_module(ModuleType::Module, "lib", &lib_path, lib_code),
+    ]);
+    let report = run_blockless(config).unwrap();
+    assert_eq!(report.code, 0);
+}
+
+#[test]
+fn test_invoke_module_export() {
+    let lib_code = r#"
+    (module
+        (global $base (mut i32) (i32.const 0))
+        (func (export "_initialize")
+            (global.set $base (i32.const 100))
+        )
+        (func (export "add") (param i32 i32) (result i32)
+            (i32.add (i32.add (local.get 0) (local.get 1)) (global.get $base))
+        )
+        (memory (export "memory") 1)
+    )
+    "#;
+    let entry_code = r#"
+    (module
+        (func (export "_start") unreachable)
+        (memory (export "memory") 1)
+    )
+    "#;
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let lib_path = temp_dir.path().join("lib.wasm");
+    let entry_path = temp_dir.path().join("run.wasm");
+    fs::write(&lib_path, lib_code).unwrap();
+    fs::write(&entry_path, entry_code).unwrap();
+
+    let config = |name: &str| {
+        let mut config = BlocklessConfig::new("_start");
+        config.set_version(BlocklessConfigVersion::Version1);
+        config.set_modules(vec![
+            blockless_module(ModuleType::Entry, "", &entry_path, entry_code),
+            blockless_module(ModuleType::Module, "lib", &lib_path, lib_code),
+        ]);
+        config.set_invoke(Some(Invoke::new(name.into(), vec!["2".into(), "3".into()])));
+        config
+    };
+    let report = run_blockless(config("lib#add")).unwrap();
+    assert_eq!(report.code, 0);
+    assert_eq!(report.results, Some(vec!["105".to_string()]));
+
+    let report = run_blockless(config("other#add")).unwrap();
+    assert_ne!(report.code, 0);
+    assert!(
+        report
+            .error
+            .unwrap()
+            .contains("failed to invoke `other#add`")
+    );
+}
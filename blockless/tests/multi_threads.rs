@@ -1,6 +1,6 @@
 use std::fs;
 
-use blockless::BlocklessConfig;
+use blockless::{BlocklessConfig, LimitKind, RunTrap};
 use tempdir::TempDir;
 
 mod common;
@@ -21,3 +21,50 @@ fn multi_threads() {
     cfg.set_feature_thread(true);
     common::multi_threads_run_blockless(cfg).unwrap();
 }
+
+// the main module spins until it's interrupted, the thread runs `$thread_body`.
+fn spinning_main(thread_body: &str) -> String {
+    format!(
+        r#"
+    (module
+        (import "env" "memory" (memory 1 1 shared))
+        (import "wasi" "thread-spawn" (func $spawn (param i32) (result i32)))
+        (func (export "_start")
+            (if (i32.lt_s (call $spawn (i32.const 0)) (i32.const 0))
+                (then unreachable))
+            (loop $spin (br $spin))
+        )
+        (func (export "wasi_thread_start") (param i32 i32)
+            {thread_body}
+        )
+    )
+    "#
+    )
+}
+
+#[test]
+fn test_thread_trap_stops_run() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_thread_trap.wat");
+    fs::write(&file_path, spinning_main("unreachable")).unwrap();
+    let mut cfg = BlocklessConfig::new(file_path.to_str().unwrap());
+    cfg.set_feature_thread(true);
+    let report = common::multi_threads_run_blockless(cfg).unwrap();
+    // the trap of the thread is the result of the run.
+    assert_eq!(report.trap, Some(RunTrap::UnreachableCodeReached));
+    assert_eq!(report.code, 11);
+    assert_eq!(report.limit_hit, None);
+}
+
+#[test]
+fn test_thread_timeout() {
+    let temp_dir = TempDir::new("blockless_run").unwrap();
+    let file_path = temp_dir.path().join("test_thread_timeout.wat");
+    fs::write(&file_path, spinning_main("(loop $spin (br $spin))")).unwrap();
+    let mut cfg = BlocklessConfig::new(file_path.to_str().unwrap());
+    cfg.set_feature_thread(true);
+    cfg.limited_time(Some(100));
+    let report = common::multi_threads_run_blockless(cfg).unwrap();
+    assert_eq!(report.trap, Some(RunTrap::Timeout));
+    assert_eq!(report.limit_hit, Some(LimitKind::Time));
+}
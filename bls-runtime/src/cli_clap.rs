@@ -1,13 +1,15 @@
 #![allow(unused)]
 use anyhow::{Result, bail};
 use blockless::{
-    BlocklessConfig, BlocklessModule, BlsNnGraph, BlsOptions, ModuleType, OptimizeOpts,
-    OptionParser, Permission, PermissionGrant, PermissionsConfig, Stderr, Stdin, Stdout,
+    BlocklessConfig, BlocklessModule, BlsNnGraph, BlsOptions, CacheSetting, DeterministicConfig,
+    ModuleType, OptimizeOpts, OptionParser, Permission, PermissionGrant, PermissionsConfig, Stderr,
+    Stdin, Stdout,
 };
 use clap::{
-    Arg, ArgMatches, Command, Parser, ValueHint,
+    Arg, ArgMatches, Args, Command, Parser, Subcommand, ValueHint,
     builder::{TypedValueParser, ValueParser},
 };
+use serde_json::{Map, Value as JsonValue, json};
 use std::{
     collections::HashMap,
     net::{IpAddr, SocketAddr, TcpListener, ToSocketAddrs},
@@ -17,9 +19,10 @@ use std::{
 };
 use url::Url;
 
-use crate::config::CliConfig;
+use crate::config_layers::{ConfigVars, LayerOpts};
 
-const INPUT_HELP: &str = "The input file can be a WASM file, a configuration file, or a CAR file.";
+const INPUT_HELP: &str =
+    "The input file can be a WASM file, a configuration file (JSON, TOML or YAML), or a CAR file.";
 
 const DEBUG_INFO_HELP: &str = "Runtime debugging information.";
 
@@ -33,7 +36,8 @@ const RUNTIME_LOGGER_HELP: &str = "The log file for the runtime.";
 
 const LIMITED_MEMORY_HELP: &str = "The maximum number of linear memories that can be created ";
 
-const RUN_TIME_HELP: &str = "The runtime's time limit, with the default set to infinite.";
+const RUN_TIME_HELP: &str =
+    "The runtime's time limit in milliseconds, with the default set to infinite.";
 
 const ENTRY_HELP: &str = "The entry point for the WASM, default is _start.";
 
@@ -60,6 +64,8 @@ const MAP_DIR_HELP: &str = "Grant access to a host directory for a guest. If spe
 const V86_HELP: &str =
     "V86 model flag when the v86 flag the car file must be v86 configure and image.";
 
+const MAX_THREADS_HELP: &str = "The maximum number of the threads spawned by the app running at the same time, the spawn fails when the maximum is reached, the default is unlimited.";
+
 const THREAD_SUPPORT_HELP: &str = "Enables multi-threading in the runtime. When set, the runtime can spawn threads, allowing concurrent task execution for improved performance and scalability.";
 
 const TCP_LISTEN_HELP: &str = "Grant access to the given TCP listen socket. ";
@@ -95,6 +101,83 @@ const DENY_NET_HELP: &str = "Deny the app to  net accessing permissions.";
 
 const ALLOW_WRITE_ALL_HELP: &str = "Allow the app to all write permissions.";
 
+const RUN_REPORT_HELP: &str =
+    "Write the run report as JSON to the file when the app finished, `-` means stderr.";
+
+const CACHE_DIR_HELP: &str =
+    "The directory for the compilation cache, the default is the wasmtime cache directory.";
+
+const CACHE_MAX_SIZE_HELP: &str =
+    "The soft limit of the compilation cache directory size in bytes.";
+
+const CACHE_CONFIG_HELP: &str =
+    "Load the compilation cache setting from the wasmtime cache configure file.";
+
+const DISABLE_CACHE_HELP: &str = "Disable the compilation cache.";
+
+const INVOKE_HELP: &str = "Invoke the export instead of the entry, the app arguments are parsed as the export's arguments (wasm-wave values for components) and the results are printed.";
+
+const PROFILE_HELP: &str = "Sample the guest stacks and write the profile to the file in the Firefox profiler format, only for the core modules.";
+
+const DETERMINISTIC_HELP: &str = "Run in the deterministic mode, the clocks are virtual, the random is seeded, the threads are rejected and the nondeterministic extensions fail unless they are allowed.";
+
+const DETERMINISTIC_SEED_HELP: &str =
+    "The seed of the random generator in the deterministic mode, the default is 0.";
+
+const DETERMINISTIC_START_TIME_HELP: &str = "The start time of the virtual wall clock in the deterministic mode, in milliseconds since the unix epoch, the default is 0.";
+
+const ALLOW_EXTENSION_HELP: &str = "Allow the nondeterministic extension in the deterministic mode, e.g. `http`, `ipfs`, `s3`, `socket`.";
+
+const AUDIT_TRACE_HELP: &str = "Write every blockless extension call and permission-checked wasi call to the file in JSON Lines, `-` means stderr, only for the core modules.";
+
+const HTTP_LISTEN_HELP: &str = "Serve the component as the `wasi:http/incoming-handler` on the address, every request runs in a new instance with the limits of the configure, e.g. `127.0.0.1:8080`.";
+
+const AUDIT_REDACT_HELP: &str = "Redact the argument of the host calls in the audit trace, e.g. `url`, `*` redacts all arguments.";
+
+const COMPILE_INPUT_HELP: &str = "The WASM module or component file to precompile.";
+
+const COMPILE_OUTPUT_HELP: &str = "The output file of the precompiled artifact, the default is the input file with the `.cwasm` extension.";
+
+const COMPILE_TARGET_HELP: &str = "The target triple for cross compiling, e.g. `aarch64-unknown-linux-gnu`, the default is the host.";
+
+const COMPILE_CONSUME_FUEL_HELP: &str =
+    "Compile with the fuel consumption, required when the app runs with `--limited-fuel`.";
+
+const COMPILE_EPOCH_INTERRUPTION_HELP: &str =
+    "Compile with the epoch interruption, required when the app runs with `--run-time`.";
+
+const COMPILE_DETERMINISTIC_HELP: &str =
+    "Compile with the NaN canonicalization, required when the app runs with `--deterministic`.";
+
+const SERVE_LISTEN_HELP: &str = "The address of the job API, `HOST:PORT` for the HTTP on TCP or `unix:PATH` for the HTTP on the Unix socket.";
+
+const SERVE_MAX_CONCURRENT_JOBS_HELP: &str = "The maximum number of the jobs running concurrently, the other jobs are queued, the default is the number of CPUs.";
+
+const SERVE_MAX_FINISHED_JOBS_HELP: &str = "The maximum number of the finished jobs kept for the query, the oldest finished jobs are removed when it's reached.";
+
+const SERVE_DETERMINISTIC_HELP: &str = "Enable the NaN canonicalization in the engine, required when the jobs run in the deterministic mode.";
+
+const VALIDATE_INPUT_HELP: &str =
+    "The configure file (JSON, TOML or YAML) or the CAR file to validate.";
+
+const VALIDATE_SCHEMA_HELP: &str =
+    "Print the JSON schema of the configure file instead of validating.";
+
+const OVERLAY_HELP: &str = "The overlay configure file merged on the input configure in order, e.g. the configure of the environment, the maps are merged by the key and the other items are replaced.";
+
+const VAR_HELP: &str = "The variable interpolated as `${KEY}` in the configure files, it overrides the host environment variable of the same name.";
+
+const VAR_ENV_HELP: &str =
+    "Allow the host environment variable to be interpolated as `${NAME}` in the configure files.";
+
+const RESOLVE_ARGS_HELP: &str = "The input configure or CAR file and the options as the running, e.g. `app.yaml --overlay prod.yaml --limited-fuel 1000`.";
+
+/// the default address of the job API.
+const DEFAULT_SERVE_LISTEN: &str = "127.0.0.1:7070";
+
+/// the default number of the finished jobs kept by the job server.
+const DEFAULT_SERVE_MAX_FINISHED_JOBS: usize = 1000;
+
 fn parse_envs(envs: &str) -> Result<(String, String)> {
     let parts: Vec<_> = envs.splitn(2, "=").collect();
     if parts.len() != 2 {
@@ -115,7 +198,7 @@ fn parse_nn_graph(envs: &str) -> Result<BlsNnGraph> {
 }
 
 fn parse_opts(opt: &str) -> Result<OptimizeOpts> {
-    let kvs: Vec<_> = opt.splitn(2, ",").collect();
+    let kvs: Vec<_> = opt.split(',').collect();
     if kvs.len() == 1 && kvs[0] == "help" {
         let mut max = 0;
         let options = OptimizeOpts::OPTIONS;
@@ -145,6 +228,7 @@ fn parse_opts(opt: &str) -> Result<OptimizeOpts> {
     }
     let mut opt = OptimizeOpts::default();
     opt.config(parsed)?;
+    opt.validate_features()?;
     Ok(opt)
 }
 
@@ -167,6 +251,8 @@ fn parse_module(module: &str) -> Result<BlocklessModule> {
         name: mods[0].into(),
         file: mods[1].into(),
         md5: String::new(), //didn't need check.
+        depends: Vec::new(),
+        sandbox: None,
     })
 }
 
@@ -188,7 +274,7 @@ fn parse_stdin(stdin: &str) -> Result<Stdin> {
     }
 }
 
-fn parse_listen(s: &str) -> Result<(SocketAddr, Option<u32>)> {
+pub(crate) fn parse_listen(s: &str) -> Result<(SocketAddr, Option<u32>)> {
     let splitn = s.splitn(2, "::");
     let saddrs = splitn.collect::<Vec<_>>();
     if saddrs.len() < 2 {
@@ -206,6 +292,19 @@ fn parse_listen(s: &str) -> Result<(SocketAddr, Option<u32>)> {
     bail!("could not resolve to any addresses")
 }
 
+fn parse_serve_addr(s: &str) -> Result<ServeAddr> {
+    if let Some(path) = s.strip_prefix("unix:") {
+        if path.is_empty() {
+            bail!("the unix socket path is empty");
+        }
+        return Ok(ServeAddr::Unix(PathBuf::from(path)));
+    }
+    match s.to_socket_addrs()?.next() {
+        Some(addr) => Ok(ServeAddr::Tcp(addr)),
+        None => bail!("could not resolve to any addresses"),
+    }
+}
+
 fn parse_dirs(s: &str) -> Result<(String, String)> {
     let mut parts = s.split("::");
     let host = parts.next().unwrap();
@@ -260,6 +359,22 @@ impl From<PermissionFlags> for PermissionsConfig {
     }
 }
 
+/// The variables interpolated in the configure files.
+#[derive(Parser, Debug, Default)]
+pub struct VarFlags {
+    #[clap(long = "var", value_name = "KEY=VALUE", help = VAR_HELP, value_parser = parse_envs)]
+    pub vars: Vec<(String, String)>,
+
+    #[clap(long = "var-env", value_name = "NAME", help = VAR_ENV_HELP)]
+    pub var_envs: Vec<String>,
+}
+
+impl VarFlags {
+    pub fn config_vars(&self) -> ConfigVars {
+        ConfigVars::new(self.vars.clone(), self.var_envs.clone())
+    }
+}
+
 #[derive(Parser, Debug)]
 pub struct StdioFlags {
     #[clap(long = "stdout", value_name = "STDOUT", help = STDOUT_HELP, value_parser = parse_stdout)]
@@ -313,14 +428,195 @@ pub fn get_styles() -> clap::builder::Styles {
         )
 }
 
+/// Precompile the WASM module or component ahead of time.
+///
+/// The precompiled artifact must be run with the same engine options(`-O`,
+/// `--feature-thread`, `--debug-info`, fuel and time limits) and on the same target.
+#[derive(Args, Debug)]
+pub(crate) struct CompileOpts {
+    #[clap(value_name = "INPUT", required = true, help = COMPILE_INPUT_HELP)]
+    pub input: String,
+
+    #[clap(long = "output", short = 'o', value_name = "OUTPUT", help = COMPILE_OUTPUT_HELP)]
+    pub output: Option<String>,
+
+    #[clap(long = "target", value_name = "TARGET", help = COMPILE_TARGET_HELP)]
+    pub target: Option<String>,
+
+    #[clap(long = "debug-info", value_name = "DEBUG-INFO", help = DEBUG_INFO_HELP)]
+    pub debug_info: bool,
+
+    #[clap(long = "feature-thread", value_name = "SUPPORT-THREAD", help = THREAD_SUPPORT_HELP)]
+    pub feature_thread: bool,
+
+    #[clap(long = "consume-fuel", help = COMPILE_CONSUME_FUEL_HELP)]
+    pub consume_fuel: bool,
+
+    #[clap(long = "epoch-interruption", help = COMPILE_EPOCH_INTERRUPTION_HELP)]
+    pub epoch_interruption: bool,
+
+    #[clap(long = "deterministic", help = COMPILE_DETERMINISTIC_HELP)]
+    pub deterministic: bool,
+
+    #[clap(long = "opt", short = 'O', value_name = "OPT=VAL,", help = OPTS_HELP,  value_parser = parse_opts)]
+    pub opts: Option<OptimizeOpts>,
+}
+
+impl CompileOpts {
+    /// the output path, the default is the input with the `.cwasm` extension.
+    pub fn output_path(&self) -> PathBuf {
+        match self.output.as_ref() {
+            Some(output) => PathBuf::from(output),
+            None => Path::new(&self.input).with_extension("cwasm"),
+        }
+    }
+
+    /// the configure for the engine options of the precompiling.
+    pub fn engine_config(&self) -> BlocklessConfig {
+        let mut conf = BlocklessConfig::new(&self.input);
+        conf.set_debug_info(self.debug_info);
+        conf.set_feature_thread(self.feature_thread);
+        // only the limits are enabled in the engine, the values are not used by precompiling.
+        if self.consume_fuel {
+            conf.limited_fuel(Some(u64::MAX));
+        }
+        if self.epoch_interruption {
+            conf.limited_time(Some(u64::MAX));
+        }
+        if self.deterministic {
+            conf.set_deterministic(Some(DeterministicConfig::default()));
+        }
+        if let Some(opts) = self.opts.clone() {
+            conf.opts = opts;
+        }
+        conf
+    }
+}
+
+/// The address of the job API.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ServeAddr {
+    Tcp(SocketAddr),
+    Unix(PathBuf),
+}
+
+/// Serve the jobs on the local HTTP API.
+///
+/// The jobs run concurrently on one shared engine with the pooling allocator,
+/// the fuel and time limits are always enabled in the engine, so every job can
+/// have its own limits.
+#[derive(Args, Debug)]
+pub(crate) struct ServeOpts {
+    #[clap(long = "listen", value_name = "ADDR", default_value = DEFAULT_SERVE_LISTEN, help = SERVE_LISTEN_HELP, value_parser = parse_serve_addr)]
+    pub listen: ServeAddr,
+
+    #[clap(long = "max-concurrent-jobs", value_name = "N", help = SERVE_MAX_CONCURRENT_JOBS_HELP)]
+    pub max_concurrent_jobs: Option<usize>,
+
+    #[clap(long = "max-finished-jobs", value_name = "N", default_value_t = DEFAULT_SERVE_MAX_FINISHED_JOBS, help = SERVE_MAX_FINISHED_JOBS_HELP)]
+    pub max_finished_jobs: usize,
+
+    #[clap(long = "debug-info", value_name = "DEBUG-INFO", help = DEBUG_INFO_HELP)]
+    pub debug_info: bool,
+
+    #[clap(long = "feature-thread", value_name = "SUPPORT-THREAD", help = THREAD_SUPPORT_HELP)]
+    pub feature_thread: bool,
+
+    #[clap(long = "deterministic", help = SERVE_DETERMINISTIC_HELP)]
+    pub deterministic: bool,
+
+    #[clap(long = "opt", short = 'O', value_name = "OPT=VAL,", help = OPTS_HELP,  value_parser = parse_opts)]
+    pub opts: Option<OptimizeOpts>,
+}
+
+impl ServeOpts {
+    /// the maximum number of the jobs running concurrently.
+    pub fn max_concurrent_jobs(&self) -> usize {
+        self.max_concurrent_jobs
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get()))
+            .max(1)
+    }
+
+    /// the configure for the engine shared by the jobs.
+    pub fn engine_config(&self) -> BlocklessConfig {
+        let mut conf = BlocklessConfig::new("");
+        conf.set_debug_info(self.debug_info);
+        conf.set_feature_thread(self.feature_thread);
+        // only the limits are enabled in the engine, the values are from the jobs.
+        conf.limited_fuel(Some(u64::MAX));
+        conf.limited_time(Some(u64::MAX));
+        if self.deterministic {
+            conf.set_deterministic(Some(DeterministicConfig::default()));
+        }
+        let mut opts = self.opts.clone().unwrap_or_default();
+        opts.pooling_allocator.get_or_insert(true);
+        conf.opts = opts;
+        conf
+    }
+}
+
+/// Validate the configure file without running the app.
+///
+/// The unknown and mistyped items are reported with the JSON path of the item.
+#[derive(Args, Debug)]
+pub(crate) struct ValidateOpts {
+    #[clap(value_name = "INPUT", required_unless_present = "schema", help = VALIDATE_INPUT_HELP)]
+    pub input: Option<String>,
+
+    #[clap(long = "schema", conflicts_with = "input", help = VALIDATE_SCHEMA_HELP)]
+    pub schema: bool,
+
+    #[clap(flatten)]
+    pub var_flags: VarFlags,
+}
+
+/// Print the configure resolved from the layers and the source of every item.
+///
+/// The layers are the input configure, the overlays and the command line options in order.
+#[derive(Args, Debug)]
+pub(crate) struct ResolveOpts {
+    #[clap(value_name = "ARGS", required = true, num_args = 1.., trailing_var_arg = true, allow_hyphen_values = true, help = RESOLVE_ARGS_HELP)]
+    pub args: Vec<String>,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum ConfigCommand {
+    Resolve(ResolveOpts),
+}
+
+/// Inspect the configure.
+#[derive(Args, Debug)]
+pub(crate) struct ConfigOpts {
+    #[clap(subcommand)]
+    pub command: ConfigCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum CliSubCommand {
+    Compile(CompileOpts),
+    Serve(ServeOpts),
+    Validate(ValidateOpts),
+    Config(ConfigOpts),
+}
+
 #[derive(Parser, Debug)]
 #[command(author, version = SHORT_VERSION, styles=get_styles(), arg_required_else_help = true, long_version = SHORT_VERSION, about = "Blockless WebAssembly Runtime")]
+#[command(subcommand_negates_reqs = true, args_conflicts_with_subcommands = true)]
 pub(crate) struct CliCommandOpts {
+    #[clap(subcommand)]
+    pub command: Option<CliSubCommand>,
+
     #[clap(long = "v86", value_name = "V86", required = false, help = V86_HELP )]
     pub v86: bool,
 
     #[clap(value_name = "INPUT", required = true, help = INPUT_HELP )]
-    pub input: String,
+    pub input: Option<String>,
+
+    #[clap(long = "overlay", value_name = "FILE", help = OVERLAY_HELP, conflicts_with = "v86")]
+    pub overlays: Vec<String>,
+
+    #[clap(flatten)]
+    pub var_flags: VarFlags,
 
     #[clap(long = "debug-info", value_name = "DEBUG-INFO", help = DEBUG_INFO_HELP)]
     pub debug_info: bool,
@@ -328,6 +624,9 @@ pub(crate) struct CliCommandOpts {
     #[clap(long = "feature-thread", value_name = "SUPPORT-THREAD", help = THREAD_SUPPORT_HELP)]
     pub feature_thread: bool,
 
+    #[clap(long = "max-threads", value_name = "N", help = MAX_THREADS_HELP, requires = "feature_thread")]
+    pub max_threads: Option<u32>,
+
     #[clap(long = "fs-root-path", value_name = "FS-ROOT-PATH", help = FS_ROOT_PATH_HELP)]
     pub fs_root_path: Option<String>,
 
@@ -352,6 +651,9 @@ pub(crate) struct CliCommandOpts {
     #[clap(long = "entry", value_name = "ENTERY", help = ENTRY_HELP)]
     pub entry: Option<String>,
 
+    #[clap(long = "invoke", value_name = "EXPORT", help = INVOKE_HELP)]
+    pub invoke: Option<String>,
+
     #[clap(flatten)]
     pub stdio: StdioFlags,
 
@@ -399,6 +701,45 @@ pub(crate) struct CliCommandOpts {
 
     #[clap(long = "nn-graph", value_name = "NN_GRAPH", value_parser = parse_nn_graph, help = NN_GRAPH_HELP)]
     pub nn_graph: Vec<BlsNnGraph>,
+
+    #[clap(long = "run-report", value_name = "RUN_REPORT", help = RUN_REPORT_HELP)]
+    pub run_report: Option<String>,
+
+    #[clap(long = "profile", value_name = "PROFILE", help = PROFILE_HELP)]
+    pub profile: Option<String>,
+
+    #[clap(long = "deterministic", help = DETERMINISTIC_HELP)]
+    pub deterministic: bool,
+
+    #[clap(long = "deterministic-seed", value_name = "SEED", help = DETERMINISTIC_SEED_HELP, requires = "deterministic")]
+    pub deterministic_seed: Option<u64>,
+
+    #[clap(long = "deterministic-start-time", value_name = "MILLISECONDS", help = DETERMINISTIC_START_TIME_HELP, requires = "deterministic")]
+    pub deterministic_start_time: Option<u64>,
+
+    #[clap(long = "allow-extension", value_name = "EXTENSION", help = ALLOW_EXTENSION_HELP, requires = "deterministic")]
+    pub allow_extensions: Vec<String>,
+
+    #[clap(long = "audit-trace", value_name = "FILE", help = AUDIT_TRACE_HELP)]
+    pub audit_trace: Option<String>,
+
+    #[clap(long = "audit-redact", value_name = "ARG", help = AUDIT_REDACT_HELP, requires = "audit_trace")]
+    pub audit_redact: Vec<String>,
+
+    #[clap(long = "http-listen", value_name = "ADDR", help = HTTP_LISTEN_HELP, conflicts_with_all = ["invoke", "run_report", "profile"])]
+    pub http_listen: Option<SocketAddr>,
+
+    #[clap(long = "cache-dir", value_name = "CACHE_DIR", help = CACHE_DIR_HELP, conflicts_with = "cache_config")]
+    pub cache_dir: Option<String>,
+
+    #[clap(long = "cache-max-size", value_name = "BYTES", help = CACHE_MAX_SIZE_HELP, requires = "cache_dir")]
+    pub cache_max_size: Option<u64>,
+
+    #[clap(long = "cache-config", value_name = "CACHE_CONFIG", help = CACHE_CONFIG_HELP)]
+    pub cache_config: Option<String>,
+
+    #[clap(long = "disable-cache", help = DISABLE_CACHE_HELP, conflicts_with_all = ["cache_dir", "cache_config"])]
+    pub disable_cache: bool,
 }
 
 impl CliCommandOpts {
@@ -418,68 +759,234 @@ impl CliCommandOpts {
 
     #[inline(always)]
     pub fn input_ref(&self) -> &str {
-        &self.input
+        self.input.as_deref().unwrap_or_default()
     }
 
-    pub fn into_config(self, conf: &mut CliConfig) -> Result<()> {
-        let envs = self.load_environment_vars()?;
+    #[inline(always)]
+    pub fn run_report(&self) -> Option<&String> {
+        self.run_report.as_ref()
+    }
 
-        conf.0.set_debug_info(self.debug_info);
-        conf.0.set_fs_root_path(self.fs_root_path);
-        conf.0.set_runtime_logger(self.runtime_logger);
-        conf.0.limited_memory(self.limited_memory);
-        conf.0.limited_fuel(self.limited_fuel);
-        conf.0.set_run_time(self.run_time);
-        conf.0.set_stdin_args(self.args);
-        conf.0.set_map_dirs(self.dirs);
-        conf.0.set_feature_thread(self.feature_thread);
-        conf.0.max_memory_size(self.max_memory_size);
-        conf.0.permissions_config = self.permission_flags.into();
+    #[inline(always)]
+    pub fn http_listen(&self) -> Option<SocketAddr> {
+        self.http_listen
+    }
 
-        // Handle IO settings
-        if let Some(stderr) = self.stdio.stderr {
-            conf.0.stdio.stderr(stderr);
+    /// the overlays, the variables and the command line layer of loading the configure.
+    pub fn layer_opts(&self, matches: &ArgMatches) -> Result<LayerOpts> {
+        Ok(LayerOpts {
+            overlays: self.overlays.clone(),
+            vars: self.var_flags.config_vars(),
+            command_line: Some(self.config_layer(matches)?),
+        })
+    }
+
+    /// the cache setting from the command line, `None` keeps the setting of the configure.
+    fn cache_setting(&self) -> Option<CacheSetting> {
+        if self.disable_cache {
+            return Some(CacheSetting::Disabled);
         }
-        if let Some(stdout) = self.stdio.stdout {
-            conf.0.stdio.stdout(stdout);
+        if let Some(path) = self.cache_dir.as_ref() {
+            return Some(CacheSetting::Directory {
+                path: path.clone(),
+                max_size: self.cache_max_size,
+            });
         }
-        if let Some(stdin) = self.stdio.stdin {
-            conf.0.stdio.stdin(stdin);
+        self.cache_config.clone().map(CacheSetting::ConfigFile)
+    }
+
+    /// the configure items of the options given, the last layer on the configure files,
+    /// the options not given keep the items of the configure, the `-O` options are the
+    /// raw values of the matches.
+    pub fn config_layer(&self, matches: &ArgMatches) -> Result<JsonValue> {
+        let mut layer = Map::new();
+        macro_rules! set_item {
+            ($key: literal, $value: expr) => {
+                layer.insert($key.to_string(), json!($value));
+            };
         }
-        if !self.permissions.is_empty() {
-            conf.0.set_permisions(self.permissions);
+        if let Some(cache) = self.cache_setting() {
+            let cache = match cache {
+                CacheSetting::Disabled => json!(false),
+                // the other items of the cache are removed, the same as the replacing.
+                CacheSetting::Directory { path, max_size } => json!({
+                    "enabled": null,
+                    "directory": path,
+                    "max_size": max_size,
+                    "config_file": null,
+                }),
+                CacheSetting::ConfigFile(file) => json!({
+                    "enabled": null,
+                    "directory": null,
+                    "max_size": null,
+                    "config_file": file,
+                }),
+                CacheSetting::Default => json!(null),
+            };
+            set_item!("cache", cache);
+        }
+        if self.debug_info {
+            set_item!("debug_info", true);
+        }
+        if let Some(path) = &self.fs_root_path {
+            set_item!("fs_root_path", path);
+        }
+        if let Some(logger) = &self.runtime_logger {
+            set_item!("runtime_logger", logger);
+        }
+        if let Some(memory) = self.limited_memory {
+            set_item!("limited_memory", memory);
+        }
+        if let Some(fuel) = self.limited_fuel {
+            set_item!("limited_fuel", fuel);
+        }
+        if let Some(run_time) = self.run_time {
+            set_item!("run_time", run_time);
+            set_item!("limited_time", run_time);
+        }
+        if let Some(profile) = &self.profile {
+            set_item!("profile", profile);
+        }
+        if self.deterministic {
+            set_item!(
+                "deterministic",
+                json!({
+                    "seed": self.deterministic_seed.unwrap_or_default(),
+                    "start_time": self.deterministic_start_time.unwrap_or_default(),
+                    "allowed_extensions": self.allow_extensions,
+                })
+            );
+        }
+        if let Some(path) = &self.audit_trace {
+            set_item!("audit", json!({"path": path, "redact": self.audit_redact}));
+        }
+        if let Some(name) = &self.invoke {
+            set_item!("invoke", json!({"name": name, "args": self.args}));
+        }
+        if !self.args.is_empty() {
+            set_item!("args", self.args);
+        }
+        if !self.dirs.is_empty() {
+            let dirs = self
+                .dirs
+                .iter()
+                .map(|(host, guest)| json!({"host": host, "guest": guest}));
+            set_item!("map_dirs", dirs.collect::<Vec<_>>());
+        }
+        if self.feature_thread {
+            set_item!("feature_thread", true);
+        }
+        if let Some(threads) = self.max_threads {
+            set_item!("max_threads", threads);
+        }
+        if let Some(size) = self.max_memory_size {
+            set_item!("max_memory_size", size);
+        }
+        let flags = &self.permission_flags;
+        let mut grants = Map::new();
+        for (name, grant) in [
+            ("allow_read", &flags.allow_read),
+            ("allow_write", &flags.allow_write),
+            ("allow_net", &flags.allow_net),
+            ("deny_read", &flags.deny_read),
+            ("deny_write", &flags.deny_write),
+            ("deny_net", &flags.deny_net),
+        ] {
+            match grant {
+                Some(PermissionGrant::All) => grants.insert(name.to_string(), json!(true)),
+                Some(PermissionGrant::List(list)) => grants.insert(name.to_string(), json!(list)),
+                None => None,
+            };
+        }
+        if flags.allow_all {
+            grants.insert("allow_all".to_string(), json!(true));
+        }
+        if !grants.is_empty() {
+            set_item!("permissions_config", grants);
         }
 
-        // Handle environment variables
-        conf.0.set_envs(envs);
-
-        conf.0.set_drivers_root_path(self.drivers_root_path);
-        let mut modules = self.modules;
-        let mut has_entry = false;
-        if let Some(e) = self.entry {
-            has_entry = true;
-            conf.0.set_entry(e);
+        macro_rules! stdio_item {
+            ($key: literal, $stdio: expr, $stdio_ty: ident) => {
+                match $stdio {
+                    Some($stdio_ty::Inherit) => set_item!($key, "inherit"),
+                    Some($stdio_ty::Null) => set_item!($key, "null"),
+                    Some($stdio_ty::FileName(file)) => set_item!($key, file),
+                    Some($stdio_ty::Buffer(_)) | None => {}
+                }
+            };
+        }
+        stdio_item!("stderr", &self.stdio.stderr, Stderr);
+        stdio_item!("stdout", &self.stdio.stdout, Stdout);
+        match &self.stdio.stdin {
+            Some(Stdin::Inherit) => set_item!("stdin", "inherit"),
+            Some(Stdin::Fixed(stdin)) => set_item!("stdin", stdin),
+            None => {}
         }
-        if !modules.is_empty() {
-            modules.push(BlocklessModule {
-                module_type: ModuleType::Entry,
-                name: String::new(),
-                file: self.input,
-                md5: String::new(),
+        if !self.permissions.is_empty() {
+            let urls = self.permissions.iter().map(|p| &p.url);
+            set_item!("permissions", urls.collect::<Vec<_>>());
+        }
+        let envs = self.load_environment_vars()?;
+        if !envs.is_empty() {
+            let envs = envs.into_iter().map(|(k, v)| (k, JsonValue::String(v)));
+            set_item!("envs", envs.collect::<Map<_, _>>());
+        }
+        if let Some(path) = &self.drivers_root_path {
+            set_item!("drivers_root_path", path);
+        }
+        if let Some(entry) = &self.entry {
+            set_item!("entry", entry);
+        }
+        if !self.modules.is_empty() {
+            let mut modules = self
+                .modules
+                .iter()
+                .map(|m| json!({"name": m.name, "file": m.file, "type": "module"}))
+                .collect::<Vec<_>>();
+            modules.push(json!({"file": self.input_ref(), "type": "entry"}));
+            set_item!("modules", modules);
+            if self.entry.is_none() {
+                // the entry function of the linked modules, see `reset_modules_model_entry`.
+                set_item!("entry", "_start");
+            }
+            set_item!("version", 1);
+        }
+        if self.nn {
+            set_item!("nn", true);
+        }
+        if !self.tcp_listens.is_empty() {
+            let listens = self.tcp_listens.iter().map(|(addr, fd)| match fd {
+                Some(fd) => format!("{addr}::{fd}"),
+                None => addr.to_string(),
             });
-            conf.0.set_modules(modules);
-            if !has_entry {
-                conf.0.reset_modules_model_entry();
+            set_item!("tcp_listens", listens.collect::<Vec<_>>());
+        }
+        if self.cli_exit_with_code {
+            set_item!("cli_exit_with_code", true);
+        }
+        if self.network_error_code {
+            set_item!("network_error_code", true);
+        }
+        if self.unknown_imports_trap {
+            set_item!("unknown_imports_trap", true);
+        }
+        if !self.nn_graph.is_empty() {
+            let graphs = self
+                .nn_graph
+                .iter()
+                .map(|g| json!({"format": g.format, "dir": g.dir}));
+            set_item!("nn_graph", graphs.collect::<Vec<_>>());
+        }
+        if let Some(opts) = matches.get_raw("opts").and_then(|opts| opts.last()) {
+            let mut optimize = Map::new();
+            for kv in opts.to_string_lossy().split(',') {
+                if let Some((key, val)) = kv.split_once('=') {
+                    optimize.insert(key.to_string(), json!(val));
+                }
             }
-            conf.0
-                .set_version(blockless::BlocklessConfigVersion::Version1);
-        }
-        conf.0.nn = self.nn;
-        conf.0.tcp_listens = self.tcp_listens;
-        conf.0.network_error_code = self.network_error_code;
-        conf.0.unknown_imports_trap = self.unknown_imports_trap;
-        conf.0.nn_graph = self.nn_graph;
-        Ok(())
+            set_item!("optimize", optimize);
+        }
+        Ok(JsonValue::Object(layer))
     }
 
     /// Load and merge environment variables from both the environment file and explicit --env arguments.
@@ -521,10 +1028,38 @@ impl CliCommandOpts {
 mod test {
     #[allow(unused)]
     use super::*;
-    use blockless::BlocklessConfigVersion;
+    use blockless::{AuditConfig, BlocklessConfigVersion, Invoke};
+    use clap::{CommandFactory, FromArgMatches};
     use std::io::Write;
     use tempfile::NamedTempFile;
 
+    use crate::config::CliConfig;
+    use crate::config_layers::ConfigLayers;
+
+    /// the layers of the base configure and the command line, the same as the running.
+    fn config_layers(base: &str, args: &[&str]) -> ConfigLayers {
+        let matches = CliCommandOpts::command()
+            .try_get_matches_from(args)
+            .unwrap();
+        let cli_opts = CliCommandOpts::from_arg_matches(&matches).unwrap();
+        let opts = cli_opts.layer_opts(&matches).unwrap();
+        let mut layers = ConfigLayers::default();
+        layers
+            .merge(serde_json::from_str(base).unwrap(), "base")
+            .unwrap();
+        layers
+            .merge(opts.command_line.unwrap(), "command line")
+            .unwrap();
+        layers
+    }
+
+    /// the configure of the command line on the base configure.
+    fn layered_config(base: &str, args: &[&str]) -> CliConfig {
+        CliConfig::from_layers(&config_layers(base, args)).unwrap()
+    }
+
+    const BASE: &str = r#"{"entry": "/a.wasm"}"#;
+
     #[test]
     fn test_cli_command_v86() {
         let cli = CliCommandOpts::try_parse_from(["cli", "test", "--v86"]).unwrap();
@@ -534,7 +1069,7 @@ mod test {
     #[test]
     fn test_cli_command() {
         let cli = CliCommandOpts::try_parse_from(["cli", "test", "--", "--test=10"]).unwrap();
-        assert_eq!(cli.input.as_str(), "test");
+        assert_eq!(cli.input_ref(), "test");
         assert_eq!(cli.args.len(), 1);
         assert_eq!(cli.args[0], "--test=10");
     }
@@ -543,7 +1078,7 @@ mod test {
     fn test_cli_command_env() {
         let cli = CliCommandOpts::try_parse_from(["cli", "test", "--env", "a=1", "--env", "b=2"])
             .unwrap();
-        assert_eq!(cli.input.as_str(), "test");
+        assert_eq!(cli.input_ref(), "test");
         assert_eq!(cli.envs.len(), 2);
         assert_eq!(cli.envs[0], ("a".to_string(), "1".to_string()));
         assert_eq!(cli.envs[1], ("b".to_string(), "2".to_string()));
@@ -604,7 +1139,7 @@ SERVICE_URL=${{BASE_URL}}/v1"#
             "http://www.google.com",
         ])
         .unwrap();
-        assert_eq!(cli.input.as_str(), "test");
+        assert_eq!(cli.input_ref(), "test");
         assert_eq!(cli.permissions.len(), 1);
         let perm = Permission {
             schema: "http".to_string(),
@@ -621,7 +1156,7 @@ SERVICE_URL=${{BASE_URL}}/v1"#
             .map(str::to_string)
             .collect::<Vec<String>>();
         let cli_opts = CliCommandOpts::try_parse_from(command_line).unwrap();
-        let pat = cli_opts.input.as_str();
+        let pat = cli_opts.input_ref();
         assert_eq!(pat, "test.wasm");
     }
 
@@ -651,14 +1186,7 @@ SERVICE_URL=${{BASE_URL}}/v1"#
 
     #[test]
     fn test_cli_command_signal_wasm() {
-        let command_line = r#"blockless_cli test.wasm --fs-root-path /"#;
-        let command_line = command_line
-            .split(" ")
-            .map(str::to_string)
-            .collect::<Vec<String>>();
-        let cli_opts = CliCommandOpts::try_parse_from(command_line).unwrap();
-        let mut cli_conf = CliConfig(BlocklessConfig::new("/a.wasm"));
-        cli_opts.into_config(&mut cli_conf);
+        let cli_conf = layered_config(BASE, &["blockless_cli", "test.wasm", "--fs-root-path", "/"]);
         assert_eq!(cli_conf.0.entry_ref(), "/a.wasm");
         assert!(matches!(
             cli_conf.0.version(),
@@ -669,13 +1197,8 @@ SERVICE_URL=${{BASE_URL}}/v1"#
     #[test]
     fn test_cli_command_modules_wasm() {
         let command_line = r#"blockless_cli test.wasm --fs-root-path / --module=test=/module.wasm"#;
-        let command_line = command_line
-            .split(" ")
-            .map(str::to_string)
-            .collect::<Vec<String>>();
-        let cli_opts = CliCommandOpts::try_parse_from(command_line).unwrap();
-        let mut cli_conf = CliConfig(BlocklessConfig::new("/a.wasm"));
-        cli_opts.into_config(&mut cli_conf);
+        let command_line = command_line.split(" ").collect::<Vec<_>>();
+        let cli_conf = layered_config(BASE, &command_line);
         assert_eq!(cli_conf.0.entry_ref(), "_start");
         assert!(matches!(
             cli_conf.0.version(),
@@ -687,17 +1210,466 @@ SERVICE_URL=${{BASE_URL}}/v1"#
     fn test_cli_command_modules_wasm_with_entry() {
         let command_line =
             r#"blockless_cli test.wasm --fs-root-path / --module=test=/module.wasm --entry=run"#;
-        let command_line = command_line
-            .split(" ")
-            .map(str::to_string)
-            .collect::<Vec<String>>();
-        let cli_opts = CliCommandOpts::try_parse_from(command_line).unwrap();
-        let mut cli_conf = CliConfig(BlocklessConfig::new("/a.wasm"));
-        cli_opts.into_config(&mut cli_conf);
+        let command_line = command_line.split(" ").collect::<Vec<_>>();
+        let cli_conf = layered_config(BASE, &command_line);
         assert_eq!(cli_conf.0.entry_ref(), "run");
         assert!(matches!(
             cli_conf.0.version(),
             BlocklessConfigVersion::Version1
         ));
     }
+
+    #[test]
+    fn test_cli_command_cache() {
+        let cli_conf = layered_config(
+            BASE,
+            &[
+                "cli",
+                "test.wasm",
+                "--cache-dir=/tmp/cache",
+                "--cache-max-size=1024",
+            ],
+        );
+        assert_eq!(
+            cli_conf.0.cache_ref(),
+            &CacheSetting::Directory {
+                path: "/tmp/cache".into(),
+                max_size: Some(1024),
+            }
+        );
+
+        let cli_conf = layered_config(BASE, &["cli", "test.wasm", "--disable-cache"]);
+        assert_eq!(cli_conf.0.cache_ref(), &CacheSetting::Disabled);
+
+        let base = r#"{"entry": "/a.wasm", "cache": false}"#;
+        let cli_conf = layered_config(base, &["cli", "test.wasm"]);
+        assert_eq!(cli_conf.0.cache_ref(), &CacheSetting::Disabled);
+
+        assert!(
+            CliCommandOpts::try_parse_from(["cli", "test.wasm", "--cache-max-size=1024"]).is_err()
+        );
+        assert!(
+            CliCommandOpts::try_parse_from([
+                "cli",
+                "test.wasm",
+                "--disable-cache",
+                "--cache-dir=/tmp/cache"
+            ])
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_command_compile() {
+        let cli_opts = CliCommandOpts::try_parse_from([
+            "cli",
+            "compile",
+            "test.wasm",
+            "--target=aarch64-unknown-linux-gnu",
+            "--consume-fuel",
+        ])
+        .unwrap();
+        let opts = match cli_opts.command {
+            Some(CliSubCommand::Compile(opts)) => opts,
+            _ => panic!("expected the compile subcommand"),
+        };
+        assert_eq!(opts.output_path(), PathBuf::from("test.cwasm"));
+        assert_eq!(opts.target.as_deref(), Some("aarch64-unknown-linux-gnu"));
+        let conf = opts.engine_config();
+        assert!(conf.get_limited_fuel().is_some());
+        assert!(conf.get_limited_time().is_none());
+
+        // the `compile` after the input is the argument of app.
+        let cli_opts = CliCommandOpts::try_parse_from(["cli", "test.wasm", "compile"]).unwrap();
+        assert!(cli_opts.command.is_none());
+        assert_eq!(cli_opts.input_ref(), "test.wasm");
+        assert_eq!(cli_opts.args, vec!["compile".to_string()]);
+    }
+
+    #[test]
+    fn test_cli_command_max_threads() {
+        let cli_conf = layered_config(
+            BASE,
+            &["cli", "test.wasm", "--feature-thread", "--max-threads=4"],
+        );
+        assert_eq!(cli_conf.0.max_threads(), Some(4));
+
+        // the maximum requires the threads.
+        let cli_opts = CliCommandOpts::try_parse_from(["cli", "test.wasm", "--max-threads=4"]);
+        assert!(cli_opts.is_err());
+
+        // the maximum of the configure is kept.
+        let base = r#"{"entry": "/a.wasm", "max_threads": 2}"#;
+        let cli_conf = layered_config(base, &["cli", "test.wasm", "--feature-thread"]);
+        assert_eq!(cli_conf.0.max_threads(), Some(2));
+    }
+
+    #[test]
+    fn test_cli_command_opts() {
+        let cli_opts = CliCommandOpts::try_parse_from([
+            "cli",
+            "test.wasm",
+            "-O",
+            "wasm_tail_call=y,wasm_memory64=yes,wasm_simd=no",
+        ])
+        .unwrap();
+        let opts = cli_opts.opts.unwrap();
+        assert_eq!(opts.wasm_tail_call, Some(true));
+        assert_eq!(opts.wasm_memory64, Some(true));
+        assert_eq!(opts.wasm_simd, Some(false));
+
+        // the relaxed SIMD requires the SIMD.
+        let cli_opts = CliCommandOpts::try_parse_from([
+            "cli",
+            "test.wasm",
+            "-O",
+            "wasm_simd=n,wasm_relaxed_simd=y",
+        ]);
+        assert!(cli_opts.is_err());
+    }
+
+    #[test]
+    fn test_cli_command_invoke() {
+        let cli_conf = layered_config(
+            BASE,
+            &["cli", "--invoke", "add", "test.wasm", "--", "-1", "2"],
+        );
+        assert_eq!(
+            cli_conf.0.invoke_ref(),
+            Some(&Invoke::new("add".into(), vec!["-1".into(), "2".into()]))
+        );
+
+        let cli_conf = layered_config(BASE, &["cli", "test.wasm"]);
+        assert_eq!(cli_conf.0.invoke_ref(), None);
+    }
+
+    #[test]
+    fn test_cli_command_profile() {
+        let cli_conf = layered_config(BASE, &["cli", "test.wasm", "--profile=out.json"]);
+        assert_eq!(cli_conf.0.profile_ref(), Some("out.json"));
+
+        let base = r#"{"entry": "/a.wasm", "profile": "conf.json"}"#;
+        let cli_conf = layered_config(base, &["cli", "test.wasm"]);
+        assert_eq!(cli_conf.0.profile_ref(), Some("conf.json"));
+    }
+
+    #[test]
+    fn test_cli_command_deterministic() {
+        let cli_conf = layered_config(
+            BASE,
+            &[
+                "cli",
+                "test.wasm",
+                "--deterministic",
+                "--deterministic-seed=7",
+                "--deterministic-start-time=1000",
+                "--allow-extension=http",
+                "--allow-extension=ipfs",
+            ],
+        );
+        assert_eq!(
+            cli_conf.0.deterministic_ref(),
+            Some(&DeterministicConfig {
+                seed: 7,
+                start_time: 1000,
+                allowed_extensions: vec!["http".into(), "ipfs".into()],
+            })
+        );
+
+        // the options of the deterministic mode require `--deterministic`.
+        let cli_opts =
+            CliCommandOpts::try_parse_from(["cli", "test.wasm", "--deterministic-seed=7"]);
+        assert!(cli_opts.is_err());
+
+        // the deterministic mode of the configure is kept.
+        let base = r#"{"entry": "/a.wasm", "deterministic": true}"#;
+        let cli_conf = layered_config(base, &["cli", "test.wasm"]);
+        assert_eq!(
+            cli_conf.0.deterministic_ref(),
+            Some(&DeterministicConfig::default())
+        );
+    }
+
+    #[test]
+    fn test_cli_command_audit_trace() {
+        let cli_conf = layered_config(
+            BASE,
+            &[
+                "cli",
+                "test.wasm",
+                "--audit-trace=audit.jsonl",
+                "--audit-redact=url",
+            ],
+        );
+        assert_eq!(
+            cli_conf.0.audit_ref(),
+            Some(&AuditConfig {
+                path: "audit.jsonl".into(),
+                redact: vec!["url".into()],
+            })
+        );
+
+        // the redacted arguments require `--audit-trace`.
+        let cli_opts = CliCommandOpts::try_parse_from(["cli", "test.wasm", "--audit-redact=url"]);
+        assert!(cli_opts.is_err());
+
+        // the audit trace of the configure is kept.
+        let base = r#"{"entry": "/a.wasm", "audit": "-"}"#;
+        let cli_conf = layered_config(base, &["cli", "test.wasm"]);
+        assert_eq!(cli_conf.0.audit_ref(), Some(&AuditConfig::new("-")));
+    }
+
+    #[test]
+    fn test_cli_command_http_listen() {
+        let cli_opts =
+            CliCommandOpts::try_parse_from(["cli", "proxy.wasm", "--http-listen=127.0.0.1:8080"])
+                .unwrap();
+        assert_eq!(
+            cli_opts.http_listen(),
+            Some("127.0.0.1:8080".parse().unwrap())
+        );
+
+        // the handler has no export to invoke.
+        let cli_opts = CliCommandOpts::try_parse_from([
+            "cli",
+            "proxy.wasm",
+            "--http-listen=127.0.0.1:8080",
+            "--invoke=add",
+        ]);
+        assert!(cli_opts.is_err());
+
+        let cli_opts = CliCommandOpts::try_parse_from(["cli", "proxy.wasm", "--http-listen=:8080"]);
+        assert!(cli_opts.is_err());
+    }
+
+    #[test]
+    fn test_cli_command_serve() {
+        let cli_opts = CliCommandOpts::try_parse_from([
+            "cli",
+            "serve",
+            "--listen=127.0.0.1:8080",
+            "--max-concurrent-jobs=4",
+        ])
+        .unwrap();
+        let opts = match cli_opts.command {
+            Some(CliSubCommand::Serve(opts)) => opts,
+            _ => panic!("expected the serve subcommand"),
+        };
+        assert_eq!(
+            opts.listen,
+            ServeAddr::Tcp("127.0.0.1:8080".parse().unwrap())
+        );
+        assert_eq!(opts.max_concurrent_jobs(), 4);
+        let conf = opts.engine_config();
+        assert!(conf.get_limited_fuel().is_some());
+        assert!(conf.get_limited_time().is_some());
+        assert_eq!(conf.opts.pooling_allocator, Some(true));
+
+        let cli_opts =
+            CliCommandOpts::try_parse_from(["cli", "serve", "--listen=unix:/tmp/bls.sock"])
+                .unwrap();
+        let Some(CliSubCommand::Serve(opts)) = cli_opts.command else {
+            panic!("expected the serve subcommand");
+        };
+        assert_eq!(opts.listen, ServeAddr::Unix("/tmp/bls.sock".into()));
+        assert!(CliCommandOpts::try_parse_from(["cli", "serve", "--listen=unix:"]).is_err());
+    }
+
+    #[test]
+    fn test_cli_command_validate() {
+        let cli_opts = CliCommandOpts::try_parse_from(["cli", "validate", "config.json"]).unwrap();
+        let Some(CliSubCommand::Validate(opts)) = cli_opts.command else {
+            panic!("expected the validate subcommand");
+        };
+        assert_eq!(opts.input.as_deref(), Some("config.json"));
+        assert!(!opts.schema);
+
+        let cli_opts = CliCommandOpts::try_parse_from(["cli", "validate", "--schema"]).unwrap();
+        let Some(CliSubCommand::Validate(opts)) = cli_opts.command else {
+            panic!("expected the validate subcommand");
+        };
+        assert!(opts.schema);
+        assert!(CliCommandOpts::try_parse_from(["cli", "validate"]).is_err());
+        assert!(
+            CliCommandOpts::try_parse_from(["cli", "validate", "--schema", "config.json"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_command_keeps_config() {
+        let base = r#"{
+            "entry": "lib.wasm",
+            "fs_root_path": "/",
+            "limited_fuel": 100,
+            "feature_thread": true,
+            "envs": {"A": "1", "B": "2"},
+            "permissions_config": {"allow_read": true, "deny_net": ["10.0.0.1"]}
+        }"#;
+        let cli_conf = layered_config(
+            base,
+            &[
+                "cli",
+                "lib.wasm",
+                "--env=B=3",
+                "--env=C=4",
+                "--allow-net=example.com",
+            ],
+        );
+        assert_eq!(cli_conf.0.fs_root_path_ref(), Some("/"));
+        assert_eq!(cli_conf.0.get_limited_fuel(), Some(100));
+        assert!(cli_conf.0.feature_thread());
+        assert_eq!(
+            cli_conf.0.envs_ref(),
+            &vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "3".to_string()),
+                ("C".to_string(), "4".to_string()),
+            ]
+        );
+        let perms = &cli_conf.0.permissions_config;
+        assert!(matches!(perms.allow_read, Some(PermissionGrant::All)));
+        assert!(matches!(perms.deny_net, Some(PermissionGrant::List(_))));
+        assert!(matches!(perms.allow_net, Some(PermissionGrant::List(_))));
+    }
+
+    #[test]
+    fn test_cli_command_config_resolve() {
+        let cli_opts = CliCommandOpts::try_parse_from([
+            "cli",
+            "config",
+            "resolve",
+            "app.yaml",
+            "--overlay",
+            "prod.yaml",
+            "--limited-fuel=10",
+        ])
+        .unwrap();
+        let Some(CliSubCommand::Config(ConfigOpts {
+            command: ConfigCommand::Resolve(opts),
+        })) = cli_opts.command
+        else {
+            panic!("expected the config resolve subcommand");
+        };
+        assert_eq!(
+            opts.args,
+            vec!["app.yaml", "--overlay", "prod.yaml", "--limited-fuel=10"]
+        );
+        assert!(CliCommandOpts::try_parse_from(["cli", "config", "resolve"]).is_err());
+
+        let cli_opts = CliCommandOpts::try_parse_from([
+            "cli",
+            "app.yaml",
+            "--overlay=base.yaml",
+            "--overlay=prod.yaml",
+        ])
+        .unwrap();
+        assert_eq!(cli_opts.overlays, vec!["base.yaml", "prod.yaml"]);
+
+        let cli_opts = CliCommandOpts::try_parse_from([
+            "cli",
+            "app.yaml",
+            "--var=NODE=node-1",
+            "--var=REGION=eu=1",
+            "--var-env=HOSTNAME",
+        ])
+        .unwrap();
+        assert_eq!(
+            cli_opts.var_flags.vars,
+            vec![
+                ("NODE".to_string(), "node-1".to_string()),
+                ("REGION".to_string(), "eu=1".to_string()),
+            ]
+        );
+        assert_eq!(cli_opts.var_flags.var_envs, vec!["HOSTNAME"]);
+        assert!(CliCommandOpts::try_parse_from(["cli", "app.yaml", "--var=NODE"]).is_err());
+        let cli_opts =
+            CliCommandOpts::try_parse_from(["cli", "validate", "app.yaml", "--var=NODE=1"])
+                .unwrap();
+        let Some(CliSubCommand::Validate(opts)) = cli_opts.command else {
+            panic!("expected the validate subcommand");
+        };
+        assert_eq!(opts.var_flags.vars.len(), 1);
+        assert!(
+            CliCommandOpts::try_parse_from(["cli", "app.car", "--v86", "--overlay=prod.yaml"])
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_cli_command_config_layer() {
+        let base = r#"{
+            "entry": "lib.wasm",
+            "limited_fuel": 100,
+            "envs": {"A": "1", "B": "2"},
+            "cache": {"config_file": "cache.toml"},
+            "permissions_config": {"allow_read": true, "deny_net": ["10.0.0.1"]}
+        }"#;
+        let args = [
+            "cli",
+            "lib.wasm",
+            "--limited-fuel=10",
+            "--run-time=20",
+            "--env=B=3",
+            "--allow-net=example.com",
+            "--stdout=null",
+            "--dir=a::b",
+            "--tcplisten=127.0.0.1:8080::3",
+            "--cache-dir=/tmp/cache",
+            "--deterministic",
+            "--deterministic-seed=7",
+            "--invoke=add",
+            "--permission=http://httpbin.org/anything",
+            "-O",
+            "opt_level=s,memory_may_move=no",
+            "1",
+            "2",
+        ];
+        let layers = config_layers(base, &args);
+        let conf = CliConfig::from_layers(&layers).unwrap().0;
+        assert_eq!(conf.get_limited_fuel(), Some(10));
+        assert_eq!(conf.get_limited_time(), Some(20));
+        assert_eq!(
+            conf.envs_ref(),
+            &vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "3".to_string()),
+            ]
+        );
+        assert_eq!(
+            conf.stdin_args_ref(),
+            &vec!["1".to_string(), "2".to_string()]
+        );
+        assert_eq!(conf.dirs, vec![("a".to_string(), "b".to_string())]);
+        assert_eq!(
+            conf.tcp_listens,
+            vec![("127.0.0.1:8080".parse().unwrap(), Some(3))]
+        );
+        assert_eq!(
+            conf.invoke_ref(),
+            Some(&Invoke::new("add".into(), vec!["1".into(), "2".into()]))
+        );
+        assert_eq!(conf.deterministic_ref().map(|d| d.seed), Some(7));
+        assert_eq!(conf.opts.memory_may_move, Some(false));
+        assert_eq!(
+            conf.cache_ref(),
+            &CacheSetting::Directory {
+                path: "/tmp/cache".into(),
+                max_size: None,
+            }
+        );
+        let perms = &conf.permissions_config;
+        assert!(matches!(perms.allow_read, Some(PermissionGrant::All)));
+        assert!(matches!(perms.deny_net, Some(PermissionGrant::List(_))));
+        assert!(matches!(perms.allow_net, Some(PermissionGrant::List(_))));
+        assert!(matches!(conf.stdio.stdout, Stdout::Null));
+        assert_eq!(conf.permisions_ref().len(), 1);
+        assert_eq!(
+            layers.resolved()["sources"]["envs.A"],
+            serde_json::json!("base")
+        );
+        assert_eq!(
+            layers.resolved()["sources"]["envs.B"],
+            serde_json::json!("command line")
+        );
+    }
 }
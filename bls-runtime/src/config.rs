@@ -1,8 +1,5 @@
-use anyhow::{Context, Result, bail};
-use blockless::{
-    self, BlocklessModule, LoggerLevel, ModuleType, OptimizeOpts, Stderr, Stdin, Stdio, Stdout,
-};
-use blockless::{BlocklessConfig, DriverConfig, MultiAddr, Permission};
+use anyhow::{Result, anyhow, bail};
+use blockless::BlocklessConfig;
 use json::{self, JsonValue};
 use rust_car::reader::{self, CarReader};
 use rust_car::utils::{extract_ipld, ipld_write};
@@ -11,6 +8,8 @@ use std::ffi::OsStr;
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
 
+use crate::config_file::{ConfigFile, ConfigFormat, ConfigOverrides};
+use crate::config_layers::{ConfigLayers, LayerOpts};
 use crate::v86config::V86config;
 
 pub(crate) struct CliConfig(pub(crate) BlocklessConfig);
@@ -46,206 +45,111 @@ impl CliConfig {
 
     /// config the wasm file as entry file
     /// current directory as the root path
-    pub fn new_with_wasm(wasm_file: impl AsRef<Path>) -> CliConfig {
-        let file_path = wasm_file.as_ref();
-        let file_name = file_path.file_name().unwrap();
-        let log_file = Self::defaut_logger_file(file_name);
-        let mut bconf = BlocklessConfig::new(file_path.to_str().unwrap());
-        bconf.set_fs_root_path(Some(".".to_string()));
-        bconf.set_runtime_logger_level(LoggerLevel::WARN);
-        if let Some(log_file) = log_file.as_ref() {
-            bconf.set_runtime_logger(Some(format!("{log_file}.log")));
-        }
-        CliConfig(bconf)
+    /// the command line layer of the options is merged on it.
+    pub fn from_wasm(wasm_file: &str, opts: &LayerOpts) -> Result<Self> {
+        Self::from_layers(&wasm_layers(wasm_file, opts)?)
     }
 
-    fn optimize_options(opt_json: &JsonValue) -> Result<OptimizeOpts> {
-        let mut opts: OptimizeOpts = OptimizeOpts::default();
-        let opt_items = opt_json
-            .entries()
-            .map(|item| (item.0.to_string(), json::stringify(item.0)))
-            .collect::<Vec<_>>();
-        opts.config(opt_items)?;
-        Ok(opts)
+    pub(crate) fn from_json_string(json_string: String) -> Result<Self> {
+        Self::from_text(&json_string, ConfigFormat::Json)
     }
 
-    fn map_dirs(map_dir: &JsonValue) -> anyhow::Result<Vec<(String, String)>> {
-        let mut ret = Vec::new();
-        if !map_dir.is_null() {
-            if !map_dir.is_array() {
-                bail!("the map dir item should be array.");
-            }
-            for obj in map_dir.members() {
-                let host = obj["host"]
-                    .as_str()
-                    .context("host item is not define.")?
-                    .to_string();
-                let guest = obj["guest"]
-                    .as_str()
-                    .context("guest item is not define.")?
-                    .to_string();
-                ret.push((host, guest));
-            }
-        }
-        Ok(ret)
-    }
-
-    fn permissions(permission_json: &JsonValue) -> Vec<Permission> {
-        match *permission_json {
-            JsonValue::Array(ref perms) => perms
-                .iter()
-                .filter_map(|p| {
-                    let p = p.as_str();
-                    match p {
-                        Some(p) => {
-                            let bs = p.as_bytes();
-                            if let Ok(addr) = MultiAddr::parse(bs) {
-                                if let Ok(schema) = addr.schema() {
-                                    Some(Permission {
-                                        schema: schema.into(),
-                                        url: p.into(),
-                                    })
-                                } else {
-                                    None
-                                }
-                            } else {
-                                None
-                            }
-                        }
-                        None => None,
-                    }
-                })
-                .collect(),
-            _ => Vec::new(),
-        }
+    /// parse the configure from the text of the format, the variables must be replaced.
+    fn from_text(text: &str, format: ConfigFormat) -> Result<Self> {
+        let config = ConfigFile::parse(text, format)?;
+        Ok(CliConfig(config.into_blockless_config()?))
     }
 
-    fn drivers(driver_json: &JsonValue) -> Vec<DriverConfig> {
-        match *driver_json {
-            JsonValue::Array(ref drvs_cfg) => {
-                let cfgs: Vec<DriverConfig> = drvs_cfg
-                    .iter()
-                    .map(|c| {
-                        let schema = c["schema"].as_str().map(String::from);
-                        let path = c["path"].as_str().map(String::from);
-                        (schema, path)
-                    })
-                    .filter(|(s, p)| s.is_some() && p.is_some())
-                    .map(|(s, p)| DriverConfig::new(s.unwrap(), p.unwrap()))
-                    .collect::<Vec<_>>();
-                cfgs
-            }
-            _ => Vec::new(),
+    /// apply the config overrides of the job, the keys are the same as the configure file,
+    /// only the limits and the per-run items can be overridden.
+    pub(crate) fn apply_overrides(&mut self, overrides: &JsonValue) -> Result<()> {
+        if overrides.is_null() {
+            return Ok(());
         }
+        ConfigOverrides::from_json(&overrides.dump())?.apply(&mut self.0)
     }
 
-    fn modules(modules: &JsonValue) -> Vec<BlocklessModule> {
-        match *modules {
-            JsonValue::Array(ref modules_cfg) => modules_cfg
-                .iter()
-                .map(|c| {
-                    let name = c["name"].as_str().map(String::from).unwrap_or_default();
-                    let file = c["file"].as_str().map(String::from).unwrap_or_default();
-                    let md5 = c["md5"].as_str().map(String::from).unwrap_or_default();
-                    let module_type = c["type"]
-                        .as_str()
-                        .map(ModuleType::parse_from_str)
-                        .unwrap_or(ModuleType::Module);
-                    BlocklessModule {
-                        module_type,
-                        name,
-                        file,
-                        md5,
-                    }
-                })
-                .collect(),
-            _ => Vec::new(),
-        }
+    pub fn from_data(
+        data: String,
+        format: ConfigFormat,
+        root_suffix: Option<String>,
+    ) -> Result<Self> {
+        let opts = LayerOpts::default();
+        Self::from_layers(&config_layers(data, format, "config", root_suffix, &opts)?)
     }
 
-    fn from_json_string(json_string: String) -> Result<Self> {
-        let json_obj = json::parse(&json_string)?;
-        let fs_root_path: Option<String> = json_obj["fs_root_path"].as_str().map(String::from);
-        let drivers_root_path: Option<String> =
-            json_obj["drivers_root_path"].as_str().map(String::from);
-        let limited_fuel: Option<u64> = json_obj["limited_fuel"].as_u64();
-        let runtime_logger = json_obj["runtime_logger"].as_str().map(String::from);
-        let runtime_logger_level = json_obj["runtime_logger_level"]
-            .as_str()
-            .map(LoggerLevel::from);
-        let limited_memory: Option<u64> = json_obj["limited_memory"].as_u64();
-        let max_memory_size: Option<u64> = json_obj["max_memory_size"].as_u64();
-        let extensions_path: Option<String> =
-            json_obj["extensions_path"].as_str().map(String::from);
-        let stdin: Option<&str> = json_obj["stdin"].as_str();
-        let stdout: Option<&str> = json_obj["stdout"].as_str();
-        let stderr: Option<&str> = json_obj["stderr"].as_str();
-        let debug_info: Option<bool> = json_obj["debug_info"].as_bool();
-        let run_time: Option<u64> = json_obj["run_time"].as_u64();
-
-        let drvs = Self::drivers(&json_obj["drivers"]);
-        let modules = Self::modules(&json_obj["modules"]);
-        let perms: Vec<Permission> = Self::permissions(&json_obj["permissions"]);
-        let entry: &str = json_obj["entry"].as_str().unwrap();
-        let version = json_obj["version"].as_usize();
-        let dirs = Self::map_dirs(&json_obj["map_dirs"])?;
-        let mut bc = BlocklessConfig::new(entry);
-        //if has the optimize item.
-        if json_obj["optimize"].is_object() {
-            bc.opts = Self::optimize_options(&json_obj["optimize"])?;
-        }
-        bc.set_map_dirs(dirs);
-        bc.set_modules(modules);
-        bc.extensions_path(extensions_path);
-        bc.set_fs_root_path(fs_root_path);
-        bc.drivers(drvs);
-        // the set debug mode
-        if let Some(b) = debug_info {
-            bc.set_debug_info(b);
-        }
-        if let Some(l) = runtime_logger_level {
-            bc.set_runtime_logger_level(l);
-        }
-        bc.set_permisions(perms);
-        bc.set_runtime_logger(runtime_logger);
-        bc.set_drivers_root_path(drivers_root_path);
-        bc.limited_fuel(limited_fuel);
-        bc.limited_memory(limited_memory);
-        bc.max_memory_size(max_memory_size);
-        bc.set_run_time(run_time);
-        if let Some(v) = version {
-            bc.set_version(v.into());
-        }
-        let stdin = match stdin {
-            Some(s) => {
-                if s == "inherit" {
-                    Stdin::Inherit
-                } else {
-                    Stdin::Fixed(s.to_string())
-                }
-            }
-            _ => Stdin::Fixed(String::new()),
-        };
-        bc.stdio = Stdio {
-            stdin,
-            stdout: stdio_cfg!(stdout, Stdout, FileName),
-            stderr: stdio_cfg!(stderr, Stderr, FileName),
-        };
-        Ok(CliConfig(bc))
+    /// load the configure file and the overlay files on it, the format is detected
+    /// by the extension.
+    pub fn from_file(path: &str, opts: &LayerOpts) -> Result<Self> {
+        Self::from_layers(&file_layers(path, opts)?)
+    }
+
+    /// convert the merged layers into the configure.
+    pub(crate) fn from_layers(layers: &ConfigLayers) -> Result<Self> {
+        Ok(CliConfig(layers.config()?.into_blockless_config()?))
     }
+}
 
-    pub fn from_data(data: String, root_suffix: Option<String>) -> Result<Self> {
-        let data = replace_vars(data, root_suffix)?;
-        Self::from_json_string(data)
+/// the layers of the configure text and the overlay files in order, the `$ROOT` and
+/// `$ENV_ROOT_PATH` of every layer are replaced before parsed, and the `${VAR}` of the
+/// string items are interpolated before merged.
+fn config_layers(
+    data: String,
+    format: ConfigFormat,
+    source: &str,
+    root_suffix: Option<String>,
+    opts: &LayerOpts,
+) -> Result<ConfigLayers> {
+    let mut layers = ConfigLayers::new(opts.vars.clone());
+    let data = replace_vars(data, root_suffix.clone())?;
+    layers.merge_text(&data, format, source)?;
+    for path in &opts.overlays {
+        let text = fs::read_to_string(path)
+            .map_err(|e| anyhow!("failed to read the overlay {path}: {e}"))?;
+        let text = replace_vars(text, root_suffix.clone())?;
+        layers.merge_text(&text, ConfigFormat::from_path(path), path)?;
     }
+    merge_command_line(&mut layers, opts)?;
+    Ok(layers)
+}
 
-    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
-        let values = fs::read_to_string(path)?;
-        let json_string = replace_vars(values, None)?;
-        Self::from_json_string(json_string)
+/// the command line options are the last layer, they are not interpolated.
+fn merge_command_line(layers: &mut ConfigLayers, opts: &LayerOpts) -> Result<()> {
+    match &opts.command_line {
+        Some(layer) => layers.merge(layer.clone(), "command line"),
+        None => Ok(()),
     }
 }
 
+/// the layers of the wasm file and the command line options, the wasm file is the
+/// entry, the current directory is the root path and the log file is named after it.
+fn wasm_layers(wasm_file: &str, opts: &LayerOpts) -> Result<ConfigLayers> {
+    if !opts.overlays.is_empty() {
+        bail!("the overlays must be merged on the config file or the car file.");
+    }
+    let mut layer = serde_json::json!({
+        "entry": wasm_file,
+        "fs_root_path": ".",
+        "runtime_logger_level": "warn",
+    });
+    let log_file = Path::new(wasm_file)
+        .file_name()
+        .and_then(CliConfig::defaut_logger_file);
+    if let Some(log_file) = log_file {
+        layer["runtime_logger"] = format!("{log_file}.log").into();
+    }
+    let mut layers = ConfigLayers::new(opts.vars.clone());
+    layers.merge(layer, wasm_file)?;
+    merge_command_line(&mut layers, opts)?;
+    Ok(layers)
+}
+
+/// the layers of the configure file and the overlay files.
+fn file_layers(path: &str, opts: &LayerOpts) -> Result<ConfigLayers> {
+    let data = fs::read_to_string(path)?;
+    config_layers(data, ConfigFormat::from_path(path), path, None, opts)
+}
+
 fn env_variables(cid: Option<String>) -> Result<Vec<EnvVar>> {
     let mut vars = Vec::new();
     match std::env::var("ENV_ROOT_PATH") {
@@ -278,18 +182,69 @@ pub(crate) fn replace_vars(json_str: String, cid: Option<String>) -> Result<Stri
     Ok(raw_json)
 }
 
-pub(crate) fn load_from_car<T, F>(car_reader: &mut T, call: F) -> Result<Config>
-where
-    F: Fn(String, Option<String>) -> Result<Config>,
-    T: CarReader,
-{
-    let cid = car_reader.search_file_cid("config.json")?;
+/// the configure of the CAR file, the `config.json`, `config.toml`, `config.yaml`
+/// or `config.yml` is searched in order, the root suffix is the first root of the CAR file.
+fn car_config<T: CarReader>(car_reader: &mut T) -> Result<(String, ConfigFormat, Option<String>)> {
+    let found = ConfigFormat::CAR_FILES.iter().find_map(|(name, format)| {
+        car_reader
+            .search_file_cid(name)
+            .ok()
+            .map(|cid| (cid, *format))
+    });
+    let Some((cid, format)) = found else {
+        bail!("the configure file is not found in the car file.");
+    };
     let mut data = Vec::new();
     ipld_write(car_reader, cid, &mut data)?;
-    let raw_json = String::from_utf8(data)?;
+    let raw_config = String::from_utf8(data)?;
     let roots = car_reader.header().roots();
     let root_suffix = roots.first().map(|c| c.to_string());
-    call(raw_json, root_suffix)
+    Ok((raw_config, format, root_suffix))
+}
+
+/// load the configure of the CAR file.
+pub(crate) fn load_from_car<T, F>(car_reader: &mut T, call: F) -> Result<Config>
+where
+    F: Fn(String, ConfigFormat, Option<String>) -> Result<Config>,
+    T: CarReader,
+{
+    let (raw_config, format, root_suffix) = car_config(car_reader)?;
+    call(raw_config, format, root_suffix)
+}
+
+fn is_car_file(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("car"))
+}
+
+/// validate the configure file, or the configure of the CAR file, with the variables.
+pub(crate) fn validate_config_file(path: &str, opts: &LayerOpts) -> Result<()> {
+    if is_car_file(path) {
+        let mut reader = reader::new_v1(File::open(path)?)?;
+        load_from_car(&mut reader, |raw, format, root_suffix| {
+            new_cliconfig(raw, format, root_suffix, opts)
+        })?;
+    } else {
+        CliConfig::from_file(path, opts)?;
+    }
+    Ok(())
+}
+
+/// the layers of the wasm file, the configure file or the configure of the CAR file,
+/// and the overlay files and the command line on it, the files of the CAR file are
+/// not extracted.
+pub(crate) fn resolve_layers(path: &str, opts: &LayerOpts) -> Result<ConfigLayers> {
+    let ext = Path::new(path).extension().and_then(OsStr::to_str);
+    if let Some("wasm" | "wasi" | "wat" | "cwasm") = ext.map(str::to_ascii_lowercase).as_deref() {
+        return wasm_layers(path, opts);
+    }
+    if !is_car_file(path) {
+        return file_layers(path, opts);
+    }
+    let mut reader = reader::new_v1(File::open(path)?)?;
+    let (raw_config, format, root_suffix) = car_config(&mut reader)?;
+    config_layers(raw_config, format, path, root_suffix, opts)
 }
 
 #[cfg(test)]
@@ -297,7 +252,9 @@ pub(crate) fn load_cli_config_from_car<T>(car_reader: &mut T) -> Result<CliConfi
 where
     T: CarReader,
 {
-    let rs = load_from_car(car_reader, new_cliconfig);
+    let rs = load_from_car(car_reader, |raw_config, format, root_suffix| {
+        new_cliconfig(raw_config, format, root_suffix, &LayerOpts::default())
+    });
     rs.map(|r| match r {
         Config::CliConfig(c) => *c,
         _ => unreachable!("can be reach!"),
@@ -306,7 +263,7 @@ where
 
 pub(crate) fn load_extract_from_car<F>(f: File, call: F) -> Result<Config>
 where
-    F: Fn(String, Option<String>) -> Result<Config>,
+    F: Fn(String, ConfigFormat, Option<String>) -> Result<Config>,
 {
     let mut reader = reader::new_v1(f)?;
     let cfg = load_from_car(&mut reader, call)?;
@@ -327,14 +284,23 @@ where
     Ok(cfg)
 }
 
-fn new_cliconfig(raw_json: String, root_suffix: Option<String>) -> Result<Config> {
-    let mut cli_cfg = CliConfig::from_data(raw_json, root_suffix)?;
+/// the configure of the CAR file with the overlay files and the variables.
+fn new_cliconfig(
+    raw_config: String,
+    format: ConfigFormat,
+    root_suffix: Option<String>,
+    opts: &LayerOpts,
+) -> Result<Config> {
+    let layers = config_layers(raw_config, format, "config", root_suffix, opts)?;
+    let mut cli_cfg = CliConfig::from_layers(&layers)?;
     cli_cfg.0.set_is_carfile(true);
     Ok(Config::CliConfig(Box::new(cli_cfg)))
 }
 
-pub(crate) fn load_cli_config_extract_from_car(f: File) -> Result<CliConfig> {
-    let rs = load_extract_from_car(f, new_cliconfig);
+pub(crate) fn load_cli_config_extract_from_car(f: File, opts: &LayerOpts) -> Result<CliConfig> {
+    let rs = load_extract_from_car(f, |raw_config, format, root_suffix| {
+        new_cliconfig(raw_config, format, root_suffix, opts)
+    });
     rs.map(|r| match r {
         Config::CliConfig(c) => *c,
         _ => unreachable!("can be reach!"),
@@ -346,7 +312,10 @@ mod test {
     #![allow(unused)]
     use std::ffi::OsString;
 
-    use blockless::BlocklessConfigVersion;
+    use blockless::{
+        AuditConfig, BlocklessConfigVersion, CacheSetting, DeterministicConfig, Invoke,
+        LoggerLevel, PermissionGrant,
+    };
 
     use super::*;
 
@@ -364,8 +333,8 @@ mod test {
     }
 
     #[test]
-    fn test_new_with_wasm() {
-        let cliconf = CliConfig::new_with_wasm("test.wasm");
+    fn test_from_wasm() {
+        let cliconf = CliConfig::from_wasm("test.wasm", &LayerOpts::default()).unwrap();
         let current = Some(".");
         let root_path = cliconf.0.fs_root_path_ref();
         assert_eq!(root_path, current);
@@ -412,7 +381,7 @@ mod test {
         unsafe {
             std::env::set_var("ENV_ROOT_PATH", "target");
         }
-        let config = CliConfig::from_data(data, None).unwrap();
+        let config = CliConfig::from_data(data, ConfigFormat::Json, None).unwrap();
         assert!(matches!(
             config.0.version(),
             BlocklessConfigVersion::Version1
@@ -420,6 +389,68 @@ mod test {
         assert_eq!(config.0.modules_ref().len(), 2);
     }
 
+    #[test]
+    fn test_load_config_sandbox() {
+        let data = r#"{
+            "version": 1,
+            "fs_root_path": "/",
+            "entry": "_start",
+            "modules": [
+                {
+                    "file": "lib.wasm",
+                    "name": "lib",
+                    "type": "module",
+                    "md5": "",
+                    "sandbox": {
+                        "permissions": ["http://httpbin.org/anything"],
+                        "permissions_config": {"allow_read": true},
+                        "envs": {"LIB_KEY": "lib"},
+                        "limited_fuel": 1000,
+                        "max_memory_size": 65536
+                    }
+                },
+                {"file": "run.wasm", "name": "run", "type": "entry", "md5": ""}
+            ]
+        }"#
+        .to_string();
+        let config = CliConfig::from_json_string(data).unwrap();
+        let modules = config.0.modules_ref();
+        let sandbox = modules[0].sandbox.clone().unwrap();
+        assert_eq!(sandbox.permissions.len(), 1);
+        assert!(matches!(
+            sandbox.permissions_config.allow_read,
+            Some(PermissionGrant::All)
+        ));
+        assert!(sandbox.permissions_config.allow_write.is_none());
+        assert_eq!(
+            sandbox.envs,
+            vec![("LIB_KEY".to_string(), "lib".to_string())]
+        );
+        assert_eq!(sandbox.limited_fuel, Some(1000));
+        assert_eq!(sandbox.max_memory_size, Some(65536));
+        assert!(modules[1].sandbox.is_none());
+    }
+
+    #[test]
+    fn test_depends_from_json() {
+        let data = r#"{
+            "version": 1,
+            "fs_root_path": "/",
+            "entry": "_start",
+            "modules": [
+                {"file": "base.wasm", "name": "base", "type": "module", "md5": ""},
+                {"file": "lib.wasm", "name": "lib", "type": "module", "md5": "", "depends": ["base"]},
+                {"file": "run.wasm", "name": "run", "type": "entry", "md5": ""}
+            ]
+        }"#
+        .to_string();
+        let config = CliConfig::from_json_string(data).unwrap();
+        let modules = config.0.modules_ref();
+        assert!(modules[0].depends.is_empty());
+        assert_eq!(modules[1].depends, vec!["base".to_string()]);
+        assert!(modules[2].depends.is_empty());
+    }
+
     #[test]
     fn test_from_json() {
         let data = r#"{
@@ -443,6 +474,40 @@ mod test {
         ));
         assert_eq!(config.0.get_limited_memory(), Some(30));
         assert_eq!(config.0.get_limited_fuel(), Some(200000000));
+        assert_eq!(config.0.get_limited_time(), None);
+    }
+
+    #[test]
+    fn test_limited_time_from_json() {
+        let data = r#"{
+            "fs_root_path": "/",
+            "entry": "lib.wasm",
+            "run_time": 1000
+        }"#
+        .to_string();
+        let config = CliConfig::from_json_string(data).unwrap();
+        assert_eq!(config.0.get_limited_time(), Some(1000));
+
+        let data = r#"{
+            "fs_root_path": "/",
+            "entry": "lib.wasm",
+            "run_time": 1000,
+            "limited_time": 500
+        }"#
+        .to_string();
+        let config = CliConfig::from_json_string(data).unwrap();
+        assert_eq!(config.0.get_limited_time(), Some(500));
+    }
+
+    #[test]
+    fn test_max_threads_from_json() {
+        let data = r#"{"entry": "lib.wasm", "max_threads": 8}"#.to_string();
+        let config = CliConfig::from_json_string(data).unwrap();
+        assert_eq!(config.0.max_threads(), Some(8));
+
+        let data = r#"{"entry": "lib.wasm"}"#.to_string();
+        let config = CliConfig::from_json_string(data).unwrap();
+        assert_eq!(config.0.max_threads(), None);
     }
 
     #[test]
@@ -482,4 +547,287 @@ mod test {
         .0;
         assert_eq!(bls_config.fix_stdin_ref(), Some("test"));
     }
+
+    #[test]
+    fn test_cache_from_json() {
+        let cache = |cache: &str| {
+            let data = format!(r#"{{"entry": "lib.wasm", "cache": {cache}}}"#);
+            CliConfig::from_json_string(data).map(|c| c.0.cache_ref().clone())
+        };
+        let config = CliConfig::from_json_string(r#"{"entry": "lib.wasm"}"#.to_string()).unwrap();
+        assert_eq!(config.0.cache_ref(), &CacheSetting::Default);
+        assert_eq!(cache("false").unwrap(), CacheSetting::Disabled);
+        assert_eq!(
+            cache(r#"{"enabled": false}"#).unwrap(),
+            CacheSetting::Disabled
+        );
+        assert_eq!(
+            cache(r#"{"directory": "/tmp/cache", "max_size": 1024}"#).unwrap(),
+            CacheSetting::Directory {
+                path: "/tmp/cache".into(),
+                max_size: Some(1024),
+            }
+        );
+        assert_eq!(
+            cache(r#"{"config_file": "cache.toml"}"#).unwrap(),
+            CacheSetting::ConfigFile("cache.toml".into())
+        );
+        assert!(cache(r#""/tmp/cache""#).is_err());
+        assert!(cache(r#"{"max_size": 1024}"#).is_err());
+        assert!(cache(r#"{"directory": "/tmp/cache", "max_size": "1G"}"#).is_err());
+        assert!(cache(r#"{"directory": "/tmp", "config_file": "cache.toml"}"#).is_err());
+    }
+
+    #[test]
+    fn test_invoke_from_json() {
+        let invoke = |invoke: &str| {
+            let data = format!(r#"{{"entry": "lib.wasm", "invoke": {invoke}}}"#);
+            CliConfig::from_json_string(data).map(|c| c.0.invoke_ref().cloned())
+        };
+        let config = CliConfig::from_json_string(r#"{"entry": "lib.wasm"}"#.to_string()).unwrap();
+        assert_eq!(config.0.invoke_ref(), None);
+        assert_eq!(
+            invoke(r#""add""#).unwrap(),
+            Some(Invoke::new("add".into(), Vec::new()))
+        );
+        assert_eq!(
+            invoke(r#"{"name": "add", "args": [1, "2", "\"hi\""]}"#).unwrap(),
+            Some(Invoke::new(
+                "add".into(),
+                vec!["1".into(), "2".into(), "\"hi\"".into()]
+            ))
+        );
+        assert!(invoke(r#"{"args": [1]}"#).is_err());
+        assert!(invoke(r#"{"name": "add", "args": 1}"#).is_err());
+    }
+
+    #[test]
+    fn test_deterministic_from_json() {
+        let deterministic = |deterministic: &str| {
+            let data = format!(r#"{{"entry": "lib.wasm", "deterministic": {deterministic}}}"#);
+            CliConfig::from_json_string(data).map(|c| c.0.deterministic_ref().cloned())
+        };
+        let config = CliConfig::from_json_string(r#"{"entry": "lib.wasm"}"#.to_string()).unwrap();
+        assert_eq!(config.0.deterministic_ref(), None);
+        assert_eq!(deterministic("false").unwrap(), None);
+        assert_eq!(
+            deterministic("true").unwrap(),
+            Some(DeterministicConfig::default())
+        );
+        assert_eq!(
+            deterministic(r#"{"seed": 7, "start_time": 1000, "allowed_extensions": ["http"]}"#)
+                .unwrap(),
+            Some(DeterministicConfig {
+                seed: 7,
+                start_time: 1000,
+                allowed_extensions: vec!["http".into()],
+            })
+        );
+        assert!(deterministic(r#"{"seed": -1}"#).is_err());
+        assert!(deterministic(r#"{"allowed_extensions": "http"}"#).is_err());
+        assert!(deterministic(r#""yes""#).is_err());
+    }
+
+    #[test]
+    fn test_optimize_from_json() {
+        let optimize = |optimize: &str| {
+            let data = format!(r#"{{"entry": "lib.wasm", "optimize": {optimize}}}"#);
+            CliConfig::from_json_string(data).map(|c| c.0.opts)
+        };
+        let opts = optimize(
+            r#"{"opt_level": "s", "wasm_tail_call": true, "wasm_memory64": "yes", "pooling_total_memories": 10}"#,
+        )
+        .unwrap();
+        assert!(opts.opt_level.is_some());
+        assert_eq!(opts.wasm_tail_call, Some(true));
+        assert_eq!(opts.wasm_memory64, Some(true));
+        assert_eq!(opts.pooling_total_memories, Some(10));
+        assert!(optimize(r#"{"wasm_simd": false, "wasm_relaxed_simd": true}"#).is_err());
+        assert!(optimize(r#"{"wasm_unknown": true}"#).is_err());
+    }
+
+    #[test]
+    fn test_audit_from_json() {
+        let audit = |audit: &str| {
+            let data = format!(r#"{{"entry": "lib.wasm", "audit": {audit}}}"#);
+            CliConfig::from_json_string(data).map(|c| c.0.audit_ref().cloned())
+        };
+        let config = CliConfig::from_json_string(r#"{"entry": "lib.wasm"}"#.to_string()).unwrap();
+        assert_eq!(config.0.audit_ref(), None);
+        assert_eq!(
+            audit(r#""audit.jsonl""#).unwrap(),
+            Some(AuditConfig::new("audit.jsonl"))
+        );
+        assert_eq!(
+            audit(r#"{"path": "-", "redact": ["url", "opts"]}"#).unwrap(),
+            Some(AuditConfig {
+                path: "-".into(),
+                redact: vec!["url".into(), "opts".into()],
+            })
+        );
+        assert!(audit(r#"{"redact": ["url"]}"#).is_err());
+        assert!(audit(r#"{"path": "-", "redact": "url"}"#).is_err());
+        assert!(audit("true").is_err());
+    }
+
+    #[test]
+    fn test_all_options_from_json() {
+        let data = r#"{
+            "entry": "lib.wasm",
+            "args": ["--verbose"],
+            "envs": {"KEY": "value"},
+            "tcp_listens": ["127.0.0.1:0", "127.0.0.1:0::5"],
+            "feature_thread": true,
+            "nn": true,
+            "nn_graph": [{"format": "openvino", "dir": "/models"}],
+            "coredump": "app.core",
+            "unknown_imports_trap": true,
+            "network_error_code": true,
+            "runtime_logger_level": "debug",
+            "permissions_config": {
+                "allow_read": true,
+                "deny_write": ["/etc"],
+                "allow_net": false
+            },
+            "store_limited": {
+                "max_table_elements": 1000,
+                "max_instances": 10,
+                "max_tables": 2,
+                "trap_on_grow_failure": true
+            },
+            "limited_memory": 30
+        }"#
+        .to_string();
+        let config = CliConfig::from_json_string(data).unwrap().0;
+        assert_eq!(config.stdin_args_ref(), &vec!["--verbose".to_string()]);
+        assert_eq!(
+            config.envs_ref(),
+            &vec![("KEY".to_string(), "value".to_string())]
+        );
+        assert_eq!(config.tcp_listens.len(), 2);
+        assert_eq!(config.tcp_listens[1].1, Some(5));
+        assert!(config.feature_thread());
+        assert!(config.nn);
+        assert_eq!(config.nn_graph[0].format, "openvino");
+        assert_eq!(config.coredump.as_deref(), Some("app.core"));
+        assert!(config.unknown_imports_trap);
+        assert!(config.network_error_code);
+        assert!(matches!(
+            config.get_runtime_logger_level(),
+            LoggerLevel::DEBUG
+        ));
+        let perms = &config.permissions_config;
+        assert!(matches!(perms.allow_read, Some(PermissionGrant::All)));
+        assert!(matches!(perms.deny_write, Some(PermissionGrant::List(ref l)) if l == &["/etc"]));
+        assert!(perms.allow_net.is_none());
+        assert_eq!(config.store_limited.max_table_elements, Some(1000));
+        assert_eq!(config.store_limited.max_instances, Some(10));
+        assert_eq!(config.store_limited.max_tables, Some(2));
+        assert_eq!(config.store_limited.trap_on_grow_failure, Some(true));
+        assert_eq!(config.get_limited_memory(), Some(30));
+
+        let data =
+            r#"{"entry": "lib.wasm", "limited_memory": 30, "store_limited": {"max_memories": 2}}"#;
+        assert!(CliConfig::from_json_string(data.to_string()).is_err());
+        let data = r#"{"entry": "lib.wasm", "tcp_listens": ["127.0.0.1"]}"#;
+        let err = CliConfig::from_json_string(data.to_string()).err().unwrap();
+        assert!(err.to_string().starts_with("the config `tcp_listens[0]`"));
+        let data = r#"{"entry": "lib.wasm", "permissions_config": {"allow_read": "/tmp"}}"#;
+        assert!(CliConfig::from_json_string(data.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_apply_overrides() {
+        let mut config = CliConfig::from_wasm("test.wasm", &LayerOpts::default()).unwrap();
+        let overrides = json::parse(
+            r#"{"limited_fuel": 100, "run_time": 2000, "invoke": "add", "deterministic": true}"#,
+        )
+        .unwrap();
+        config.apply_overrides(&overrides).unwrap();
+        assert_eq!(config.0.get_limited_fuel(), Some(100));
+        assert_eq!(config.0.get_limited_time(), Some(2000));
+        assert_eq!(
+            config.0.invoke_ref(),
+            Some(&Invoke::new("add".into(), Vec::new()))
+        );
+        assert_eq!(
+            config.0.deterministic_ref(),
+            Some(&DeterministicConfig::default())
+        );
+        config.apply_overrides(&JsonValue::Null).unwrap();
+        assert_eq!(config.0.get_limited_fuel(), Some(100));
+
+        let overrides = json::parse(r#"{"limited_fuel": -1}"#).unwrap();
+        assert!(config.apply_overrides(&overrides).is_err());
+        let overrides = json::parse(r#"{"entry": "other.wasm"}"#).unwrap();
+        assert!(config.apply_overrides(&overrides).is_err());
+        assert!(config.apply_overrides(&"fuel".into()).is_err());
+    }
+
+    #[test]
+    fn test_load_config_overlays() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let base = temp_dir.path().join("app.yaml");
+        fs::write(
+            &base,
+            "entry: app.wasm\nlimited_fuel: 1000\nenvs:\n  A: \"1\"\npermissions: [\"http://a.com/\"]\n",
+        )
+        .unwrap();
+        let overlay = temp_dir.path().join("prod.toml");
+        fs::write(
+            &overlay,
+            "limited_fuel = 2000\n\"permissions+\" = [\"http://b.com/\"]\n[envs]\nB = \"2\"\n",
+        )
+        .unwrap();
+        let base = base.to_str().unwrap();
+        let overlays = vec![overlay.to_str().unwrap().to_string()];
+        let opts = LayerOpts {
+            overlays: overlays.clone(),
+            ..Default::default()
+        };
+
+        let config = CliConfig::from_file(base, &opts).unwrap();
+        assert_eq!(config.0.get_limited_fuel(), Some(2000));
+        assert_eq!(config.0.permisions_ref().len(), 2);
+        assert_eq!(
+            config.0.envs_ref(),
+            &vec![
+                ("A".to_string(), "1".to_string()),
+                ("B".to_string(), "2".to_string()),
+            ]
+        );
+
+        let resolved = resolve_layers(base, &opts).unwrap().resolved();
+        assert_eq!(resolved["sources"]["entry"], base);
+        assert_eq!(resolved["sources"]["limited_fuel"], overlays[0].as_str());
+        assert_eq!(resolved["sources"]["envs.B"], overlays[0].as_str());
+
+        let missing = LayerOpts {
+            overlays: vec![temp_dir.path().join("missing.yaml").display().to_string()],
+            ..Default::default()
+        };
+        let err = CliConfig::from_file(base, &missing).err().unwrap();
+        assert!(err.to_string().starts_with("failed to read the overlay"));
+        assert!(resolve_layers("app.wasm", &opts).is_err());
+
+        // the command line is the last layer, on the wasm file too.
+        let opts = LayerOpts {
+            overlays,
+            command_line: Some(serde_json::json!({"limited_fuel": 3000})),
+            ..Default::default()
+        };
+        let config = CliConfig::from_file(base, &opts).unwrap();
+        assert_eq!(config.0.get_limited_fuel(), Some(3000));
+        let opts = LayerOpts {
+            overlays: Vec::new(),
+            ..opts
+        };
+        let resolved = resolve_layers("app.wasm", &opts).unwrap().resolved();
+        assert_eq!(resolved["config"]["entry"], "app.wasm");
+        assert_eq!(resolved["sources"]["entry"], "app.wasm");
+        assert_eq!(resolved["sources"]["limited_fuel"], "command line");
+        let config = CliConfig::from_wasm("app.wasm", &opts).unwrap();
+        assert_eq!(config.0.get_limited_fuel(), Some(3000));
+        assert_eq!(config.0.fs_root_path_ref(), Some("."));
+    }
 }
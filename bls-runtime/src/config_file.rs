@@ -0,0 +1,902 @@
+use anyhow::{Result, anyhow, bail};
+use blockless::{
+    AuditConfig, BlocklessConfig, BlocklessModule, BlsNnGraph, CacheSetting, DeterministicConfig,
+    DriverConfig, Invoke, LoggerLevel, ModuleSandbox, ModuleType, MultiAddr, OptimizeOpts,
+    Permission, PermissionGrant, PermissionsConfig, Stderr, Stdin, Stdio, Stdout,
+};
+use schemars::{JsonSchema, Schema, SchemaGenerator, json_schema};
+use serde::de::{self, MapAccess, Unexpected, Visitor, value::MapAccessDeserializer};
+use serde::{Deserialize, Deserializer};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::path::Path;
+
+use crate::cli_clap::parse_listen;
+
+/// the error of the configure item, the path is the JSON path of the item.
+pub(crate) fn invalid(path: impl Display, err: impl Display) -> anyhow::Error {
+    anyhow!("the config `{path}`: {err}")
+}
+
+/// The format of the configure file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum ConfigFormat {
+    #[default]
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// the names of the configure file in the CAR file, in the searching order.
+    pub(crate) const CAR_FILES: [(&'static str, ConfigFormat); 4] = [
+        ("config.json", ConfigFormat::Json),
+        ("config.toml", ConfigFormat::Toml),
+        ("config.yaml", ConfigFormat::Yaml),
+        ("config.yml", ConfigFormat::Yaml),
+    ];
+
+    /// the format of the file by the extension, the default is JSON.
+    pub(crate) fn from_path(path: impl AsRef<Path>) -> Self {
+        let ext = path.as_ref().extension().and_then(|ext| ext.to_str());
+        match ext.map(str::to_ascii_lowercase).as_deref() {
+            Some("toml") => ConfigFormat::Toml,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Json,
+        }
+    }
+}
+
+/// the error of the deserializing, with the path of the bad item.
+fn path_error<E: Display>(e: serde_path_to_error::Error<E>) -> anyhow::Error {
+    let path = e.path().to_string();
+    match path.as_str() {
+        "." => anyhow!("the config: {}", e.inner()),
+        _ => invalid(path, e.inner()),
+    }
+}
+
+/// deserialize the text of the format, the error carries the path of the bad item.
+fn from_str<'de, T: Deserialize<'de>>(text: &'de str, format: ConfigFormat) -> Result<T> {
+    match format {
+        ConfigFormat::Json => {
+            let de = &mut serde_json::Deserializer::from_str(text);
+            let value = serde_path_to_error::deserialize(&mut *de).map_err(path_error)?;
+            de.end().map_err(|e| anyhow!("the config: {e}"))?;
+            Ok(value)
+        }
+        ConfigFormat::Toml => {
+            serde_path_to_error::deserialize(toml::Deserializer::new(text)).map_err(path_error)
+        }
+        ConfigFormat::Yaml => {
+            serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(text))
+                .map_err(path_error)
+        }
+    }
+}
+
+/// parse the text of the format as the JSON value, the layers are merged as the values.
+pub(crate) fn parse_value(text: &str, format: ConfigFormat) -> Result<serde_json::Value> {
+    from_str(text, format)
+}
+
+/// The short form of the item, e.g. `"cache": false` for `"cache": {"enabled": false}`.
+trait ShortForm: Sized {
+    const EXPECTING: &'static str;
+
+    fn from_bool(_v: bool) -> Option<Self> {
+        None
+    }
+
+    fn from_str(_v: &str) -> Option<Self> {
+        None
+    }
+}
+
+impl ShortForm for bool {
+    const EXPECTING: &'static str = "a boolean or an object";
+
+    fn from_bool(v: bool) -> Option<Self> {
+        Some(v)
+    }
+}
+
+impl ShortForm for String {
+    const EXPECTING: &'static str = "a string or an object";
+
+    fn from_str(v: &str) -> Option<Self> {
+        Some(v.to_string())
+    }
+}
+
+/// The item is the short form or the object.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum ShortOr<S, T> {
+    Short(S),
+    Table(T),
+}
+
+struct ShortOrVisitor<S, T>(PhantomData<(S, T)>);
+
+impl<'de, S: ShortForm, T: Deserialize<'de>> Visitor<'de> for ShortOrVisitor<S, T> {
+    type Value = ShortOr<S, T>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(S::EXPECTING)
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Self::Value, E> {
+        S::from_bool(v)
+            .map(ShortOr::Short)
+            .ok_or_else(|| E::invalid_type(Unexpected::Bool(v), &self))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        S::from_str(v)
+            .map(ShortOr::Short)
+            .ok_or_else(|| E::invalid_type(Unexpected::Str(v), &self))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+        // the items of the object are deserialized with the path of the item.
+        T::deserialize(MapAccessDeserializer::new(map)).map(ShortOr::Table)
+    }
+}
+
+impl<'de, S: ShortForm, T: Deserialize<'de>> Deserialize<'de> for ShortOr<S, T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(ShortOrVisitor(PhantomData))
+    }
+}
+
+impl<S: JsonSchema, T: JsonSchema> JsonSchema for ShortOr<S, T> {
+    fn inline_schema() -> bool {
+        true
+    }
+
+    fn schema_name() -> Cow<'static, str> {
+        format!("{}_or_{}", S::schema_name(), T::schema_name()).into()
+    }
+
+    fn json_schema(generator: &mut SchemaGenerator) -> Schema {
+        json_schema!({
+            "anyOf": [generator.subschema_for::<S>(), generator.subschema_for::<T>()]
+        })
+    }
+}
+
+/// The level of the runtime logger.
+#[derive(Debug, Clone, Copy, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogLevel {
+    #[serde(alias = "TRACE")]
+    Trace,
+    #[serde(alias = "DEBUG")]
+    Debug,
+    #[serde(alias = "INFO")]
+    Info,
+    #[serde(alias = "WARN")]
+    Warn,
+    #[serde(alias = "ERROR")]
+    Error,
+}
+
+impl From<LogLevel> for LoggerLevel {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Trace => LoggerLevel::TRACE,
+            LogLevel::Debug => LoggerLevel::DEBUG,
+            LogLevel::Info => LoggerLevel::INFO,
+            LogLevel::Warn => LoggerLevel::WARN,
+            LogLevel::Error => LoggerLevel::ERROR,
+        }
+    }
+}
+
+/// The driver of the schema.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DriverItem {
+    /// the schema of the driver, e.g. `tcp`.
+    pub schema: String,
+    /// the path of the driver library.
+    pub path: String,
+}
+
+/// The host directory mapped into the guest.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct MapDirItem {
+    pub host: String,
+    pub guest: String,
+}
+
+/// The graph pre-loaded for the wasi-nn.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct NnGraphItem {
+    /// the encoding of the graph, e.g. `openvino`.
+    pub format: String,
+    /// the directory of the graph.
+    pub dir: String,
+}
+
+/// The grant of the permission, `true` grants all, or the paths or the urls.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(untagged, expecting = "a boolean or an array of strings")]
+pub(crate) enum GrantItem {
+    All(bool),
+    List(Vec<String>),
+}
+
+/// The permissions of the file system and the network, the same as `--allow-read`,
+/// `--deny-net` and so on.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct PermissionsConfigItem {
+    pub allow_read: Option<GrantItem>,
+    pub allow_write: Option<GrantItem>,
+    pub allow_net: Option<GrantItem>,
+    pub deny_read: Option<GrantItem>,
+    pub deny_write: Option<GrantItem>,
+    pub deny_net: Option<GrantItem>,
+    #[serde(default)]
+    pub allow_all: bool,
+}
+
+/// The limits of the store.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct StoreLimitedItem {
+    /// the maximum memory size in bytes, the same as `max_memory_size`.
+    pub max_memory_size: Option<usize>,
+    pub max_table_elements: Option<u32>,
+    pub max_instances: Option<usize>,
+    pub max_tables: Option<u32>,
+    /// the maximum number of the memories, the same as `limited_memory`.
+    pub max_memories: Option<usize>,
+    pub trap_on_grow_failure: Option<bool>,
+}
+
+/// The type of the module.
+#[derive(Debug, Clone, Copy, Default, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ModuleKind {
+    #[serde(alias = "ENTRY")]
+    Entry,
+    #[default]
+    #[serde(alias = "MODULE")]
+    Module,
+}
+
+/// The sandbox of the library module.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SandboxItem {
+    /// the permissions of the module, the entry's are not inherited.
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    /// the file and network rights of the module, the entry's are not inherited.
+    pub permissions_config: Option<PermissionsConfigItem>,
+    /// the environment variables of the module.
+    #[serde(default)]
+    pub envs: BTreeMap<String, String>,
+    /// the directories mapped into the module.
+    #[serde(default)]
+    pub map_dirs: Vec<MapDirItem>,
+    /// the fuel budget of the module.
+    pub limited_fuel: Option<u64>,
+    /// the maximum memory size of the module in bytes.
+    pub max_memory_size: Option<usize>,
+}
+
+/// The module of the app.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ModuleItem {
+    /// the file of the module.
+    pub file: String,
+    /// the name of the module, the imports of the other modules use it.
+    #[serde(default)]
+    pub name: String,
+    /// the md5 checksum of the module file.
+    #[serde(default)]
+    pub md5: String,
+    #[serde(default, rename = "type")]
+    pub kind: ModuleKind,
+    /// the modules loaded before the module.
+    #[serde(default)]
+    pub depends: Vec<String>,
+    pub sandbox: Option<SandboxItem>,
+}
+
+/// The compilation cache setting.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CacheItem {
+    pub enabled: Option<bool>,
+    /// the directory of the cache.
+    pub directory: Option<String>,
+    /// the soft limit of the cache directory size in bytes.
+    pub max_size: Option<u64>,
+    /// the wasmtime cache configure file.
+    pub config_file: Option<String>,
+}
+
+/// The export invoked instead of the entry.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct InvokeItem {
+    pub name: String,
+    /// the string argument is passed as is, the others are passed as the JSON text.
+    #[serde(default)]
+    pub args: Vec<serde_json::Value>,
+}
+
+/// The deterministic mode setting.
+#[derive(Debug, Clone, Default, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct DeterministicItem {
+    /// the seed of the random generator.
+    #[serde(default)]
+    pub seed: u64,
+    /// the start time of the virtual wall clock in milliseconds since the unix epoch.
+    #[serde(default)]
+    pub start_time: u64,
+    /// the nondeterministic extensions allowed.
+    #[serde(default)]
+    pub allowed_extensions: Vec<String>,
+}
+
+/// The audit trace setting.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AuditItem {
+    /// the trace file, `-` means stderr.
+    pub path: String,
+    /// the arguments redacted in the trace.
+    #[serde(default)]
+    pub redact: Vec<String>,
+}
+
+/// The configure file of the app.
+#[derive(Debug, Clone, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+#[schemars(title = "bls-runtime configure")]
+pub(crate) struct ConfigFile {
+    /// the version of the configure, the version 1 links the modules.
+    #[schemars(range(min = 0, max = 1))]
+    pub version: Option<u64>,
+    /// the entry file, or the name of the entry module in the version 1.
+    pub entry: String,
+    pub fs_root_path: Option<String>,
+    pub drivers_root_path: Option<String>,
+    /// the runtime logger file.
+    pub runtime_logger: Option<String>,
+    pub runtime_logger_level: Option<LogLevel>,
+    pub limited_fuel: Option<u64>,
+    /// the memory limit in pages.
+    pub limited_memory: Option<u64>,
+    /// the maximum memory size in bytes.
+    pub max_memory_size: Option<u64>,
+    pub extensions_path: Option<String>,
+    /// `inherit` or the fixed input.
+    pub stdin: Option<String>,
+    /// `inherit`, `null` or the file name.
+    pub stdout: Option<String>,
+    /// `inherit`, `null` or the file name.
+    pub stderr: Option<String>,
+    pub debug_info: Option<bool>,
+    /// the time limit in milliseconds, the alias of `limited_time`.
+    pub run_time: Option<u64>,
+    /// the time limit in milliseconds.
+    pub limited_time: Option<u64>,
+    /// the profile file in the Firefox profiler format.
+    pub profile: Option<String>,
+    /// the maximum number of the threads.
+    pub max_threads: Option<u32>,
+    #[serde(default)]
+    pub drivers: Vec<DriverItem>,
+    #[serde(default)]
+    pub modules: Vec<ModuleItem>,
+    /// the permission urls, e.g. `http://httpbin.org/anything`.
+    #[serde(default)]
+    pub permissions: Vec<String>,
+    #[serde(default)]
+    pub map_dirs: Vec<MapDirItem>,
+    /// `false` disables the cache.
+    pub cache: Option<ShortOr<bool, CacheItem>>,
+    /// the export name or the object.
+    pub invoke: Option<ShortOr<String, InvokeItem>>,
+    /// `true` or the object.
+    pub deterministic: Option<ShortOr<bool, DeterministicItem>>,
+    /// the trace file or the object.
+    pub audit: Option<ShortOr<String, AuditItem>>,
+    /// the optimize options, the same as the `-O` options.
+    pub optimize: Option<BTreeMap<String, serde_json::Value>>,
+    /// the arguments of the app.
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// the environment variables of the app.
+    #[serde(default)]
+    pub envs: BTreeMap<String, String>,
+    /// the TCP listeners preopened for the app, `ADDR[::FD]`, the same as `--tcplisten`.
+    #[serde(default)]
+    pub tcp_listens: Vec<String>,
+    pub feature_thread: Option<bool>,
+    /// enable the wasi-nn.
+    pub nn: Option<bool>,
+    #[serde(default)]
+    pub nn_graph: Vec<NnGraphItem>,
+    /// the core dump file written when the app traps.
+    pub coredump: Option<String>,
+    /// allow the entry to import the unknown functions, they trap when called.
+    pub unknown_imports_trap: Option<bool>,
+    pub cli_exit_with_code: Option<bool>,
+    /// return the network errors as the error codes.
+    pub network_error_code: Option<bool>,
+    pub permissions_config: Option<PermissionsConfigItem>,
+    pub store_limited: Option<StoreLimitedItem>,
+}
+
+/// The overrides of the job configure, only the limits and the per-run items.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ConfigOverrides {
+    pub limited_fuel: Option<u64>,
+    pub limited_time: Option<u64>,
+    pub run_time: Option<u64>,
+    pub limited_memory: Option<u64>,
+    pub max_memory_size: Option<u64>,
+    pub permissions: Option<Vec<String>>,
+    pub invoke: Option<ShortOr<String, InvokeItem>>,
+    pub deterministic: Option<ShortOr<bool, DeterministicItem>>,
+    pub audit: Option<ShortOr<String, AuditItem>>,
+}
+
+/// the string argument is passed as is, the others are passed as the JSON text.
+fn json_text(value: &serde_json::Value) -> String {
+    value
+        .as_str()
+        .map(String::from)
+        .unwrap_or_else(|| value.to_string())
+}
+
+fn permissions(path: &str, perms: &[String]) -> Result<Vec<Permission>> {
+    perms
+        .iter()
+        .enumerate()
+        .map(|(i, url)| {
+            let schema = MultiAddr::parse(url.as_bytes())
+                .ok()
+                .and_then(|addr| addr.schema().ok())
+                .ok_or_else(|| invalid(format!("{path}[{i}]"), "the invalid permission url."))?;
+            Ok(Permission {
+                schema: schema.into(),
+                url: url.clone(),
+            })
+        })
+        .collect()
+}
+
+fn grant(grant: Option<GrantItem>) -> Option<PermissionGrant> {
+    match grant? {
+        GrantItem::All(all) => all.then_some(PermissionGrant::All),
+        GrantItem::List(list) => Some(PermissionGrant::List(list)),
+    }
+}
+
+fn permissions_config(perms: PermissionsConfigItem) -> PermissionsConfig {
+    PermissionsConfig {
+        allow_read: grant(perms.allow_read),
+        allow_write: grant(perms.allow_write),
+        allow_net: grant(perms.allow_net),
+        deny_read: grant(perms.deny_read),
+        deny_write: grant(perms.deny_write),
+        deny_net: grant(perms.deny_net),
+        allow_all: perms.allow_all,
+    }
+}
+
+fn tcp_listens(listens: &[String]) -> Result<Vec<(SocketAddr, Option<u32>)>> {
+    listens
+        .iter()
+        .enumerate()
+        .map(|(i, l)| parse_listen(l).map_err(|e| invalid(format!("tcp_listens[{i}]"), e)))
+        .collect()
+}
+
+fn map_dirs(dirs: Vec<MapDirItem>) -> Vec<(String, String)> {
+    dirs.into_iter().map(|d| (d.host, d.guest)).collect()
+}
+
+fn cache_setting(cache: ShortOr<bool, CacheItem>) -> Result<Option<CacheSetting>> {
+    let cache = match cache {
+        ShortOr::Short(enabled) => return Ok((!enabled).then_some(CacheSetting::Disabled)),
+        ShortOr::Table(cache) => cache,
+    };
+    if cache.enabled == Some(false) {
+        return Ok(Some(CacheSetting::Disabled));
+    }
+    let max_size = cache.max_size;
+    let setting = match (cache.directory, cache.config_file) {
+        (Some(_), Some(_)) => bail!("the cache directory and config_file are conflict."),
+        (Some(path), None) => CacheSetting::Directory { path, max_size },
+        (None, _) if max_size.is_some() => {
+            bail!("the cache max_size must be used with the directory.")
+        }
+        (None, Some(file)) => CacheSetting::ConfigFile(file),
+        (None, None) => CacheSetting::Default,
+    };
+    Ok(Some(setting))
+}
+
+fn invoke(invoke: ShortOr<String, InvokeItem>) -> Invoke {
+    match invoke {
+        ShortOr::Short(name) => Invoke::new(name, Vec::new()),
+        ShortOr::Table(invoke) => {
+            Invoke::new(invoke.name, invoke.args.iter().map(json_text).collect())
+        }
+    }
+}
+
+fn deterministic(deterministic: ShortOr<bool, DeterministicItem>) -> Option<DeterministicConfig> {
+    match deterministic {
+        ShortOr::Short(enabled) => enabled.then(DeterministicConfig::default),
+        ShortOr::Table(d) => Some(DeterministicConfig {
+            seed: d.seed,
+            start_time: d.start_time,
+            allowed_extensions: d.allowed_extensions,
+        }),
+    }
+}
+
+fn audit(audit: ShortOr<String, AuditItem>) -> AuditConfig {
+    match audit {
+        ShortOr::Short(path) => AuditConfig::new(path),
+        ShortOr::Table(audit) => AuditConfig {
+            path: audit.path,
+            redact: audit.redact,
+        },
+    }
+}
+
+fn optimize_options(optimize: BTreeMap<String, serde_json::Value>) -> Result<OptimizeOpts> {
+    let mut opts = OptimizeOpts::default();
+    // the string values are not quoted, e.g. `"opt_level": "s"`.
+    let opt_items = optimize
+        .iter()
+        .map(|(key, val)| (key.clone(), json_text(val)))
+        .collect::<Vec<_>>();
+    opts.config(opt_items)?;
+    opts.validate_features()?;
+    Ok(opts)
+}
+
+fn module_sandbox(path: &str, sandbox: SandboxItem) -> Result<ModuleSandbox> {
+    Ok(ModuleSandbox {
+        permissions: permissions(&format!("{path}.permissions"), &sandbox.permissions)?,
+        envs: sandbox.envs.into_iter().collect(),
+        dirs: map_dirs(sandbox.map_dirs),
+        limited_fuel: sandbox.limited_fuel,
+        max_memory_size: sandbox.max_memory_size,
+        permissions_config: sandbox
+            .permissions_config
+            .map(permissions_config)
+            .unwrap_or_default(),
+    })
+}
+
+fn modules(modules: Vec<ModuleItem>) -> Result<Vec<BlocklessModule>> {
+    modules
+        .into_iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let sandbox = m
+                .sandbox
+                .map(|s| module_sandbox(&format!("modules[{i}].sandbox"), s))
+                .transpose()?;
+            Ok(BlocklessModule {
+                module_type: match m.kind {
+                    ModuleKind::Entry => ModuleType::Entry,
+                    ModuleKind::Module => ModuleType::Module,
+                },
+                name: m.name,
+                file: m.file,
+                md5: m.md5,
+                depends: m.depends,
+                sandbox,
+            })
+        })
+        .collect()
+}
+
+impl ConfigFile {
+    /// parse the configure from the text of the format.
+    pub(crate) fn parse(text: &str, format: ConfigFormat) -> Result<Self> {
+        from_str(text, format)
+    }
+
+    /// parse the configure from the JSON text.
+    pub(crate) fn from_json(json: &str) -> Result<Self> {
+        Self::parse(json, ConfigFormat::Json)
+    }
+
+    /// convert the JSON value into the configure, e.g. the merged layers.
+    pub(crate) fn from_value(value: serde_json::Value) -> Result<Self> {
+        serde_path_to_error::deserialize(value).map_err(path_error)
+    }
+
+    /// the JSON schema of the configure file.
+    pub(crate) fn json_schema() -> String {
+        let schema = schemars::schema_for!(ConfigFile);
+        serde_json::to_string_pretty(&schema).expect("the schema is serializable")
+    }
+
+    /// convert the configure file into the runtime configure, the values are checked.
+    pub(crate) fn into_blockless_config(self) -> Result<BlocklessConfig> {
+        let mut bc = BlocklessConfig::new(&self.entry);
+        if let Some(cache) = self.cache {
+            if let Some(cache) = cache_setting(cache).map_err(|e| invalid("cache", e))? {
+                bc.set_cache(cache);
+            }
+        }
+        bc.set_invoke(self.invoke.map(invoke));
+        bc.set_profile(self.profile);
+        bc.set_deterministic(self.deterministic.and_then(deterministic));
+        bc.set_audit(self.audit.map(audit));
+        if let Some(optimize) = self.optimize {
+            bc.opts = optimize_options(optimize).map_err(|e| invalid("optimize", e))?;
+        }
+        bc.set_map_dirs(map_dirs(self.map_dirs));
+        bc.set_modules(modules(self.modules)?);
+        bc.extensions_path(self.extensions_path);
+        bc.set_fs_root_path(self.fs_root_path);
+        bc.drivers(
+            self.drivers
+                .into_iter()
+                .map(|d| DriverConfig::new(d.schema, d.path))
+                .collect(),
+        );
+        if let Some(b) = self.debug_info {
+            bc.set_debug_info(b);
+        }
+        if let Some(l) = self.runtime_logger_level {
+            bc.set_runtime_logger_level(l.into());
+        }
+        bc.set_permisions(permissions("permissions", &self.permissions)?);
+        bc.set_runtime_logger(self.runtime_logger);
+        bc.set_drivers_root_path(self.drivers_root_path);
+        bc.limited_fuel(self.limited_fuel);
+        bc.limited_memory(self.limited_memory);
+        bc.max_memory_size(self.max_memory_size);
+        bc.set_run_time(self.run_time);
+        bc.limited_time(self.limited_time.or(self.run_time));
+        bc.set_max_threads(self.max_threads);
+        if let Some(limits) = self.store_limited {
+            if limits.max_memory_size.is_some() && self.max_memory_size.is_some() {
+                return Err(invalid(
+                    "store_limited.max_memory_size",
+                    "conflicts with `max_memory_size`.",
+                ));
+            }
+            if limits.max_memories.is_some() && self.limited_memory.is_some() {
+                return Err(invalid(
+                    "store_limited.max_memories",
+                    "conflicts with `limited_memory`.",
+                ));
+            }
+            let store_limited = &mut bc.store_limited;
+            macro_rules! set_limit {
+                ($($field: ident),*) => {
+                    $(if limits.$field.is_some() {
+                        store_limited.$field = limits.$field;
+                    })*
+                };
+            }
+            set_limit!(
+                max_memory_size,
+                max_table_elements,
+                max_instances,
+                max_tables,
+                max_memories,
+                trap_on_grow_failure
+            );
+        }
+        bc.set_stdin_args(self.args);
+        bc.set_envs(self.envs.into_iter().collect());
+        bc.tcp_listens = tcp_listens(&self.tcp_listens)?;
+        bc.set_feature_thread(self.feature_thread.unwrap_or_default());
+        bc.nn = self.nn.unwrap_or_default();
+        bc.nn_graph = self
+            .nn_graph
+            .into_iter()
+            .map(|g| BlsNnGraph {
+                format: g.format,
+                dir: g.dir,
+            })
+            .collect();
+        bc.coredump = self.coredump;
+        bc.unknown_imports_trap = self.unknown_imports_trap.unwrap_or_default();
+        bc.cli_exit_with_code = self.cli_exit_with_code.unwrap_or_default();
+        bc.network_error_code = self.network_error_code.unwrap_or_default();
+        if let Some(perms) = self.permissions_config {
+            bc.permissions_config = permissions_config(perms);
+        }
+        match self.version {
+            None => {}
+            Some(v @ (0 | 1)) => bc.set_version((v as usize).into()),
+            Some(_) => return Err(invalid("version", "the version must be 0 or 1.")),
+        }
+        let stdin = match self.stdin {
+            Some(s) if s == "inherit" => Stdin::Inherit,
+            Some(s) => Stdin::Fixed(s),
+            None => Stdin::Fixed(String::new()),
+        };
+        let stdout = self.stdout.as_deref();
+        let stderr = self.stderr.as_deref();
+        bc.stdio = Stdio {
+            stdin,
+            stdout: stdio_cfg!(stdout, Stdout, FileName),
+            stderr: stdio_cfg!(stderr, Stderr, FileName),
+        };
+        Ok(bc)
+    }
+}
+
+impl ConfigOverrides {
+    /// parse the overrides from the JSON text.
+    pub(crate) fn from_json(json: &str) -> Result<Self> {
+        from_str(json, ConfigFormat::Json)
+    }
+
+    /// apply the overrides to the configure.
+    pub(crate) fn apply(self, bc: &mut BlocklessConfig) -> Result<()> {
+        if let Some(fuel) = self.limited_fuel {
+            bc.limited_fuel(Some(fuel));
+        }
+        if let Some(run_time) = self.run_time {
+            bc.set_run_time(Some(run_time));
+            bc.limited_time(Some(run_time));
+        }
+        if let Some(time) = self.limited_time {
+            bc.limited_time(Some(time));
+        }
+        if let Some(memory) = self.limited_memory {
+            bc.limited_memory(Some(memory));
+        }
+        if let Some(size) = self.max_memory_size {
+            bc.max_memory_size(Some(size));
+        }
+        if let Some(perms) = self.permissions {
+            bc.set_permisions(permissions("permissions", &perms)?);
+        }
+        if let Some(i) = self.invoke {
+            bc.set_invoke(Some(invoke(i)));
+        }
+        if let Some(d) = self.deterministic {
+            bc.set_deterministic(deterministic(d));
+        }
+        if let Some(a) = self.audit {
+            bc.set_audit(Some(audit(a)));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_config_file_errors() {
+        let err = |json: &str| ConfigFile::from_json(json).err().unwrap().to_string();
+        assert!(
+            err(r#"{"entry": "a.wasm", "limited_fule": 1}"#)
+                .contains("unknown field `limited_fule`")
+        );
+        assert!(err(r#"{"limited_fuel": 1}"#).contains("missing field `entry`"));
+        let e = err(r#"{"entry": "a.wasm", "modules": [{"file": "a.wasm", "type": "entyr"}]}"#);
+        assert!(e.starts_with("the config `modules[0].type`"));
+        let e = err(r#"{"entry": "a.wasm", "cache": {"directory": "/tmp", "max_sise": 1}}"#);
+        assert!(e.starts_with("the config `cache"));
+        assert!(e.contains("unknown field `max_sise`"));
+        let e = err(r#"{"entry": "a.wasm", "audit": true}"#);
+        assert!(e.contains("expected a string or an object"));
+        let e = err(
+            r#"{"entry": "a.wasm", "modules": [{"file": "a.wasm", "sandbox": {"limited_fuel": "1"}}]}"#,
+        );
+        assert!(e.starts_with("the config `modules[0].sandbox.limited_fuel`"));
+
+        let conf =
+            ConfigFile::from_json(r#"{"entry": "a.wasm", "permissions": ["http:/bad"]}"#).unwrap();
+        let e = conf.into_blockless_config().err().unwrap().to_string();
+        assert!(e.starts_with("the config `permissions[0]`"));
+        let conf = ConfigFile::from_json(r#"{"entry": "a.wasm", "version": 2}"#).unwrap();
+        assert!(conf.into_blockless_config().is_err());
+    }
+
+    #[test]
+    fn test_config_formats() {
+        let toml = r#"
+            entry = "release"
+            version = 1
+            permissions = ["http://httpbin.org/anything"]
+
+            [envs]
+            KEY = "value"
+
+            [[modules]]
+            file = "lib.wasm"
+            name = "lib"
+
+            [[modules]]
+            file = "release.wasm"
+            name = "release"
+            type = "entry"
+            depends = ["lib"]
+        "#;
+        let yaml = r#"
+entry: release
+version: 1
+permissions:
+  - http://httpbin.org/anything
+envs:
+  KEY: value
+modules:
+  - file: lib.wasm
+    name: lib
+  - file: release.wasm
+    name: release
+    type: entry
+    depends: [lib]
+"#;
+        for (text, format) in [(toml, ConfigFormat::Toml), (yaml, ConfigFormat::Yaml)] {
+            let conf = ConfigFile::parse(text, format)
+                .unwrap()
+                .into_blockless_config()
+                .unwrap();
+            assert_eq!(conf.entry_ref(), "release");
+            assert_eq!(conf.permisions_ref().len(), 1);
+            assert_eq!(conf.envs_ref(), &vec![("KEY".into(), "value".into())]);
+            assert_eq!(conf.modules_ref().len(), 2);
+            assert_eq!(conf.modules_ref()[1].depends, vec!["lib".to_string()]);
+        }
+
+        let err = ConfigFile::parse(
+            "entry = \"a.wasm\"\nlimited_fuel = \"1\"",
+            ConfigFormat::Toml,
+        );
+        assert!(
+            err.err()
+                .unwrap()
+                .to_string()
+                .starts_with("the config `limited_fuel`")
+        );
+        let err = ConfigFile::parse(
+            "entry: a.wasm\nmodules:\n  - file: a.wasm\n    tpye: entry",
+            ConfigFormat::Yaml,
+        );
+        assert!(
+            err.err()
+                .unwrap()
+                .to_string()
+                .contains("unknown field `tpye`")
+        );
+
+        assert_eq!(ConfigFormat::from_path("app.TOML"), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path("app.yml"), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path("app.conf"), ConfigFormat::Json);
+    }
+
+    #[test]
+    fn test_json_schema() {
+        let schema: serde_json::Value = serde_json::from_str(&ConfigFile::json_schema()).unwrap();
+        assert_eq!(schema["additionalProperties"], false);
+        assert_eq!(schema["required"], serde_json::json!(["entry"]));
+        assert!(schema["properties"]["modules"].is_object());
+    }
+}
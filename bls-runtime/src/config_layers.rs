@@ -0,0 +1,459 @@
+use anyhow::{Result, anyhow, bail};
+use serde_json::{Map, Value, json};
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::config_file::{ConfigFile, ConfigFormat, invalid, parse_value};
+
+/// the lookup of the host environment variables.
+type EnvSource = fn(&str) -> Option<String>;
+
+fn host_env(name: &str) -> Option<String> {
+    std::env::var(name).ok()
+}
+
+/// The variables interpolated in the string items of the configure files, `${VAR}` or
+/// `${VAR:-default}`, the default is used when the variable is not defined or empty,
+/// and `$${` escapes `${`. The default is the text to the first `}`, so the variables
+/// can't be nested in it.
+#[derive(Debug, Clone)]
+pub(crate) struct ConfigVars {
+    /// the variables of the command line, they override the host environment variables.
+    vars: BTreeMap<String, String>,
+    /// the host environment variables allowed to be interpolated.
+    allowed_envs: BTreeSet<String>,
+    /// the source of the host environment variables, the tests replace it.
+    env: EnvSource,
+}
+
+impl Default for ConfigVars {
+    fn default() -> Self {
+        Self::new(Vec::new(), Vec::new())
+    }
+}
+
+fn is_var_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+impl ConfigVars {
+    pub(crate) fn new(vars: Vec<(String, String)>, allowed_envs: Vec<String>) -> Self {
+        Self {
+            vars: vars.into_iter().collect(),
+            allowed_envs: allowed_envs.into_iter().collect(),
+            env: host_env,
+        }
+    }
+
+    /// the value of the variable, the host environment variable must be allowed.
+    fn get(&self, name: &str) -> Option<String> {
+        if let Some(value) = self.vars.get(name) {
+            return Some(value.clone());
+        }
+        if !self.allowed_envs.contains(name) {
+            return None;
+        }
+        (self.env)(name)
+    }
+
+    /// interpolate the variables of the text.
+    pub(crate) fn interpolate(&self, text: &str) -> Result<String> {
+        let mut out = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(pos) = rest.find('$') {
+            out.push_str(&rest[..pos]);
+            let tail = &rest[pos..];
+            if let Some(tail) = tail.strip_prefix("$${") {
+                out.push_str("${");
+                rest = tail;
+                continue;
+            }
+            let Some(tail) = tail.strip_prefix("${") else {
+                // the `$` without the brace is kept, e.g. `$ROOT`.
+                out.push('$');
+                rest = &tail[1..];
+                continue;
+            };
+            let Some(end) = tail.find('}') else {
+                bail!("the `${{` is not closed.");
+            };
+            let expr = &tail[..end];
+            let (name, default) = match expr.split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (expr, None),
+            };
+            if !is_var_name(name) {
+                bail!("the invalid variable name `{name}`.");
+            }
+            // the default ends at the first `}`, `${A:-${B}}` would leave the `}`.
+            if default.is_some_and(|d| d.contains("${")) {
+                bail!("the default of the variable `{name}` can't have the `${{`.");
+            }
+            let value = match (self.get(name), default) {
+                (Some(value), Some(default)) if value.is_empty() => default.to_string(),
+                (Some(value), _) => value,
+                (None, Some(default)) => default.to_string(),
+                (None, None) => bail!("the variable `{name}` is not defined."),
+            };
+            out.push_str(&value);
+            rest = &tail[end + 1..];
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    /// interpolate the variables of the string items, the keys of the maps are kept.
+    fn interpolate_value(&self, value: &mut Value, path: &str) -> Result<()> {
+        match value {
+            Value::String(text) if text.contains('$') => {
+                *text = self.interpolate(text).map_err(|e| invalid(path, e))?;
+            }
+            Value::Array(items) => {
+                for (i, item) in items.iter_mut().enumerate() {
+                    self.interpolate_value(item, &format!("{path}[{i}]"))?;
+                }
+            }
+            Value::Object(map) => {
+                for (key, item) in map.iter_mut() {
+                    self.interpolate_value(item, &item_path(path, key))?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+/// The options of loading the configure, the overlay files, the variables and the
+/// command line options.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct LayerOpts {
+    pub overlays: Vec<String>,
+    pub vars: ConfigVars,
+    /// the items of the command line options, merged on the overlays as the last layer.
+    pub command_line: Option<Value>,
+}
+
+/// The configure merged from the layers, the base configure, the overlays and the
+/// command line, the later layer overrides the earlier.
+///
+/// the maps are merged by the key, the other values (the lists included) are replaced,
+/// `null` removes the item, and the `key+` appends the list to the list of the item.
+#[derive(Debug, Default)]
+pub(crate) struct ConfigLayers {
+    value: Map<String, Value>,
+    /// the source of the items, the key is the path of the item, e.g. `envs.KEY`.
+    sources: BTreeMap<String, String>,
+    /// the variables interpolated in the text layers.
+    vars: ConfigVars,
+}
+
+fn item_path(prefix: &str, key: &str) -> String {
+    if prefix.is_empty() {
+        key.to_string()
+    } else {
+        format!("{prefix}.{key}")
+    }
+}
+
+/// remove the sources of the item and the nested items.
+fn clear_sources(sources: &mut BTreeMap<String, String>, path: &str) {
+    let nested = format!("{path}.");
+    sources.retain(|k, _| k != path && !k.starts_with(&nested));
+}
+
+fn merge_map(
+    base: &mut Map<String, Value>,
+    layer: Map<String, Value>,
+    prefix: &str,
+    source: &str,
+    sources: &mut BTreeMap<String, String>,
+) -> Result<()> {
+    for (key, value) in layer {
+        if let Some(key) = key.strip_suffix('+') {
+            let path = item_path(prefix, key);
+            let Value::Array(items) = value else {
+                return Err(invalid(format!("{path}+"), "must be a list to append."));
+            };
+            let entry = base.entry(key).or_insert_with(|| Value::Array(Vec::new()));
+            let Value::Array(list) = entry else {
+                return Err(invalid(format!("{path}+"), "the item is not a list."));
+            };
+            list.extend(items);
+            let source = match sources.remove(&path) {
+                Some(s) => format!("{s}, {source}"),
+                None => source.to_string(),
+            };
+            sources.insert(path, source);
+            continue;
+        }
+        let path = item_path(prefix, &key);
+        match value {
+            Value::Null => {
+                base.remove(&key);
+                clear_sources(sources, &path);
+            }
+            Value::Object(layer) => {
+                let entry = base.entry(key).or_insert_with(|| Value::Object(Map::new()));
+                if !entry.is_object() {
+                    *entry = Value::Object(Map::new());
+                    clear_sources(sources, &path);
+                }
+                let Value::Object(map) = entry else {
+                    unreachable!("the item is a map");
+                };
+                sources.remove(&path);
+                merge_map(map, layer, &path, source, sources)?;
+                // the empty map is the item of the layer.
+                if map.is_empty() {
+                    sources.insert(path, source.to_string());
+                }
+            }
+            value => {
+                clear_sources(sources, &path);
+                base.insert(key, value);
+                sources.insert(path, source.to_string());
+            }
+        }
+    }
+    Ok(())
+}
+
+impl ConfigLayers {
+    pub(crate) fn new(vars: ConfigVars) -> Self {
+        Self {
+            vars,
+            ..Default::default()
+        }
+    }
+
+    /// merge the layer on the layers, the layer must be a map.
+    pub(crate) fn merge(&mut self, layer: Value, source: &str) -> Result<()> {
+        let Value::Object(layer) = layer else {
+            return Err(anyhow!("{source}: the configure must be a map."));
+        };
+        merge_map(&mut self.value, layer, "", source, &mut self.sources)
+            .map_err(|e| anyhow!("{source}: {e}"))
+    }
+
+    /// merge the text of the format as the layer, the variables of the string items
+    /// are interpolated.
+    pub(crate) fn merge_text(
+        &mut self,
+        text: &str,
+        format: ConfigFormat,
+        source: &str,
+    ) -> Result<()> {
+        let mut layer = parse_value(text, format).map_err(|e| anyhow!("{source}: {e}"))?;
+        self.vars
+            .interpolate_value(&mut layer, "")
+            .map_err(|e| anyhow!("{source}: {e}"))?;
+        self.merge(layer, source)
+    }
+
+    /// the configure of the merged layers.
+    pub(crate) fn config(&self) -> Result<ConfigFile> {
+        ConfigFile::from_value(Value::Object(self.value.clone()))
+    }
+
+    /// the merged configure and the source of every item, the items not given use the defaults.
+    pub(crate) fn resolved(&self) -> Value {
+        json!({
+            "config": self.value,
+            "sources": self.sources,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_merge_layers() {
+        let mut layers = ConfigLayers::default();
+        let base = r#"
+entry: app.wasm
+limited_fuel: 1000
+permissions: ["http://a.com/"]
+envs:
+  A: "1"
+  B: "2"
+cache:
+  directory: /tmp/cache
+"#;
+        layers
+            .merge_text(base, ConfigFormat::Yaml, "base.yaml")
+            .unwrap();
+        let overlay = r#"
+limited_fuel = 2000
+"permissions+" = ["http://b.com/"]
+cache = false
+
+[envs]
+B = "3"
+C = "4"
+"#;
+        layers
+            .merge_text(overlay, ConfigFormat::Toml, "prod.toml")
+            .unwrap();
+        layers
+            .merge(
+                json!({"envs": {"A": null}, "max_threads": 2}),
+                "command line",
+            )
+            .unwrap();
+
+        let resolved = layers.resolved();
+        assert_eq!(
+            resolved["config"],
+            json!({
+                "entry": "app.wasm",
+                "limited_fuel": 2000,
+                "permissions": ["http://a.com/", "http://b.com/"],
+                "envs": {"B": "3", "C": "4"},
+                "cache": false,
+                "max_threads": 2,
+            })
+        );
+        assert_eq!(
+            resolved["sources"],
+            json!({
+                "entry": "base.yaml",
+                "limited_fuel": "prod.toml",
+                "permissions": "base.yaml, prod.toml",
+                "envs.B": "prod.toml",
+                "envs.C": "prod.toml",
+                "cache": "prod.toml",
+                "max_threads": "command line",
+            })
+        );
+        let config = layers.config().unwrap();
+        assert_eq!(config.limited_fuel, Some(2000));
+        assert_eq!(config.envs.len(), 2);
+
+        // the map replaces the short form, and the short form replaces the map.
+        layers
+            .merge(json!({"cache": {"directory": "/tmp"}}), "cli")
+            .unwrap();
+        assert_eq!(layers.resolved()["sources"]["cache.directory"], "cli");
+        layers.merge(json!({"cache": null}), "cli").unwrap();
+        assert!(
+            layers.resolved()["sources"]
+                .get("cache.directory")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_merge_layers_errors() {
+        let mut layers = ConfigLayers::default();
+        layers
+            .merge(json!({"entry": "app.wasm", "limited_fuel": 1}), "base.json")
+            .unwrap();
+        let err = layers
+            .merge(json!({"limited_fuel+": [1]}), "prod.json")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "prod.json: the config `limited_fuel+`: the item is not a list."
+        );
+        let err = layers
+            .merge(json!({"permissions+": "http://a.com/"}), "prod.json")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "prod.json: the config `permissions+`: must be a list to append."
+        );
+        let err = layers.merge(json!([1]), "prod.json").unwrap_err();
+        assert_eq!(err.to_string(), "prod.json: the configure must be a map.");
+        let err = layers
+            .merge_text("entry = ", ConfigFormat::Toml, "prod.toml")
+            .unwrap_err();
+        assert!(err.to_string().starts_with("prod.toml: "));
+
+        layers
+            .merge(json!({"limited_fuel": "many"}), "prod.json")
+            .unwrap();
+        let err = layers.config().unwrap_err();
+        assert!(err.to_string().starts_with("the config `limited_fuel`"));
+    }
+
+    fn test_env(name: &str) -> Option<String> {
+        match name {
+            "BLS_TEST_NODE" => Some("node-7".to_string()),
+            "BLS_TEST_REGION" => Some("us".to_string()),
+            "BLS_TEST_HIDDEN" => Some("hidden".to_string()),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_interpolate_vars() {
+        let vars = ConfigVars {
+            env: test_env,
+            ..ConfigVars::new(
+                vec![
+                    ("NAME".to_string(), "app".to_string()),
+                    ("EMPTY".to_string(), String::new()),
+                    ("BLS_TEST_REGION".to_string(), "eu".to_string()),
+                ],
+                vec!["BLS_TEST_NODE".to_string(), "BLS_TEST_REGION".to_string()],
+            )
+        };
+        assert_eq!(vars.interpolate("${NAME}.wasm").unwrap(), "app.wasm");
+        assert_eq!(vars.interpolate("${NODE:-node-0}").unwrap(), "node-0");
+        assert_eq!(vars.interpolate("${EMPTY:-x}|${EMPTY}").unwrap(), "x|");
+        assert_eq!(vars.interpolate("${BLS_TEST_NODE}").unwrap(), "node-7");
+        assert_eq!(vars.interpolate("${BLS_TEST_REGION}").unwrap(), "eu");
+        assert_eq!(
+            vars.interpolate("$${NAME} $ROOT/$${").unwrap(),
+            "${NAME} $ROOT/${"
+        );
+        // the host environment variable must be allowed.
+        let err = vars.interpolate("${BLS_TEST_HIDDEN}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the variable `BLS_TEST_HIDDEN` is not defined."
+        );
+        let err = vars.interpolate("${NAME:-${x}}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the default of the variable `NAME` can't have the `${`."
+        );
+
+        let err = vars.interpolate("${HOME}").unwrap_err();
+        assert_eq!(err.to_string(), "the variable `HOME` is not defined.");
+        let err = vars.interpolate("${NAME").unwrap_err();
+        assert_eq!(err.to_string(), "the `${` is not closed.");
+        let err = vars.interpolate("${1X}").unwrap_err();
+        assert_eq!(err.to_string(), "the invalid variable name `1X`.");
+
+        let mut layers = ConfigLayers::new(vars);
+        let text =
+            r#"{"entry": "${NAME}.wasm", "limited_fuel": 10, "modules": [{"file": "${LIB}"}]}"#;
+        let err = layers
+            .merge_text(text, ConfigFormat::Json, "app.json")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "app.json: the config `modules[0].file`: the variable `LIB` is not defined."
+        );
+        let text = r#"{"entry": "${NAME}.wasm", "envs": {"${KEY}": "${BLS_TEST_NODE}"}}"#;
+        layers
+            .merge_text(text, ConfigFormat::Json, "app.json")
+            .unwrap();
+        assert_eq!(
+            layers.resolved()["config"],
+            json!({"entry": "app.wasm", "envs": {"${KEY}": "node-7"}})
+        );
+        // the command line layer is not interpolated.
+        layers
+            .merge(json!({"args": ["${NAME}"]}), "command line")
+            .unwrap();
+        assert_eq!(layers.resolved()["config"]["args"], json!(["${NAME}"]));
+    }
+}
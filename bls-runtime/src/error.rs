@@ -22,6 +22,8 @@ pub enum CliExitCode {
     AppTimeout,
     PagesExceedsMemoryLimits,
     ConfigureError,
+    /// the app exited with the exit code by itself.
+    GuestExit(i32),
     UnknownError(String),
 }
 
@@ -53,6 +55,7 @@ impl fmt::Display for CliExitCode {
             CliExitCode::PagesExceedsMemoryLimits => {
                 write!(f, "Memory pages exceeds memory limits")
             }
+            CliExitCode::GuestExit(code) => write!(f, "The app exited with code {}", code),
             CliExitCode::UnknownError(err_str) => write!(f, "Unknown error: {}", err_str),
         }
     }
@@ -112,6 +115,8 @@ impl From<CliExitCode> for u8 {
             CliExitCode::AppTimeout => 15,
             CliExitCode::PagesExceedsMemoryLimits => 16,
             CliExitCode::ConfigureError => 128,
+            // the exit status of process is truncated to 8 bits.
+            CliExitCode::GuestExit(code) => code as u8,
             CliExitCode::UnknownError(_) => 255,
         }
     }
@@ -154,4 +159,13 @@ mod tests {
         let into_i32: i32 = CliExitCode::Success.into();
         assert_eq!(into_i32, 0i32);
     }
+
+    #[test]
+    fn test_cli_exit_code_guest_exit() {
+        let into_u8: u8 = CliExitCode::GuestExit(42).into();
+        assert_eq!(into_u8, 42u8);
+
+        let into_u8: u8 = CliExitCode::GuestExit(256 + 3).into();
+        assert_eq!(into_u8, 3u8);
+    }
 }
@@ -2,25 +2,35 @@
 mod macros;
 mod cli_clap;
 mod config;
+mod config_file;
+mod config_layers;
 mod error;
 mod plog;
+mod serve;
 mod v86;
 mod v86config;
-use blockless::{LoggerLevel, Stdin, blockless_run};
+use blockless::{
+    BlocklessConfig, LoggerLevel, RunReport, Stdin, blockless_run, blockless_serve_http, precompile,
+};
 #[allow(unused_imports)]
 use clap::Parser;
-use clap::{CommandFactory, FromArgMatches};
-use cli_clap::{CliCommandOpts, RuntimeType};
+use clap::{ArgMatches, CommandFactory, FromArgMatches};
+use cli_clap::{
+    CliCommandOpts, CliSubCommand, CompileOpts, ConfigCommand, ResolveOpts, RuntimeType,
+    ValidateOpts,
+};
 #[allow(unused_imports)]
 use config::CliConfig;
-use config::load_cli_config_extract_from_car;
+use config::{load_cli_config_extract_from_car, resolve_layers, validate_config_file};
+use config_file::ConfigFile;
+use config_layers::LayerOpts;
 use env_logger::Target;
 use error::CliExitCode;
 use log::{LevelFilter, error, info};
 use std::fs;
 use std::path::Path;
 use std::process::exit;
-use std::{io::Read, path::PathBuf, time::Duration};
+use std::{io::Read, path::PathBuf};
 use v86::V86Lib;
 use v86config::load_v86conf_extract_from_car;
 
@@ -95,8 +105,8 @@ fn file_md5(f: impl AsRef<Path>) -> Result<String, CliExitCode> {
     Ok(format!("{digest:x}"))
 }
 
-fn check_module_sum(cfg: &CliConfig) -> Result<(), CliExitCode> {
-    for module in cfg.0.modules_ref() {
+fn check_module_sum(cfg: &BlocklessConfig) -> Result<(), CliExitCode> {
+    for module in cfg.modules_ref() {
         let m_file = &module.file;
         let md5sum = file_md5(m_file)?;
         if md5sum != module.md5 {
@@ -110,8 +120,11 @@ fn check_module_sum(cfg: &CliConfig) -> Result<(), CliExitCode> {
 /// the cli support 3 type file,
 /// 1. the car file format, all files archive into the car file.
 /// 2. the wasm or wasi file format, will run wasm directly.
-/// 3. the the config file, format, all files is define in the config file.
-fn load_cli_config(file_path: &str) -> Result<CliConfig, CliExitCode> {
+/// 3. the the config file, format, all files is define in the config file,
+///    the `.toml`, `.yaml` and `.yml` files are TOML and YAML, the others are JSON.
+///
+/// the overlay files are merged on the configure of the car file or the config file.
+fn load_cli_config(file_path: &str, opts: &LayerOpts) -> Result<CliConfig, CliExitCode> {
     let ext = Path::new(file_path).extension();
     let cfg = ext.and_then(|ext| ext.to_str().map(str::to_ascii_lowercase));
     let cli_config = match cfg {
@@ -124,15 +137,15 @@ fn load_cli_config(file_path: &str) -> Result<CliConfig, CliExitCode> {
                         "the car file does not exist or is unreadable.".into(),
                     )
                 })?;
-            Some(load_cli_config_extract_from_car(file))
+            Some(load_cli_config_extract_from_car(file, opts))
         }
-        Some(ext) if ext == "wasm" || ext == "wasi" || ext == "wat" => {
-            Some(Ok(CliConfig::new_with_wasm(file_path)))
+        Some(ext) if ext == "wasm" || ext == "wasi" || ext == "wat" || ext == "cwasm" => {
+            Some(CliConfig::from_wasm(file_path, opts))
         }
         _ => None,
     };
     cli_config
-        .unwrap_or_else(|| CliConfig::from_file(file_path))
+        .unwrap_or_else(|| CliConfig::from_file(file_path, opts))
         .map_err(|e| CliExitCode::UnknownError(e.to_string()))
 }
 
@@ -161,35 +174,154 @@ async fn wasm_runtime(mut cfg: CliConfig, cli_command_opts: CliCommandOpts) -> C
         return err;
     }
 
-    let run_time = cfg.0.run_time();
-    cli_command_opts.into_config(&mut cfg).unwrap();
+    let run_report = cli_command_opts.run_report().cloned();
+    if let Some(addr) = cli_command_opts.http_listen() {
+        info!("The http handler started.");
+        if let Err(e) = blockless_serve_http(cfg.0, addr).await {
+            perror!("failed to serve the http requests: {}", e);
+            return CliExitCode::UnknownError(e.to_string());
+        }
+        return CliExitCode::Success;
+    }
     if cfg.0.is_fixed_stdin() {
         if let Some(stdin_buffer) = non_blocking_read(std::io::stdin()).await {
             cfg.0.stdio.stdin(Stdin::Fixed(stdin_buffer));
         }
     }
 
-    if let Some(time) = run_time {
-        let _ = tokio::spawn(async move {
-            tokio::time::sleep(Duration::from_millis(time)).await;
-            info!("The wasm execute finish, the exit code: 15");
-            std::process::exit(CliExitCode::AppTimeout.into());
-        })
-        .await;
-    }
-
     info!("The wasm app started.");
     std::panic::set_hook(Box::new(|panic_info| {
         error!("{}", panic_info);
         perror!("WASM app crashed, please check the runtime.log file");
     }));
 
-    let exit_status = blockless_run(cfg.0).await.unwrap();
-    info!(
-        "The wasm execute finish, the exit code: {}",
-        exit_status.code
-    );
-    exit_status.code.into()
+    let report = match blockless_run(cfg.0).await {
+        Ok(report) => report,
+        Err(e) => {
+            perror!("failed to run the app: {}", e);
+            return CliExitCode::UnknownError(e.to_string());
+        }
+    };
+    info!("The wasm execute finish, the exit code: {}", report.code);
+    // print the results of the invoked export.
+    for result in report.results.iter().flatten() {
+        println!("{result}");
+    }
+    if let Some(path) = run_report {
+        if let Err(e) = emit_run_report(&report, &path) {
+            perror!("{}", e);
+        }
+    }
+    match report.guest_exit_code {
+        Some(code) => CliExitCode::GuestExit(code),
+        None => report.code.into(),
+    }
+}
+
+/// write the run report as JSON for the orchestrators, the `-` means stderr, the stdout
+/// is the output of the app.
+fn emit_run_report(report: &RunReport, path: &str) -> Result<(), CliExitCode> {
+    let json = report.to_json().dump();
+    if path == "-" {
+        eprintln!("{json}");
+        return Ok(());
+    }
+    fs::write(path, json)
+        .map_err(|e| CliExitCode::UnknownError(format!("failed to write the run report: {}", e)))
+}
+
+/// precompile the wasm file into the `.cwasm` artifact.
+fn compile_wasm(opts: CompileOpts) -> CliExitCode {
+    let bytes = match fs::read(&opts.input) {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            perror!("failed to read the file {}: {}", opts.input, e);
+            return CliExitCode::UnknownError(e.to_string());
+        }
+    };
+    let conf = opts.engine_config();
+    let artifact = match precompile(&conf, opts.target.as_deref(), &bytes) {
+        Ok(artifact) => artifact,
+        Err(e) => {
+            perror!("failed to compile the file {}: {:#}", opts.input, e);
+            return CliExitCode::UnknownError(e.to_string());
+        }
+    };
+    let output = opts.output_path();
+    if let Err(e) = fs::write(&output, artifact.bytes) {
+        perror!("failed to write the file {}: {}", output.display(), e);
+        return CliExitCode::UnknownError(e.to_string());
+    }
+    CliExitCode::Success
+}
+
+/// validate the configure file, or print the JSON schema of the configure file.
+fn validate_config(opts: ValidateOpts) -> CliExitCode {
+    if opts.schema {
+        println!("{}", ConfigFile::json_schema());
+        return CliExitCode::Success;
+    }
+    let input = opts.input.unwrap_or_default();
+    let layer_opts = LayerOpts {
+        vars: opts.var_flags.config_vars(),
+        ..Default::default()
+    };
+    match validate_config_file(&input, &layer_opts) {
+        Ok(()) => {
+            println!("{input}: the configure is valid.");
+            CliExitCode::Success
+        }
+        Err(e) => {
+            perror!("{input}: {}", e);
+            CliExitCode::ConfigureError
+        }
+    }
+}
+
+/// the configure resolved from the input, the overlays and the command line options.
+fn resolve_layered_config(
+    cli_command_opts: &CliCommandOpts,
+    matches: &ArgMatches,
+) -> anyhow::Result<serde_json::Value> {
+    if cli_command_opts.v86 {
+        anyhow::bail!("the v86 configure can't be resolved.");
+    }
+    let layer_opts = cli_command_opts.layer_opts(matches)?;
+    let layers = resolve_layers(cli_command_opts.input_ref(), &layer_opts)?;
+    CliConfig::from_layers(&layers)?;
+    Ok(layers.resolved())
+}
+
+/// print the configure resolved from the layers and the source of every item,
+/// the arguments are parsed as the running.
+fn resolve_config(opts: ResolveOpts) -> CliExitCode {
+    let args = std::iter::once("bls-runtime".to_string()).chain(opts.args);
+    let matches = match CliCommandOpts::command().try_get_matches_from(args) {
+        Ok(matches) => matches,
+        Err(e) => {
+            let _ = e.print();
+            return CliExitCode::ConfigureError;
+        }
+    };
+    let cli_command_opts = match opts_from_matches(&matches) {
+        Ok(opts) => opts,
+        Err(e) => {
+            let _ = e.print();
+            return CliExitCode::ConfigureError;
+        }
+    };
+    set_root_path_env_var(&cli_command_opts);
+    match resolve_layered_config(&cli_command_opts, &matches) {
+        Ok(resolved) => {
+            let resolved = serde_json::to_string_pretty(&resolved).unwrap_or_default();
+            println!("{resolved}");
+            CliExitCode::Success
+        }
+        Err(e) => {
+            perror!("{}: {}", cli_command_opts.input_ref(), e);
+            CliExitCode::ConfigureError
+        }
+    }
 }
 
 fn set_root_path_env_var(cli_command_opts: &CliCommandOpts) {
@@ -214,10 +346,9 @@ async fn non_blocking_read<R: Read + Send + 'static>(mut reader: R) -> Option<St
     rx.recv_timeout(std::time::Duration::from_millis(1000)).ok()
 }
 
-fn parse_args() -> CliCommandOpts {
-    let mut cli_command = CliCommandOpts::command();
-    let clap_match = cli_command.get_matches_mut();
-    let cli_command_opts = CliCommandOpts::from_arg_matches(&clap_match);
+/// the options of the matches, the permission flags without the values grant all.
+fn opts_from_matches(clap_match: &ArgMatches) -> Result<CliCommandOpts, clap::Error> {
+    let mut o = CliCommandOpts::from_arg_matches(clap_match)?;
     macro_rules! set_perm_grant {
         ($id: literal, $perm: expr) => {
             if $perm.is_none() && clap_match.contains_id($id) {
@@ -225,16 +356,20 @@ fn parse_args() -> CliCommandOpts {
             }
         };
     }
-    match cli_command_opts {
-        Ok(mut o) => {
-            set_perm_grant!("allow-read", o.permission_flags.allow_read);
-            set_perm_grant!("allow-write", o.permission_flags.allow_write);
-            set_perm_grant!("deny-read", o.permission_flags.deny_read);
-            set_perm_grant!("deny-write", o.permission_flags.deny_write);
-            set_perm_grant!("allow-net", o.permission_flags.allow_net);
-            set_perm_grant!("deny-net", o.permission_flags.deny_net);
-            o
-        }
+    set_perm_grant!("allow-read", o.permission_flags.allow_read);
+    set_perm_grant!("allow-write", o.permission_flags.allow_write);
+    set_perm_grant!("deny-read", o.permission_flags.deny_read);
+    set_perm_grant!("deny-write", o.permission_flags.deny_write);
+    set_perm_grant!("allow-net", o.permission_flags.allow_net);
+    set_perm_grant!("deny-net", o.permission_flags.deny_net);
+    Ok(o)
+}
+
+fn parse_args() -> (CliCommandOpts, ArgMatches) {
+    let mut cli_command = CliCommandOpts::command();
+    let clap_match = cli_command.get_matches_mut();
+    match opts_from_matches(&clap_match) {
+        Ok(o) => (o, clap_match),
         Err(_) => {
             exit(255);
         }
@@ -243,7 +378,16 @@ fn parse_args() -> CliCommandOpts {
 
 #[tokio::main]
 async fn main() -> CliExitCode {
-    let cli_command_opts = parse_args();
+    let (mut cli_command_opts, matches) = parse_args();
+    match cli_command_opts.command.take() {
+        Some(CliSubCommand::Compile(opts)) => return compile_wasm(opts),
+        Some(CliSubCommand::Serve(opts)) => return serve::serve(opts).await,
+        Some(CliSubCommand::Validate(opts)) => return validate_config(opts),
+        Some(CliSubCommand::Config(opts)) => match opts.command {
+            ConfigCommand::Resolve(opts) => return resolve_config(opts),
+        },
+        None => {}
+    }
     set_root_path_env_var(&cli_command_opts);
     let path = cli_command_opts.input_ref();
 
@@ -256,16 +400,27 @@ async fn main() -> CliExitCode {
             }
         },
         RuntimeType::Wasm => {
-            let cfg = match load_cli_config(path) {
+            let layer_opts = match cli_command_opts.layer_opts(&matches) {
+                Ok(layer_opts) => layer_opts,
+                Err(e) => {
+                    perror!("failed to load CLI config: {}", e);
+                    return CliExitCode::ConfigureError;
+                }
+            };
+            let cfg = match load_cli_config(path, &layer_opts) {
                 Ok(cfg) => cfg,
                 Err(e) => {
                     perror!("failed to load CLI config: {}", e);
                     return e;
                 }
             };
-            if let Err(code) = check_module_sum(&cfg) {
-                perror!("{}", code);
-                return code;
+            // the modules of the command line replace the modules of the configure,
+            // they have no checksums.
+            if cli_command_opts.modules.is_empty() {
+                if let Err(code) = check_module_sum(&cfg.0) {
+                    perror!("{}", code);
+                    return code;
+                }
             }
             return wasm_runtime(cfg, cli_command_opts).await;
         }
@@ -301,7 +456,7 @@ mod test {
 
     #[test]
     fn test_load_cli_wasm_config() {
-        let wasm_conf = load_cli_config("test.wasm");
+        let wasm_conf = load_cli_config("test.wasm", &LayerOpts::default());
         let wasm_conf = wasm_conf.unwrap();
         let entry_ref = wasm_conf.0.entry_ref();
         assert_eq!(entry_ref, "test.wasm");
@@ -364,6 +519,32 @@ mod test {
         assert_eq!(cfg.0.drivers_root_path_ref(), Some("target/drivers"));
     }
 
+    #[test]
+    fn test_load_yaml_from_car() {
+        let mut buf = Vec::new();
+        let output = std::io::Cursor::new(&mut buf);
+        let mut writer = car_writer::new_v1_default_roots(output).unwrap();
+        let data =
+            b"fs_root_path: $ENV_ROOT_PATH\nentry: release.wasm\nlimited_fuel: 1000\n".to_vec();
+        let d_len = data.len();
+        let f_cid = writer.write_ipld(Ipld::Bytes(data)).unwrap();
+        let mut unixfs = UnixFs::new_directory();
+        unixfs.add_link(Link::new(f_cid, "config.yaml".to_string(), d_len as _));
+        let root_cid = writer.write_ipld(unixfs.encode().unwrap()).unwrap();
+        writer
+            .rewrite_header(CarHeader::new_v1(vec![root_cid]))
+            .unwrap();
+        writer.flush().unwrap();
+
+        unsafe { std::env::set_var("ENV_ROOT_PATH", "target") };
+        let input = std::io::Cursor::new(&mut buf);
+        let mut car_reader = reader::new_v1(input).unwrap();
+        let cfg = load_cli_config_from_car(&mut car_reader).unwrap();
+        assert_eq!(cfg.0.fs_root_path_ref(), Some("target"));
+        assert_eq!(cfg.0.entry_ref(), "release.wasm");
+        assert_eq!(cfg.0.get_limited_fuel(), Some(1000));
+    }
+
     #[tokio::test]
     async fn test_no_input_non_blocking_read() {
         let result = non_blocking_read(std::io::stdin()).await;
@@ -0,0 +1,863 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, UNIX_EPOCH};
+
+use anyhow::{Context, Result, anyhow, bail};
+use blockless::{
+    BlocklessConfig, BlocklessConfigVersion, BlocklessEngine, BlocklessRunner, LoggerLevel,
+    OutputCapture, RunReport, Stderr, Stdin, Stdout,
+};
+use json::JsonValue;
+use log::{debug, error, info};
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio::io::{AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::runtime::Handle;
+use tokio::sync::{Semaphore, watch};
+
+use crate::cli_clap::{ServeAddr, ServeOpts};
+use crate::error::CliExitCode;
+
+/// the maximum size of the request body.
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024;
+
+/// the maximum number of the request headers.
+const MAX_HEADERS: usize = 100;
+
+/// the timeout of reading the request, the idle connection is closed after the timeout.
+const REQUEST_READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// the maximum number of the prepared runners cached by the server.
+const MAX_CACHED_RUNNERS: usize = 64;
+
+/// The job submitted to the server, the module is the WASM file, the CAR file or
+/// the configure file, the `config` overrides the configure of the module.
+struct JobSpec {
+    module: String,
+    config: JsonValue,
+    stdin: String,
+    args: Vec<String>,
+    envs: Vec<(String, String)>,
+    // wait for the job finished before the response.
+    wait: bool,
+}
+
+impl JobSpec {
+    fn from_json(job_json: &JsonValue) -> Result<Self> {
+        if !job_json.is_object() {
+            bail!("the job must be an object.");
+        }
+        let module = job_json["module"]
+            .as_str()
+            .context("the job module must be a string.")?
+            .to_string();
+        let stdin = &job_json["stdin"];
+        if !stdin.is_null() && !stdin.is_string() {
+            bail!("the job stdin must be a string.");
+        }
+        let args = &job_json["args"];
+        if !args.is_null() && !args.is_array() {
+            bail!("the job args must be an array.");
+        }
+        let args = args
+            .members()
+            .map(|arg| {
+                arg.as_str()
+                    .map(String::from)
+                    .context("the job arg must be a string.")
+            })
+            .collect::<Result<_>>()?;
+        let envs = &job_json["envs"];
+        if !envs.is_null() && !envs.is_object() {
+            bail!("the job envs must be an object.");
+        }
+        let envs = envs
+            .entries()
+            .map(|(name, value)| {
+                value
+                    .as_str()
+                    .map(|value| (name.to_string(), value.to_string()))
+                    .with_context(|| format!("the job env {name} must be a string."))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            module,
+            config: job_json["config"].clone(),
+            stdin: stdin.as_str().unwrap_or_default().to_string(),
+            args,
+            envs,
+            wait: job_json["wait"].as_bool().unwrap_or(false),
+        })
+    }
+
+    /// the configure of the job, the stdin is fixed and the output is captured,
+    /// so the jobs never share the stdio of the server.
+    fn blockless_config(&self) -> Result<BlocklessConfig> {
+        let mut cfg = crate::load_cli_config(&self.module, &Default::default())
+            .map_err(|e| anyhow!("{e}"))?;
+        cfg.apply_overrides(&self.config)?;
+        let mut conf = cfg.0;
+        conf.stdio.stdin(Stdin::Fixed(self.stdin.clone()));
+        conf.stdio.stdout(Stdout::Buffer(OutputCapture::default()));
+        conf.stdio.stderr(Stderr::Buffer(OutputCapture::default()));
+        if !self.args.is_empty() {
+            conf.set_stdin_args(self.args.clone());
+        }
+        if !self.envs.is_empty() {
+            conf.set_envs(self.envs.clone());
+        }
+        Ok(conf)
+    }
+
+    /// the key of the prepared runner, the jobs with the same files and configure
+    /// overrides share the runner, the files are the job module and the modules of it.
+    /// The files are identified by the size and the modified time, so they are not
+    /// read when the runner is cached.
+    fn runner_key(&self, conf: &BlocklessConfig) -> Result<String> {
+        let mut files = vec![self.module.as_str()];
+        match conf.version() {
+            BlocklessConfigVersion::Version0 => files.push(conf.entry_ref()),
+            BlocklessConfigVersion::Version1 => {
+                files.extend(conf.modules_ref().into_iter().map(|m| m.file.as_str()))
+            }
+        }
+        files.dedup();
+        let mut ctx = md5::Context::new();
+        ctx.consume(self.config.dump());
+        for file in files {
+            let meta = std::fs::metadata(file).with_context(|| format!("failed to read {file}"))?;
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .unwrap_or_default();
+            ctx.consume(file);
+            ctx.consume(format!("{}:{}", meta.len(), modified.as_nanos()));
+        }
+        Ok(format!("{:x}", ctx.compute()))
+    }
+
+    async fn run(self, engine: BlocklessEngine, runners: Arc<RunnerCache>) -> Result<RunReport> {
+        let conf = self.blockless_config()?;
+        let key = self.runner_key(&conf)?;
+        // the checksums of the modules are checked only when the runner is prepared.
+        let runner = runners.get_or_prepare(key, || {
+            crate::check_module_sum(&conf).map_err(|e| anyhow!("{e}"))?;
+            engine.prepare(&conf)
+        })?;
+        runner.run(conf).await
+    }
+}
+
+/// The prepared runners of the jobs, so the modules are compiled once for the jobs
+/// of the same module files, the least recently used runner is removed when it's full.
+#[derive(Default)]
+struct RunnerCache {
+    clock: AtomicU64,
+    runners: Mutex<HashMap<String, (Arc<BlocklessRunner>, u64)>>,
+}
+
+impl RunnerCache {
+    fn get_or_prepare(
+        &self,
+        key: String,
+        prepare: impl FnOnce() -> Result<BlocklessRunner>,
+    ) -> Result<Arc<BlocklessRunner>> {
+        let tick = self.clock.fetch_add(1, Ordering::SeqCst);
+        if let Some((runner, used)) = self.runners.lock().unwrap().get_mut(&key) {
+            *used = tick;
+            return Ok(runner.clone());
+        }
+        // the module is compiled without the lock, so the other jobs are not blocked.
+        let runner = Arc::new(prepare()?);
+        let mut runners = self.runners.lock().unwrap();
+        if runners.len() >= MAX_CACHED_RUNNERS {
+            let oldest = runners
+                .iter()
+                .min_by_key(|(_, (_, used))| *used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                runners.remove(&oldest);
+            }
+        }
+        runners.insert(key, (runner.clone(), tick));
+        Ok(runner)
+    }
+
+    fn len(&self) -> usize {
+        self.runners.lock().unwrap().len()
+    }
+}
+
+#[derive(Clone)]
+enum JobState {
+    Queued,
+    Running,
+    // the run report of the job.
+    Finished(JsonValue),
+    // the job can't run, e.g. the module is not found.
+    Failed(String),
+}
+
+impl JobState {
+    fn as_str(&self) -> &'static str {
+        match self {
+            JobState::Queued => "queued",
+            JobState::Running => "running",
+            JobState::Finished(_) => "finished",
+            JobState::Failed(_) => "failed",
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        matches!(self, JobState::Finished(_) | JobState::Failed(_))
+    }
+}
+
+#[derive(Clone)]
+struct Job {
+    module: String,
+    state: watch::Receiver<JobState>,
+}
+
+impl Job {
+    fn to_json(&self, id: u64) -> JsonValue {
+        let mut obj = JsonValue::new_object();
+        obj["id"] = id.into();
+        obj["module"] = self.module.clone().into();
+        let state = self.state.borrow();
+        obj["status"] = state.as_str().into();
+        match &*state {
+            JobState::Finished(report) => obj["report"] = report.clone(),
+            JobState::Failed(error) => obj["error"] = error.clone().into(),
+            _ => {}
+        }
+        obj
+    }
+}
+
+struct Request {
+    method: String,
+    path: String,
+    body: Vec<u8>,
+}
+
+struct Response {
+    status: u16,
+    body: JsonValue,
+}
+
+impl Response {
+    fn new(status: u16, body: JsonValue) -> Self {
+        Self { status, body }
+    }
+
+    fn error(status: u16, message: impl Into<String>) -> Self {
+        let mut body = JsonValue::new_object();
+        body["error"] = message.into().into();
+        Self::new(status, body)
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let reason = match self.status {
+            200 => "OK",
+            202 => "Accepted",
+            400 => "Bad Request",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            _ => "Internal Server Error",
+        };
+        let body = self.body.dump();
+        format!(
+            "HTTP/1.1 {} {reason}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            self.status,
+            body.len(),
+        )
+        .into_bytes()
+    }
+}
+
+/// read the HTTP/1.1 request, only the body with `Content-Length` is supported.
+async fn read_request<R: AsyncBufRead + Unpin>(reader: &mut R) -> Result<Request> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        bail!("the connection is closed.");
+    }
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        bail!("the request line is malformed.");
+    };
+    let (method, path) = (method.to_string(), path.to_string());
+    let mut content_length = 0;
+    for _ in 0..MAX_HEADERS {
+        line.clear();
+        if reader.read_line(&mut line).await? == 0 {
+            bail!("the request headers are incomplete.");
+        }
+        let header = line.trim_end();
+        if header.is_empty() {
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).await?;
+            return Ok(Request { method, path, body });
+        }
+        let Some((name, value)) = header.split_once(':') else {
+            bail!("the request header is malformed.");
+        };
+        let name = name.trim();
+        if name.eq_ignore_ascii_case("transfer-encoding") {
+            bail!("the chunked request body is not supported.");
+        }
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = value
+                .trim()
+                .parse()
+                .context("the content-length is invalid.")?;
+            if content_length > MAX_BODY_SIZE {
+                bail!("the request body is too large.");
+            }
+        }
+    }
+    bail!("too many request headers.")
+}
+
+/// remove the oldest finished jobs over the limit, the jobs not done are kept.
+fn remove_oldest_finished(jobs: &mut HashMap<u64, Job>, max_finished_jobs: usize) {
+    let mut finished: Vec<u64> = jobs
+        .iter()
+        .filter(|(_, job)| job.state.borrow().is_done())
+        .map(|(id, _)| *id)
+        .collect();
+    if finished.len() <= max_finished_jobs {
+        return;
+    }
+    finished.sort_unstable();
+    let excess = finished.len() - max_finished_jobs;
+    for id in &finished[..excess] {
+        jobs.remove(id);
+    }
+}
+
+/// The listener of the job API.
+enum Listener {
+    Tcp(TcpListener),
+    #[cfg(unix)]
+    Unix(UnixListener),
+}
+
+impl Listener {
+    async fn bind(addr: &ServeAddr) -> Result<Self> {
+        match addr {
+            ServeAddr::Tcp(addr) => {
+                let listener = TcpListener::bind(addr)
+                    .await
+                    .with_context(|| format!("failed to listen on {addr}"))?;
+                Ok(Listener::Tcp(listener))
+            }
+            #[cfg(unix)]
+            ServeAddr::Unix(path) => {
+                let listener = UnixListener::bind(path)
+                    .with_context(|| format!("failed to listen on {}", path.display()))?;
+                Ok(Listener::Unix(listener))
+            }
+            #[cfg(not(unix))]
+            ServeAddr::Unix(_) => bail!("the unix socket is not supported on the platform."),
+        }
+    }
+
+    fn local_addr(&self) -> String {
+        match self {
+            Listener::Tcp(l) => l.local_addr().map(|a| a.to_string()),
+            #[cfg(unix)]
+            Listener::Unix(l) => l.local_addr().map(|a| match a.as_pathname() {
+                Some(path) => format!("unix:{}", path.display()),
+                None => "unix".to_string(),
+            }),
+        }
+        .unwrap_or_default()
+    }
+}
+
+/// The job server, the jobs run on the shared engine, the permits limit
+/// the jobs running concurrently.
+struct JobServer {
+    engine: BlocklessEngine,
+    permits: Arc<Semaphore>,
+    runners: Arc<RunnerCache>,
+    // the maximum number of the finished jobs kept.
+    max_finished_jobs: usize,
+    read_timeout: Duration,
+    next_id: AtomicU64,
+    jobs: Arc<Mutex<HashMap<u64, Job>>>,
+}
+
+impl JobServer {
+    fn new(opts: &ServeOpts) -> Result<Self> {
+        let engine = BlocklessEngine::new(&opts.engine_config())?;
+        Ok(Self {
+            engine,
+            permits: Arc::new(Semaphore::new(opts.max_concurrent_jobs())),
+            runners: Default::default(),
+            max_finished_jobs: opts.max_finished_jobs,
+            read_timeout: REQUEST_READ_TIMEOUT,
+            next_id: AtomicU64::new(1),
+            jobs: Default::default(),
+        })
+    }
+
+    async fn serve(self: Arc<Self>, listener: Listener) -> Result<()> {
+        loop {
+            let server = self.clone();
+            let accepted = match &listener {
+                Listener::Tcp(l) => l
+                    .accept()
+                    .await
+                    .map(|(stream, _)| tokio::spawn(server.handle_connection(stream))),
+                #[cfg(unix)]
+                Listener::Unix(l) => l
+                    .accept()
+                    .await
+                    .map(|(stream, _)| tokio::spawn(server.handle_connection(stream))),
+            };
+            // the accept error is transient, e.g. too many open files.
+            if let Err(e) = accepted {
+                error!("failed to accept the connection: {e}");
+            }
+        }
+    }
+
+    async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(self: Arc<Self>, mut stream: S) {
+        let reading = read_request(&mut BufReader::new(&mut stream));
+        let response = match tokio::time::timeout(self.read_timeout, reading).await {
+            Ok(Ok(request)) => self.route(request).await,
+            Ok(Err(e)) => Response::error(400, format!("{e:#}")),
+            Err(_) => Response::error(408, "the request is timeout."),
+        };
+        if let Err(e) = stream.write_all(&response.to_bytes()).await {
+            debug!("failed to write the response: {e}");
+        }
+        let _ = stream.shutdown().await;
+    }
+
+    async fn route(&self, request: Request) -> Response {
+        let path = request.path.split('?').next().unwrap_or_default();
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["jobs"]) => self.submit(&request.body).await,
+            ("GET", ["jobs"]) => self.list(),
+            ("GET", ["jobs", id]) => self.get(id),
+            ("DELETE", ["jobs", id]) => self.remove(id),
+            (_, ["jobs"]) | (_, ["jobs", _]) => Response::error(405, "the method is not allowed."),
+            _ => Response::error(404, "the path is not found."),
+        }
+    }
+
+    async fn submit(&self, body: &[u8]) -> Response {
+        let job_json = match std::str::from_utf8(body).map(json::parse) {
+            Ok(Ok(job_json)) => job_json,
+            _ => return Response::error(400, "the job must be the JSON object."),
+        };
+        let spec = match JobSpec::from_json(&job_json) {
+            Ok(spec) => spec,
+            Err(e) => return Response::error(400, format!("{e:#}")),
+        };
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (sender, state) = watch::channel(JobState::Queued);
+        let wait = spec.wait;
+        let mut job = Job {
+            module: spec.module.clone(),
+            state,
+        };
+        self.jobs.lock().unwrap().insert(id, job.clone());
+        info!("the job {id} is submitted: {}", spec.module);
+        self.spawn_job(id, spec, sender);
+        if !wait {
+            return Response::new(202, job.to_json(id));
+        }
+        // the sender is never dropped before the job done.
+        let _ = job.state.wait_for(JobState::is_done).await;
+        // the job may be removed from the list already, the response is from the own copy.
+        Response::new(200, job.to_json(id))
+    }
+
+    fn spawn_job(&self, id: u64, spec: JobSpec, state: watch::Sender<JobState>) {
+        let engine = self.engine.clone();
+        let runners = self.runners.clone();
+        let permits = self.permits.clone();
+        let jobs = self.jobs.clone();
+        let max_finished_jobs = self.max_finished_jobs;
+        tokio::spawn(async move {
+            // the queued jobs wait for the permit.
+            let Ok(_permit) = permits.acquire_owned().await else {
+                return;
+            };
+            state.send_replace(JobState::Running);
+            // the job runs on the blocking thread, so the guest never blocks the API.
+            let handle = Handle::current();
+            let result =
+                tokio::task::spawn_blocking(move || handle.block_on(spec.run(engine, runners)));
+            let done = match result.await {
+                Ok(Ok(report)) => {
+                    info!("the job {id} is finished, the exit code: {}", report.code);
+                    JobState::Finished(report.to_json())
+                }
+                Ok(Err(e)) => {
+                    error!("the job {id} is failed: {e:#}");
+                    JobState::Failed(format!("{e:#}"))
+                }
+                Err(e) => JobState::Failed(format!("the job is crashed: {e}")),
+            };
+            // the job is done and the oldest finished jobs are removed at once,
+            // so the list never has the finished jobs over the limit.
+            let mut jobs = jobs.lock().unwrap();
+            state.send_replace(done);
+            remove_oldest_finished(&mut jobs, max_finished_jobs);
+        });
+    }
+
+    fn job_response(&self, status: u16, id: u64) -> Response {
+        match self.jobs.lock().unwrap().get(&id) {
+            Some(job) => Response::new(status, job.to_json(id)),
+            None => Response::error(404, format!("the job {id} is not found.")),
+        }
+    }
+
+    fn list(&self) -> Response {
+        let jobs = self.jobs.lock().unwrap();
+        let mut ids: Vec<_> = jobs.keys().copied().collect();
+        ids.sort_unstable();
+        let mut list = JsonValue::new_array();
+        for id in ids {
+            let mut obj = JsonValue::new_object();
+            obj["id"] = id.into();
+            obj["status"] = jobs[&id].state.borrow().as_str().into();
+            let _ = list.push(obj);
+        }
+        let mut body = JsonValue::new_object();
+        body["jobs"] = list;
+        Response::new(200, body)
+    }
+
+    fn get(&self, id: &str) -> Response {
+        match id.parse() {
+            Ok(id) => self.job_response(200, id),
+            Err(_) => Response::error(404, format!("the job {id} is not found.")),
+        }
+    }
+
+    /// remove the finished job, the running job can't be removed.
+    fn remove(&self, id: &str) -> Response {
+        let Ok(id) = id.parse::<u64>() else {
+            return Response::error(404, format!("the job {id} is not found."));
+        };
+        let mut jobs = self.jobs.lock().unwrap();
+        let Some(job) = jobs.get(&id) else {
+            return Response::error(404, format!("the job {id} is not found."));
+        };
+        if !job.state.borrow().is_done() {
+            return Response::error(409, format!("the job {id} is not finished."));
+        }
+        let job = jobs.remove(&id).unwrap();
+        Response::new(200, job.to_json(id))
+    }
+}
+
+/// serve the jobs on the job API until the server is killed.
+pub(crate) async fn serve(opts: ServeOpts) -> CliExitCode {
+    if let Err(e) = crate::logger_init(None, LoggerLevel::INFO) {
+        perror!("failed to init logger: {}", e);
+        return e;
+    }
+    let server = match JobServer::new(&opts) {
+        Ok(server) => Arc::new(server),
+        Err(e) => {
+            perror!("failed to create the engine: {:#}", e);
+            return CliExitCode::UnknownError(e.to_string());
+        }
+    };
+    let listener = match Listener::bind(&opts.listen).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            perror!("{:#}", e);
+            return CliExitCode::UnknownError(e.to_string());
+        }
+    };
+    info!("the job server is listening on {}", listener.local_addr());
+    if let Err(e) = server.serve(listener).await {
+        perror!("the job server is stopped: {:#}", e);
+        return CliExitCode::UnknownError(e.to_string());
+    }
+    CliExitCode::Success
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::time::Duration;
+
+    use clap::Parser;
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::cli_clap::{CliCommandOpts, CliSubCommand};
+
+    const HELLO_WAT: &str = r#"
+    (module
+        (import "wasi_snapshot_preview1" "fd_write"
+            (func $fd_write (param i32 i32 i32 i32) (result i32)))
+        (memory (export "memory") 1)
+        (data (i32.const 16) "hello\n")
+        (func (export "_start")
+            (i32.store (i32.const 0) (i32.const 16))
+            (i32.store (i32.const 4) (i32.const 6))
+            (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+        )
+    )
+    "#;
+
+    const SPIN_WAT: &str = r#"
+    (module
+        (func (export "_start")
+            (loop $l (br $l))
+        )
+        (memory (export "memory") 1)
+    )
+    "#;
+
+    fn serve_opts(args: &[&str]) -> ServeOpts {
+        let args = ["cli", "serve"].iter().chain(args);
+        match CliCommandOpts::try_parse_from(args).unwrap().command {
+            Some(CliSubCommand::Serve(opts)) => opts,
+            _ => panic!("expected the serve subcommand"),
+        }
+    }
+
+    async fn start_server(args: &[&str]) -> (SocketAddr, Arc<JobServer>) {
+        let opts = serve_opts(args);
+        let server = Arc::new(JobServer::new(&opts).unwrap());
+        let listener = Listener::bind(&opts.listen).await.unwrap();
+        let addr = listener.local_addr().parse().unwrap();
+        tokio::spawn(server.clone().serve(listener));
+        (addr, server)
+    }
+
+    async fn request<S: AsyncRead + AsyncWrite + Unpin>(
+        mut stream: S,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (u16, JsonValue) {
+        let request = format!(
+            "{method} {path} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, json::parse(body).unwrap())
+    }
+
+    async fn tcp_request(
+        addr: SocketAddr,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (u16, JsonValue) {
+        request(TcpStream::connect(addr).await.unwrap(), method, path, body).await
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serve_job() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let module = temp_dir.path().join("hello.wat");
+        std::fs::write(&module, HELLO_WAT).unwrap();
+        let (addr, server) = start_server(&["--listen=127.0.0.1:0"]).await;
+
+        let job = format!(
+            r#"{{"module": {:?}, "wait": true}}"#,
+            module.to_str().unwrap()
+        );
+        let (status, job) = tcp_request(addr, "POST", "/jobs", &job).await;
+        assert_eq!(status, 200);
+        assert_eq!(job["status"], "finished");
+        assert_eq!(job["report"]["code"], 0);
+        assert_eq!(job["report"]["stdout"], "hello\n");
+        // the job of the same module reuses the prepared runner.
+        let again = format!(
+            r#"{{"module": {:?}, "wait": true}}"#,
+            module.to_str().unwrap()
+        );
+        let (status, again) = tcp_request(addr, "POST", "/jobs", &again).await;
+        assert_eq!(status, 200);
+        assert_eq!(again["report"]["stdout"], "hello\n");
+        assert_eq!(server.runners.len(), 1);
+        let (status, _) = tcp_request(addr, "DELETE", &format!("/jobs/{}", again["id"]), "").await;
+        assert_eq!(status, 200);
+        // the changed module is prepared again.
+        let changed = HELLO_WAT.replace("hello", "howdy") + ";; changed\n";
+        std::fs::write(&module, changed).unwrap();
+        let changed = format!(
+            r#"{{"module": {:?}, "wait": true}}"#,
+            module.to_str().unwrap()
+        );
+        let (status, changed) = tcp_request(addr, "POST", "/jobs", &changed).await;
+        assert_eq!(status, 200);
+        assert_eq!(changed["report"]["stdout"], "howdy\n");
+        assert_eq!(server.runners.len(), 2);
+        let (status, _) =
+            tcp_request(addr, "DELETE", &format!("/jobs/{}", changed["id"]), "").await;
+        assert_eq!(status, 200);
+
+        let (status, got) = tcp_request(addr, "GET", &format!("/jobs/{}", job["id"]), "").await;
+        assert_eq!(status, 200);
+        assert_eq!(got, job);
+        let (status, list) = tcp_request(addr, "GET", "/jobs", "").await;
+        assert_eq!(status, 200);
+        assert_eq!(list["jobs"].len(), 1);
+
+        let (status, _) = tcp_request(addr, "DELETE", &format!("/jobs/{}", job["id"]), "").await;
+        assert_eq!(status, 200);
+        let (status, _) = tcp_request(addr, "GET", &format!("/jobs/{}", job["id"]), "").await;
+        assert_eq!(status, 404);
+
+        // the module can't be loaded.
+        let job = r#"{"module": "not-found.wasm", "wait": true}"#;
+        let (status, job) = tcp_request(addr, "POST", "/jobs", job).await;
+        assert_eq!(status, 200);
+        assert_eq!(job["status"], "failed");
+        assert!(job["error"].is_string());
+
+        let (status, _) = tcp_request(addr, "POST", "/jobs", "{}").await;
+        assert_eq!(status, 400);
+        let (status, _) = tcp_request(addr, "PUT", "/jobs", "").await;
+        assert_eq!(status, 405);
+        let (status, _) = tcp_request(addr, "GET", "/", "").await;
+        assert_eq!(status, 404);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serve_concurrent_jobs_with_limits() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let module = temp_dir.path().join("spin.wat");
+        std::fs::write(&module, SPIN_WAT).unwrap();
+        let (addr, server) =
+            start_server(&["--listen=127.0.0.1:0", "--max-concurrent-jobs=1"]).await;
+        // the only permit is held, so the jobs are queued until it's released.
+        let permit = server.permits.clone().acquire_owned().await.unwrap();
+
+        // the jobs have their own limits.
+        let module = module.to_str().unwrap();
+        let fuel_job = format!(r#"{{"module": {module:?}, "config": {{"limited_fuel": 10000}}}}"#);
+        let time_job = format!(r#"{{"module": {module:?}, "config": {{"run_time": 200}}}}"#);
+        let (status, first) = tcp_request(addr, "POST", "/jobs", &time_job).await;
+        assert_eq!(status, 202);
+        let (status, second) = tcp_request(addr, "POST", "/jobs", &fuel_job).await;
+        assert_eq!(status, 202);
+        for id in [&first["id"], &second["id"]] {
+            let (_, job) = tcp_request(addr, "GET", &format!("/jobs/{id}"), "").await;
+            assert_eq!(job["status"], "queued");
+        }
+        let (status, _) = tcp_request(addr, "DELETE", &format!("/jobs/{}", second["id"]), "").await;
+        assert_eq!(status, 409);
+        drop(permit);
+
+        let mut reports = Vec::new();
+        for id in [&first["id"], &second["id"]] {
+            loop {
+                let (_, job) = tcp_request(addr, "GET", &format!("/jobs/{id}"), "").await;
+                if job["status"] == "finished" {
+                    reports.push(job["report"].clone());
+                    break;
+                }
+                tokio::time::sleep(Duration::from_millis(50)).await;
+            }
+        }
+        assert_eq!(reports[0]["limit_hit"], "time");
+        assert_eq!(reports[1]["limit_hit"], "fuel");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serve_max_finished_jobs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let module = temp_dir.path().join("hello.wat");
+        std::fs::write(&module, HELLO_WAT).unwrap();
+        let (addr, _) = start_server(&["--listen=127.0.0.1:0", "--max-finished-jobs=2"]).await;
+
+        let job = format!(
+            r#"{{"module": {:?}, "wait": true}}"#,
+            module.to_str().unwrap()
+        );
+        let mut ids = Vec::new();
+        for _ in 0..3 {
+            let (status, job) = tcp_request(addr, "POST", "/jobs", &job).await;
+            assert_eq!(status, 200);
+            assert_eq!(job["status"], "finished");
+            ids.push(job["id"].as_u64().unwrap());
+        }
+        // the oldest finished job is removed.
+        let (_, list) = tcp_request(addr, "GET", "/jobs", "").await;
+        let listed: Vec<_> = list["jobs"]
+            .members()
+            .map(|j| j["id"].as_u64().unwrap())
+            .collect();
+        assert_eq!(listed, ids[1..]);
+        let (status, _) = tcp_request(addr, "GET", &format!("/jobs/{}", ids[0]), "").await;
+        assert_eq!(status, 404);
+    }
+
+    #[tokio::test]
+    async fn test_request_read_timeout() {
+        let mut server = JobServer::new(&serve_opts(&[])).unwrap();
+        server.read_timeout = Duration::from_millis(100);
+        let (mut client, stream) = tokio::io::duplex(1024);
+        tokio::spawn(Arc::new(server).handle_connection(stream));
+        // the request is never completed, the connection is closed after the timeout.
+        client.write_all(b"GET /jobs HTTP/1.1\r\n").await.unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 408 Request Timeout\r\n"));
+    }
+
+    #[cfg(unix)]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_serve_unix_socket() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let module = temp_dir.path().join("hello.wat");
+        std::fs::write(&module, HELLO_WAT).unwrap();
+        let socket = temp_dir.path().join("bls.sock");
+        let listen = format!("--listen=unix:{}", socket.display());
+        let opts = serve_opts(&[&listen]);
+        let server = Arc::new(JobServer::new(&opts).unwrap());
+        let listener = Listener::bind(&opts.listen).await.unwrap();
+        tokio::spawn(server.serve(listener));
+
+        let stream = tokio::net::UnixStream::connect(&socket).await.unwrap();
+        let job = format!(
+            r#"{{"module": {:?}, "stdin": "input", "wait": true}}"#,
+            module.to_str().unwrap()
+        );
+        let (status, job) = request(stream, "POST", "/jobs", &job).await;
+        assert_eq!(status, 200);
+        assert_eq!(job["report"]["stdout"], "hello\n");
+    }
+
+    #[tokio::test]
+    async fn test_read_request() {
+        let raw = b"POST /jobs HTTP/1.1\r\nContent-Length: 2\r\n\r\n{}";
+        let request = read_request(&mut &raw[..]).await.unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/jobs");
+        assert_eq!(request.body, b"{}");
+
+        let raw = b"POST /jobs HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n";
+        assert!(read_request(&mut &raw[..]).await.is_err());
+        let raw = b"GET /jobs HTTP/1.1\r\n";
+        assert!(read_request(&mut &raw[..]).await.is_err());
+    }
+}
@@ -2,9 +2,10 @@ use std::fs::File;
 
 use crate::{
     config::{Config, load_extract_from_car, replace_vars},
+    config_file::ConfigFormat,
     error::CliExitCode,
 };
-use anyhow::Result;
+use anyhow::{Result, bail};
 
 pub(crate) struct V86config {
     pub raw_config: Option<String>,
@@ -41,7 +42,10 @@ impl V86config {
 }
 
 pub(crate) fn load_v86conf_extract_from_car(f: File) -> Result<V86config, CliExitCode> {
-    let config = load_extract_from_car(f, |raw_json, root_suffix| {
+    let config = load_extract_from_car(f, |raw_json, format, root_suffix| {
+        if format != ConfigFormat::Json {
+            bail!("the v86 configure must be the config.json.");
+        }
         let mut cfg = V86config::from_data(raw_json.clone(), root_suffix.clone())?;
         cfg.raw_config = replace_vars(raw_json, root_suffix).ok();
         Ok(Config::V86config(cfg))
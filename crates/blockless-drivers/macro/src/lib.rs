@@ -30,6 +30,7 @@ pub fn linker_integration(args: TokenStream) -> proc_macro::TokenStream {
         .map(|(m, b)| quote!(+#target_path::#b::#m))
         .collect::<Vec<_>>();
     ctx.push(quote!(+#target_path::types::UserErrorConversion));
+    ctx.push(quote!(+wasi_common::HostCallAudit));
     let s = quote!(
         pub fn #method_name<T, U>(
             linker: &mut Linker<T>,
@@ -79,6 +80,13 @@ fn generate_func(
         1 => names::wasm_type(results[0]),
         _ => unimplemented!(),
     };
+    // the errno of the call in the audit trace.
+    let errno = match results.len() {
+        0 => quote!(|_| 0),
+        _ => quote!(|r| *r as i32),
+    };
+    let audit_name = format!("{module_name}.{func_name}");
+    let audit_args = audit_args(func, &arg_names);
     let abi_func = quote!( #target_path::#module_ident::#func_ident );
     let linker = quote!(
         linker.func_wrap_async(
@@ -86,19 +94,68 @@ fn generate_func(
             #func_name,
             move |mut caller: wiggle::wasmtime_crate::Caller<'_, T> ,(#( #arg_name_decls)*):(#(#arg_type_decls)*)| {
                 Box::new(async move {
-                    let mem = match caller.get_export("memory") {
-                        Some(wiggle::wasmtime_crate::Extern::Memory(m)) => m,
+                    // the shared memory is exported by the modules with the threads.
+                    let export = caller.get_export("memory");
+                    let (mut mem, data) = match &export {
+                        Some(wiggle::wasmtime_crate::Extern::Memory(m)) => {
+                            let (mem, data) = m.data_and_store_mut(&mut caller);
+                            (wiggle::GuestMemory::Unshared(mem), data)
+                        }
+                        Some(wiggle::wasmtime_crate::Extern::SharedMemory(m)) => {
+                            (wiggle::GuestMemory::Shared(m.data()), caller.data_mut())
+                        }
                         _ => {
                             wiggle::anyhow::bail!("missing required memory export");
                         }
                     };
-                    let (mem, data) = mem.data_and_store_mut(&mut caller);
-                    let mut mem = wiggle::GuestMemory::Unshared(mem);
                     let ctx = get_ctx(data);
-                    Ok(<#ret_ty>::from(#abi_func(ctx, &mut mem #(, #arg_names)*).await ?))
+                    let audit = wasi_common::HostCallAudit::audit_trace(ctx).map(|trace| {
+                        let module = wasi_common::HostCallAudit::audit_module(ctx);
+                        trace.call(module, #audit_name, vec![#(#audit_args),*])
+                    });
+                    let result = #abi_func(ctx, &mut mem #(, #arg_names)*).await;
+                    if let Some(audit) = audit {
+                        audit.finish(result.as_ref().ok().map(#errno));
+                    }
+                    Ok(<#ret_ty>::from(result?))
                 })
             },
         )?;
     );
     linker
 }
+
+/// the arguments of the func in the audit trace, the strings are read from the memory,
+/// the other arguments are recorded as the numbers, e.g. the handles and the pointers.
+fn audit_args(func: &witx::InterfaceFunc, arg_names: &[Ident]) -> Vec<proc_macro2::TokenStream> {
+    let mut args = Vec::new();
+    let mut wasm_args = arg_names.iter();
+    for param in func.params.iter() {
+        let name = param.name.as_str();
+        let ty: &witx::Type = &param.tref.type_();
+        match ty {
+            // the list is passed by the pointer and length pair.
+            witx::Type::List(elem) => {
+                let (Some(ptr), Some(len)) = (wasm_args.next(), wasm_args.next()) else {
+                    break;
+                };
+                let elem: &witx::Type = &elem.type_();
+                if matches!(elem, witx::Type::Builtin(witx::BuiltinType::Char)) {
+                    args.push(quote!((#name, wasi_common::AuditArg::guest_str(
+                        &mem,
+                        wiggle::GuestPtr::new((#ptr as u32, #len as u32)),
+                    ))));
+                } else {
+                    args.push(quote!((#name, wasi_common::AuditArg::Num(#len as i64))));
+                }
+            }
+            _ => {
+                let Some(arg) = wasm_args.next() else {
+                    break;
+                };
+                args.push(quote!((#name, wasi_common::AuditArg::Num(#arg as i64))));
+            }
+        }
+    }
+    args
+}
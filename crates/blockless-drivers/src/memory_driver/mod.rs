@@ -1,3 +1,5 @@
+use std::env;
+
 use crate::BlocklessMemoryErrorKind;
 
 pub async fn read(buf: &mut [u8], string: String) -> Result<u32, BlocklessMemoryErrorKind> {
@@ -11,3 +13,25 @@ pub async fn read(buf: &mut [u8], string: String) -> Result<u32, BlocklessMemory
 
     Ok(bytes.len() as u32)
 }
+
+/// the JSON object of the env vars listed by the `BLS_LIST_VARS` env var.
+pub fn env_vars_json() -> String {
+    // get the list of env_vars to load into the wasi assembly
+    // from the BLS_LIST_VARS env var
+    let env_var = match env::var_os("BLS_LIST_VARS") {
+        Some(v) => v.into_string().unwrap(),
+        None => "".to_string(),
+    };
+
+    let mut owned_string: String = "{".to_owned();
+    for s in env_var.split(";") {
+        let env_var = match env::var_os(s) {
+            Some(v) => v.into_string().unwrap(),
+            None => "".to_string(),
+        };
+        owned_string.push_str(&format!("\"{}\": \"{}\",", s, env_var));
+    }
+    owned_string.pop();
+    owned_string.push('}');
+    owned_string
+}
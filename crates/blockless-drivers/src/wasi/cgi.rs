@@ -53,15 +53,12 @@ impl blockless_cgi::BlocklessCgi for WasiCtx {
         memory: &mut GuestMemory<'_>,
         command_with_args: GuestPtr<str>,
     ) -> Result<types::CgiHandle, CgiErrorKind> {
-        let cmd: &str = memory
-            .as_str(command_with_args)
-            .map_err(|e| {
-                error!("command error: {}", e);
-                CgiErrorKind::InvalidParameter
-            })?
-            .unwrap();
+        let cmd = memory.as_cow_str(command_with_args).map_err(|e| {
+            error!("command error: {}", e);
+            CgiErrorKind::InvalidParameter
+        })?;
         let root_path = self.config_drivers_root_path_ref().unwrap();
-        command_and_exec(&root_path, cmd).await.map(|r| r.into())
+        command_and_exec(&root_path, &cmd).await.map(|r| r.into())
     }
 
     async fn cgi_list_exec(
@@ -131,15 +128,11 @@ impl blockless_cgi::BlocklessCgi for WasiCtx {
         buf: GuestPtr<u8>,
         buf_len: u32,
     ) -> Result<u32, CgiErrorKind> {
-        let buf = memory
-            .as_slice(buf.as_array(buf_len))
-            .map_err(|e| {
-                error!("guest stdin write buf error: {}", e);
-                CgiErrorKind::InvalidParameter
-            })?
-            .unwrap();
-        let buf = unsafe { std::slice::from_raw_parts(buf.as_ptr(), buf_len as _) };
-        child_stdin_write(handle.into(), buf).await
+        let buf = memory.as_cow(buf.as_array(buf_len)).map_err(|e| {
+            error!("guest stdin write buf error: {}", e);
+            CgiErrorKind::InvalidParameter
+        })?;
+        child_stdin_write(handle.into(), &buf).await
     }
 
     async fn cgi_close(
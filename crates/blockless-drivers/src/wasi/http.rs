@@ -103,27 +103,21 @@ impl blockless_http::BlocklessHttp for WasiCtx {
         url: GuestPtr<str>,
         opts: GuestPtr<str>,
     ) -> Result<(types::HttpHandle, types::CodeType), HttpErrorKind> {
-        let url: &str = memory
-            .as_str(url)
-            .map_err(|e| {
-                error!("guest url error: {}", e);
-                HttpErrorKind::Utf8Error
-            })?
-            .unwrap();
+        let url = memory.as_cow_str(url).map_err(|e| {
+            error!("guest url error: {}", e);
+            HttpErrorKind::Utf8Error
+        })?;
 
-        let url_ = Url::from_str(url).map_err(|_| HttpErrorKind::InvalidUrl)?;
+        let url_ = Url::from_str(&url).map_err(|_| HttpErrorKind::InvalidUrl)?;
         if !self.check_url_permissions(&url_, "http_req") {
             error!("Permission Deny");
             return Err(HttpErrorKind::PermissionDeny);
         }
-        let opts: &str = memory
-            .as_str(opts)
-            .map_err(|e| {
-                error!("guest options error: {}", e);
-                HttpErrorKind::Utf8Error
-            })?
-            .unwrap();
-        let (fd, code) = http_driver::http_req(url, opts).await?;
+        let opts = memory.as_cow_str(opts).map_err(|e| {
+            error!("guest options error: {}", e);
+            HttpErrorKind::Utf8Error
+        })?;
+        let (fd, code) = http_driver::http_req(&url, &opts).await?;
         Ok((types::HttpHandle::from(fd), types::CodeType::from(code)))
     }
 
@@ -143,15 +137,12 @@ impl blockless_http::BlocklessHttp for WasiCtx {
         buf: GuestPtr<u8>,
         buf_len: u32,
     ) -> Result<u32, HttpErrorKind> {
-        let head: &str = memory
-            .as_str(head)
-            .map_err(|e| {
-                error!("guest head error: {}", e);
-                HttpErrorKind::Utf8Error
-            })?
-            .unwrap();
+        let head = memory.as_cow_str(head).map_err(|e| {
+            error!("guest head error: {}", e);
+            HttpErrorKind::Utf8Error
+        })?;
         let mut dest_buf = vec![0; buf_len as _];
-        let rs = http_driver::http_read_head(handle.into(), head, &mut dest_buf[..]).await?;
+        let rs = http_driver::http_read_head(handle.into(), &head, &mut dest_buf[..]).await?;
         memory
             .copy_from_slice(&dest_buf[0..rs as _], buf.as_array(rs))
             .map_err(|_| HttpErrorKind::MemoryAccessError)?;
@@ -52,14 +52,11 @@ impl blockless_ipfs::BlocklessIpfs for WasiCtx {
         memory: &mut GuestMemory<'_>,
         params: GuestPtr<str>,
     ) -> Result<(types::IpfsHandle, types::StatusCode), IpfsErrorKind> {
-        let params = memory
-            .as_str(params)
-            .map_err(|e| {
-                error!("guest url error: {}", e);
-                IpfsErrorKind::Utf8Error
-            })?
-            .unwrap();
-        let (status, fd) = ipfs_driver::command(params).await?;
+        let params = memory.as_cow_str(params).map_err(|e| {
+            error!("guest url error: {}", e);
+            IpfsErrorKind::Utf8Error
+        })?;
+        let (status, fd) = ipfs_driver::command(&params).await?;
         Ok((types::IpfsHandle::from(fd), types::StatusCode::from(status)))
     }
 
@@ -96,14 +93,11 @@ impl blockless_ipfs::BlocklessIpfs for WasiCtx {
         buf: GuestPtr<u8>,
         buf_len: u32,
     ) -> Result<u32, IpfsErrorKind> {
-        let buf = memory
-            .as_slice(buf.as_array(buf_len))
-            .map_err(|e| {
-                error!("guest url error: {}", e);
-                IpfsErrorKind::InvalidParameter
-            })?
-            .unwrap();
-        let rs = ipfs_driver::write_body(handle.into(), buf).await?;
+        let buf = memory.as_cow(buf.as_array(buf_len)).map_err(|e| {
+            error!("guest url error: {}", e);
+            IpfsErrorKind::InvalidParameter
+        })?;
+        let rs = ipfs_driver::write_body(handle.into(), &buf).await?;
         Ok(rs)
     }
 }
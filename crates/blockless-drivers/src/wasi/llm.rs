@@ -54,15 +54,12 @@ impl blockless_llm::BlocklessLlm for WasiCtx {
         handle: GuestPtr<types::LlmHandle>,
         model: GuestPtr<str>,
     ) -> Result<(), LlmErrorKind> {
-        let model: &str = memory
-            .as_str(model)
-            .map_err(|e| {
-                error!("guest model error: {}", e);
-                LlmErrorKind::Utf8Error
-            })?
-            .unwrap();
+        let model = memory.as_cow_str(model).map_err(|e| {
+            error!("guest model error: {}", e);
+            LlmErrorKind::Utf8Error
+        })?;
         // Use a closure that captures self to check URL permissions
-        let fd = llm_driver::llm_set_model(model, |url: &url::Url| -> bool {
+        let fd = llm_driver::llm_set_model(&model, |url: &url::Url| -> bool {
             self.check_url_permissions(url, "llm_set_model")
         })
         .await?;
@@ -99,13 +96,10 @@ impl blockless_llm::BlocklessLlm for WasiCtx {
         handle: types::LlmHandle,
         options: GuestPtr<str>,
     ) -> Result<(), LlmErrorKind> {
-        let options: &str = memory
-            .as_str(options)
-            .map_err(|e| {
-                error!("guest options error: {}", e);
-                LlmErrorKind::Utf8Error
-            })?
-            .unwrap();
+        let options = memory.as_cow_str(options).map_err(|e| {
+            error!("guest options error: {}", e);
+            LlmErrorKind::Utf8Error
+        })?;
         llm_driver::llm_set_options(handle, options.as_bytes()).await?;
         return Ok(());
     }
@@ -135,14 +129,11 @@ impl blockless_llm::BlocklessLlm for WasiCtx {
         handle: types::LlmHandle,
         prompt: GuestPtr<str>,
     ) -> Result<(), LlmErrorKind> {
-        let prompt: &str = memory
-            .as_str(prompt)
-            .map_err(|e| {
-                error!("guest prompt error: {}", e);
-                LlmErrorKind::Utf8Error
-            })?
-            .unwrap();
-        llm_driver::llm_prompt(handle, prompt).await?;
+        let prompt = memory.as_cow_str(prompt).map_err(|e| {
+            error!("guest prompt error: {}", e);
+            LlmErrorKind::Utf8Error
+        })?;
+        llm_driver::llm_prompt(handle, &prompt).await?;
         Ok(())
     }
 
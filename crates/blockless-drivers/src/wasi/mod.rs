@@ -99,20 +99,18 @@ impl blockless_drivers::BlocklessDrivers for WasiCtx {
         opts: GuestPtr<str>,
     ) -> Result<types::Fd, ErrorKind> {
         let path = memory
-            .as_str(path)
-            .map_err(|_| ErrorKind::DriverBadParams)?
-            .unwrap();
+            .as_cow_str(path)
+            .map_err(|_| ErrorKind::DriverBadParams)?;
         let opts = memory
-            .as_str(opts)
-            .map_err(|_| ErrorKind::DriverBadParams)?
-            .unwrap();
-        let drv: Arc<dyn Driver + Sync + Send> = match DriverConetxt::find_driver(path) {
+            .as_cow_str(opts)
+            .map_err(|_| ErrorKind::DriverBadParams)?;
+        let drv: Arc<dyn Driver + Sync + Send> = match DriverConetxt::find_driver(&path) {
             Some(d) => d,
             None => return Err(ErrorKind::DriverNotFound),
         };
         let mode = FileAccessMode::READ | FileAccessMode::WRITE;
         match drv
-            .open(path, opts)
+            .open(&path, &opts)
             .await
             .map(|f| Arc::new(FileEntry::new(f, mode)))
         {
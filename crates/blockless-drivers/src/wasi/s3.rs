@@ -53,14 +53,11 @@ impl blockless_s3::BlocklessS3 for WasiCtx {
         cmd: u16,
         param: GuestPtr<str>,
     ) -> Result<types::S3Handle, S3ErrorKind> {
-        let params = memory
-            .as_str(param)
-            .map_err(|e| {
-                error!("guest url error: {}", e);
-                S3ErrorKind::Utf8Error
-            })?
-            .unwrap();
-        let rs = s3_driver::bucket_command(cmd, params).await?;
+        let params = memory.as_cow_str(param).map_err(|e| {
+            error!("guest url error: {}", e);
+            S3ErrorKind::Utf8Error
+        })?;
+        let rs = s3_driver::bucket_command(cmd, &params).await?;
         Ok(rs.into())
     }
 
@@ -71,22 +68,16 @@ impl blockless_s3::BlocklessS3 for WasiCtx {
         buf: GuestPtr<u8>,
         buf_len: u32,
     ) -> Result<(), S3ErrorKind> {
-        let cfg = memory
-            .as_str(cfg)
-            .map_err(|e| {
-                error!("guest url error: {}", e);
-                S3ErrorKind::Utf8Error
-            })?
-            .unwrap();
+        let cfg = memory.as_cow_str(cfg).map_err(|e| {
+            error!("guest url error: {}", e);
+            S3ErrorKind::Utf8Error
+        })?;
 
-        let params = memory
-            .as_slice(buf.as_array(buf_len))
-            .map_err(|e| {
-                error!("guest url error: {}", e);
-                S3ErrorKind::InvalidParameter
-            })?
-            .unwrap();
-        s3_driver::bucket_put_object(cfg, params).await
+        let params = memory.as_cow(buf.as_array(buf_len)).map_err(|e| {
+            error!("guest url error: {}", e);
+            S3ErrorKind::InvalidParameter
+        })?;
+        s3_driver::bucket_put_object(&cfg, &params).await
     }
 
     async fn s3_read(
@@ -81,11 +81,10 @@ impl blockless_socket::BlocklessSocket for WasiCtx {
         bind: GuestPtr<str>,
     ) -> Result<types::SocketHandle, BlocklessSocketErrorKind> {
         let addr = memory
-            .as_str(bind)
-            .map_err(|_| BlocklessSocketErrorKind::ParameterError)?
-            .unwrap();
+            .as_cow_str(bind)
+            .map_err(|_| BlocklessSocketErrorKind::ParameterError)?;
         let mode = FileAccessMode::READ | FileAccessMode::WRITE;
-        match tcp_bind(addr)
+        match tcp_bind(&addr)
             .await
             .map(|f| Arc::new(FileEntry::new(f, mode)))
         {
@@ -104,11 +103,10 @@ impl blockless_socket::BlocklessSocket for WasiCtx {
         target: GuestPtr<str>,
     ) -> Result<types::SocketHandle, BlocklessSocketErrorKind> {
         let addr = memory
-            .as_str(target)
-            .map_err(|_| BlocklessSocketErrorKind::ParameterError)?
-            .unwrap();
+            .as_cow_str(target)
+            .map_err(|_| BlocklessSocketErrorKind::ParameterError)?;
         let mode = FileAccessMode::READ | FileAccessMode::WRITE;
-        match tcp_connect(addr)
+        match tcp_connect(&addr)
             .await
             .map(|f| Arc::new(FileEntry::new(f, mode)))
         {
@@ -0,0 +1,79 @@
+use super::blockless::extensions::cgi::{self, CgiError, CgiHandle};
+use super::{BlocklessWitCtx, read_buf};
+use crate::CgiErrorKind;
+use crate::cgi_driver::{
+    self, cgi_directory_list_exec, cgi_directory_list_read, child_stderr_read, child_stdin_write,
+    child_stdout_read, command_and_exec,
+};
+
+impl From<CgiErrorKind> for CgiError {
+    fn from(e: CgiErrorKind) -> CgiError {
+        match e {
+            CgiErrorKind::InvalidHandle => CgiError::InvalidHandle,
+            CgiErrorKind::RuntimeError => CgiError::RuntimeError,
+            CgiErrorKind::InvalidParameter => CgiError::InvalidParameter,
+            CgiErrorKind::InvalidExtension => CgiError::InvalidExtension,
+        }
+    }
+}
+
+impl BlocklessWitCtx {
+    fn cgi_root_path(&self) -> Result<&str, CgiError> {
+        self.drivers_root_path
+            .as_deref()
+            .ok_or(CgiError::RuntimeError)
+    }
+}
+
+impl cgi::Host for BlocklessWitCtx {
+    async fn cgi_list_exec(&mut self) -> Result<CgiHandle, CgiError> {
+        let root_path = self.cgi_root_path()?;
+        Ok(cgi_directory_list_exec(root_path).await?)
+    }
+
+    async fn cgi_list_read(
+        &mut self,
+        handle: CgiHandle,
+        max_len: u32,
+    ) -> Result<Vec<u8>, CgiError> {
+        let mut buf = read_buf(max_len);
+        let rs = cgi_directory_list_read(handle, &mut buf).await?;
+        buf.truncate(rs as _);
+        Ok(buf)
+    }
+
+    async fn cgi_open(&mut self, param: String) -> Result<CgiHandle, CgiError> {
+        let root_path = self.cgi_root_path()?;
+        Ok(command_and_exec(root_path, &param).await?)
+    }
+
+    async fn cgi_stdout_read(
+        &mut self,
+        handle: CgiHandle,
+        max_len: u32,
+    ) -> Result<Vec<u8>, CgiError> {
+        let mut buf = read_buf(max_len);
+        let rs = child_stdout_read(handle, &mut buf).await?;
+        buf.truncate(rs as _);
+        Ok(buf)
+    }
+
+    async fn cgi_stderr_read(
+        &mut self,
+        handle: CgiHandle,
+        max_len: u32,
+    ) -> Result<Vec<u8>, CgiError> {
+        let mut buf = read_buf(max_len);
+        let rs = child_stderr_read(handle, &mut buf).await?;
+        buf.truncate(rs as _);
+        Ok(buf)
+    }
+
+    async fn cgi_stdin_write(&mut self, handle: CgiHandle, buf: Vec<u8>) -> Result<u32, CgiError> {
+        Ok(child_stdin_write(handle, &buf).await?)
+    }
+
+    async fn cgi_close(&mut self, handle: CgiHandle) -> Result<(), CgiError> {
+        Ok(cgi_driver::close(handle)?)
+    }
+}
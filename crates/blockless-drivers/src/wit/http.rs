@@ -0,0 +1,71 @@
+use log::error;
+use url::Url;
+
+use super::blockless::extensions::http::{self, CodeType, HttpError, ResponseHandle};
+use super::{BlocklessWitCtx, read_buf};
+use crate::{HttpErrorKind, http_driver};
+
+impl From<HttpErrorKind> for HttpError {
+    fn from(e: HttpErrorKind) -> HttpError {
+        match e {
+            HttpErrorKind::InvalidHandle => HttpError::InvalidHandle,
+            HttpErrorKind::MemoryAccessError => HttpError::MemoryAccessError,
+            HttpErrorKind::BufferTooSmall => HttpError::BufferTooSmall,
+            HttpErrorKind::HeaderNotFound => HttpError::HeaderNotFound,
+            HttpErrorKind::Utf8Error => HttpError::Utf8Error,
+            HttpErrorKind::DestinationNotAllowed => HttpError::DestinationNotAllowed,
+            HttpErrorKind::InvalidMethod => HttpError::InvalidMethod,
+            HttpErrorKind::InvalidEncoding => HttpError::InvalidEncoding,
+            HttpErrorKind::InvalidUrl => HttpError::InvalidUrl,
+            HttpErrorKind::RequestError => HttpError::RequestError,
+            HttpErrorKind::RuntimeError => HttpError::RuntimeError,
+            HttpErrorKind::TooManySessions => HttpError::TooManySessions,
+            HttpErrorKind::InvalidDriver => HttpError::InvalidDriver,
+            HttpErrorKind::PermissionDeny => HttpError::PermissionDeny,
+            HttpErrorKind::HeadersValidationError => HttpError::HeadersValidationError,
+        }
+    }
+}
+
+impl http::Host for BlocklessWitCtx {
+    async fn http_req(
+        &mut self,
+        url: String,
+        opts: String,
+    ) -> Result<(ResponseHandle, CodeType), HttpError> {
+        let url_ = Url::parse(&url).map_err(|_| HttpError::InvalidUrl)?;
+        if !self.check_url_permissions(&url_, "http_req") {
+            error!("Permission Deny");
+            return Err(HttpError::PermissionDeny);
+        }
+        let (fd, code) = http_driver::http_req(&url, &opts).await?;
+        Ok((fd, code))
+    }
+
+    async fn http_close(&mut self, handle: ResponseHandle) -> Result<(), HttpError> {
+        Ok(http_driver::http_close(handle).await?)
+    }
+
+    async fn http_read_header(
+        &mut self,
+        handle: ResponseHandle,
+        header_name: String,
+        max_len: u32,
+    ) -> Result<Vec<u8>, HttpError> {
+        let mut buf = read_buf(max_len);
+        let rs = http_driver::http_read_head(handle, &header_name, &mut buf).await?;
+        buf.truncate(rs as _);
+        Ok(buf)
+    }
+
+    async fn http_read_body(
+        &mut self,
+        handle: ResponseHandle,
+        max_len: u32,
+    ) -> Result<Vec<u8>, HttpError> {
+        let mut buf = read_buf(max_len);
+        let rs = http_driver::http_read_body(handle, &mut buf).await?;
+        buf.truncate(rs as _);
+        Ok(buf)
+    }
+}
@@ -0,0 +1,44 @@
+use super::blockless::extensions::ipfs::{self, IpfsError, IpfsHandle, StatusCode};
+use super::{BlocklessWitCtx, read_buf};
+use crate::{IpfsErrorKind, ipfs_driver};
+
+impl From<IpfsErrorKind> for IpfsError {
+    fn from(e: IpfsErrorKind) -> IpfsError {
+        match e {
+            IpfsErrorKind::InvalidHandle => IpfsError::InvalidHandle,
+            IpfsErrorKind::Utf8Error => IpfsError::Utf8Error,
+            IpfsErrorKind::InvalidMethod => IpfsError::InvalidMethod,
+            IpfsErrorKind::InvalidEncoding => IpfsError::InvalidEncoding,
+            IpfsErrorKind::InvalidParameter => IpfsError::InvalidParameter,
+            IpfsErrorKind::RequestError => IpfsError::RequestError,
+            IpfsErrorKind::RuntimeError => IpfsError::RuntimeError,
+            IpfsErrorKind::TooManySessions => IpfsError::TooManySessions,
+            IpfsErrorKind::PermissionDeny => IpfsError::PermissionDeny,
+        }
+    }
+}
+
+impl ipfs::Host for BlocklessWitCtx {
+    async fn ipfs_command(
+        &mut self,
+        params: String,
+    ) -> Result<(IpfsHandle, StatusCode), IpfsError> {
+        let (status, fd) = ipfs_driver::command(&params).await?;
+        Ok((fd, status as _))
+    }
+
+    async fn ipfs_read(&mut self, handle: IpfsHandle, max_len: u32) -> Result<Vec<u8>, IpfsError> {
+        let mut buf = read_buf(max_len);
+        let rs = ipfs_driver::read_body(handle, &mut buf).await?;
+        buf.truncate(rs as _);
+        Ok(buf)
+    }
+
+    async fn ipfs_close(&mut self, handle: IpfsHandle) -> Result<(), IpfsError> {
+        Ok(ipfs_driver::close(handle).await?)
+    }
+
+    async fn ipfs_write(&mut self, handle: IpfsHandle, body: Vec<u8>) -> Result<u32, IpfsError> {
+        Ok(ipfs_driver::write_body(handle, &body).await?)
+    }
+}
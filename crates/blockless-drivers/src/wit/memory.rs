@@ -0,0 +1,13 @@
+use super::BlocklessWitCtx;
+use super::blockless::extensions::memory::{self, MemoryError};
+use crate::memory_driver;
+
+impl memory::Host for BlocklessWitCtx {
+    async fn memory_read(&mut self) -> Result<String, MemoryError> {
+        Ok(self.stdin.clone())
+    }
+
+    async fn env_var_read(&mut self) -> Result<String, MemoryError> {
+        Ok(memory_driver::env_vars_json())
+    }
+}
@@ -0,0 +1,181 @@
+//! The blockless extensions for the components, the interfaces are defined in the `wit`
+//! directory and implemented on the same drivers as the witx extensions of core modules.
+mod cgi;
+mod http;
+mod ipfs;
+mod llm;
+mod memory;
+mod s3;
+mod socket;
+
+use socket::SocketTable;
+use url::Url;
+use wasi_common::BlsRuntimePermissionsContainer;
+use wasmtime::component::Linker;
+
+wasmtime::component::bindgen!({
+    path: "wit",
+    world: "blockless:extensions/host",
+    async: true,
+});
+
+pub use blockless::extensions::*;
+
+/// the max bytes read by one call, the guest reads the rest by the next calls.
+const MAX_READ_LEN: u32 = 64 * 1024;
+
+/// the buffer of the read, the `max_len` of the guest is capped by `MAX_READ_LEN`.
+fn read_buf(max_len: u32) -> Vec<u8> {
+    vec![0; max_len.min(MAX_READ_LEN) as _]
+}
+
+/// The host context of the blockless extensions for the components.
+pub struct BlocklessWitCtx {
+    perms: BlsRuntimePermissionsContainer,
+    // the fixed stdin of the configure, read by `memory-read`.
+    stdin: String,
+    drivers_root_path: Option<String>,
+    sockets: SocketTable,
+}
+
+impl BlocklessWitCtx {
+    pub fn new(
+        perms: BlsRuntimePermissionsContainer,
+        stdin: String,
+        drivers_root_path: Option<String>,
+    ) -> Self {
+        Self {
+            perms,
+            stdin,
+            drivers_root_path,
+            sockets: SocketTable::default(),
+        }
+    }
+
+    fn check_url_permissions(&self, url: &Url, api_name: &str) -> bool {
+        self.perms.check_net_url(url, api_name).is_ok()
+    }
+
+    /// check the permission of the address like `host:port`.
+    fn check_addr_permissions(&mut self, addr: &str, api_name: &str) -> bool {
+        let Some((host, port)) = addr.rsplit_once(':') else {
+            return false;
+        };
+        let Ok(port) = port.parse::<u16>() else {
+            return false;
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        self.perms.check_net(&(host, Some(port)), api_name).is_ok()
+    }
+}
+
+/// add the blockless extensions to the linker of components,
+/// only the interfaces accepted by `allowed` are added, e.g. `http`.
+pub fn add_to_linker<T: Send>(
+    linker: &mut Linker<T>,
+    allowed: impl Fn(&str) -> bool,
+    get: impl Fn(&mut T) -> &mut BlocklessWitCtx + Send + Sync + Copy + 'static,
+) -> anyhow::Result<()> {
+    macro_rules! add_interface {
+        ($($name:ident),*) => {
+            $(
+                if allowed(stringify!($name)) {
+                    blockless::extensions::$name::add_to_linker(linker, get)?;
+                }
+            )*
+        };
+    }
+    add_interface!(http, ipfs, s3, memory, cgi, socket, llm);
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_check_addr_permissions() {
+        let perms = BlsRuntimePermissionsContainer::new_with_env_cwd(None);
+        perms.allow_all();
+        let mut ctx = BlocklessWitCtx::new(perms, String::new(), None);
+        assert!(ctx.check_addr_permissions("127.0.0.1:8080", "test"));
+        assert!(ctx.check_addr_permissions("[::1]:8080", "test"));
+        assert!(!ctx.check_addr_permissions("127.0.0.1", "test"));
+        assert!(!ctx.check_addr_permissions("127.0.0.1:port", "test"));
+    }
+
+    #[test]
+    fn test_read_buf() {
+        assert_eq!(read_buf(16).len(), 16);
+        assert_eq!(read_buf(u32::MAX).len(), MAX_READ_LEN as usize);
+    }
+
+    #[tokio::test]
+    async fn test_component_memory_read() {
+        // the component calls `memory-read` and returns the length of the stdin.
+        let code = r#"
+        (component
+            (type $memory (instance
+                (type $error (enum "invalid-handle" "runtime-error" "invalid-parameter"))
+                (export "memory-error" (type $memory-error (eq $error)))
+                (type $read-result (result string (error $memory-error)))
+                (type $read (func (result $read-result)))
+                (export "memory-read" (func (type $read)))
+            ))
+            (import "blockless:extensions/memory@0.1.0" (instance $memory (type $memory)))
+            (alias export $memory "memory-read" (func $memory-read))
+            (core module $libc
+                (memory (export "memory") 1)
+                (global $heap (mut i32) (i32.const 1024))
+                (func (export "realloc") (param i32 i32 i32 i32) (result i32)
+                    (local $ptr i32)
+                    (local.set $ptr (global.get $heap))
+                    (global.set $heap (i32.add (global.get $heap) (local.get 3)))
+                    (local.get $ptr)
+                )
+            )
+            (core instance $libc (instantiate $libc))
+            (core func $memory-read (canon lower (func $memory-read)
+                (memory $libc "memory") (realloc (func $libc "realloc"))))
+            (core module $main
+                (import "libc" "memory" (memory 1))
+                (import "memory" "memory-read" (func $memory-read (param i32)))
+                (func (export "read-len") (result i32)
+                    (call $memory-read (i32.const 0))
+                    (if (i32.load8_u (i32.const 0)) (then unreachable))
+                    (i32.load (i32.const 8))
+                )
+            )
+            (core instance $main (instantiate $main
+                (with "libc" (instance $libc))
+                (with "memory" (instance (export "memory-read" (func $memory-read))))
+            ))
+            (func (export "read-len") (result u32) (canon lift (core func $main "read-len")))
+        )
+        "#;
+        let mut config = wasmtime::Config::new();
+        config.async_support(true);
+        let engine = wasmtime::Engine::new(&config).unwrap();
+        let component = wasmtime::component::Component::new(&engine, code).unwrap();
+        let mut linker = Linker::new(&engine);
+        add_to_linker(
+            &mut linker,
+            |name| name == "memory",
+            |ctx: &mut BlocklessWitCtx| ctx,
+        )
+        .unwrap();
+        let perms = BlsRuntimePermissionsContainer::new_with_env_cwd(None);
+        let ctx = BlocklessWitCtx::new(perms, "hello".into(), None);
+        let mut store = wasmtime::Store::new(&engine, ctx);
+        let instance = linker
+            .instantiate_async(&mut store, &component)
+            .await
+            .unwrap();
+        let read_len = instance
+            .get_typed_func::<(), (u32,)>(&mut store, "read-len")
+            .unwrap();
+        let (len,) = read_len.call_async(&mut store, ()).await.unwrap();
+        read_len.post_return_async(&mut store).await.unwrap();
+        assert_eq!(len, 5);
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::io::ErrorKind;
+
+use log::error;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net;
+use wasmtime::component::Resource;
+
+use super::blockless::extensions::socket::{self, SocketError, TcpListener, TcpStream};
+use super::{BlocklessWitCtx, read_buf};
+
+/// the sockets of the component, the resources refer to the sockets by the key.
+#[derive(Default)]
+pub(super) struct SocketTable {
+    next_key: u32,
+    streams: HashMap<u32, net::TcpStream>,
+    listeners: HashMap<u32, net::TcpListener>,
+}
+
+impl SocketTable {
+    fn next_key(&mut self) -> u32 {
+        self.next_key += 1;
+        self.next_key
+    }
+
+    fn push_stream(&mut self, stream: net::TcpStream) -> Resource<TcpStream> {
+        let key = self.next_key();
+        self.streams.insert(key, stream);
+        Resource::new_own(key)
+    }
+
+    fn stream(&mut self, res: &Resource<TcpStream>) -> Result<&mut net::TcpStream, SocketError> {
+        self.streams
+            .get_mut(&res.rep())
+            .ok_or(SocketError::ParameterError)
+    }
+}
+
+fn socket_error(e: std::io::Error) -> SocketError {
+    match e.kind() {
+        ErrorKind::ConnectionRefused => SocketError::ConnectionRefused,
+        ErrorKind::AddrInUse => SocketError::AddressInUse,
+        ErrorKind::InvalidInput => SocketError::ParameterError,
+        _ => SocketError::ConnectionReset,
+    }
+}
+
+impl socket::HostTcpStream for BlocklessWitCtx {
+    async fn read(
+        &mut self,
+        self_: Resource<TcpStream>,
+        max_len: u32,
+    ) -> Result<Vec<u8>, SocketError> {
+        let stream = self.sockets.stream(&self_)?;
+        let mut buf = read_buf(max_len);
+        let n = stream.read(&mut buf).await.map_err(socket_error)?;
+        buf.truncate(n);
+        Ok(buf)
+    }
+
+    async fn write(
+        &mut self,
+        self_: Resource<TcpStream>,
+        buf: Vec<u8>,
+    ) -> Result<u32, SocketError> {
+        let stream = self.sockets.stream(&self_)?;
+        let n = stream.write(&buf).await.map_err(socket_error)?;
+        Ok(n as _)
+    }
+
+    async fn drop(&mut self, rep: Resource<TcpStream>) -> wasmtime::Result<()> {
+        self.sockets.streams.remove(&rep.rep());
+        Ok(())
+    }
+}
+
+impl socket::HostTcpListener for BlocklessWitCtx {
+    async fn accept(
+        &mut self,
+        self_: Resource<TcpListener>,
+    ) -> Result<Resource<TcpStream>, SocketError> {
+        let listener = self
+            .sockets
+            .listeners
+            .get(&self_.rep())
+            .ok_or(SocketError::ParameterError)?;
+        let (stream, _) = listener.accept().await.map_err(socket_error)?;
+        Ok(self.sockets.push_stream(stream))
+    }
+
+    async fn drop(&mut self, rep: Resource<TcpListener>) -> wasmtime::Result<()> {
+        self.sockets.listeners.remove(&rep.rep());
+        Ok(())
+    }
+}
+
+impl socket::Host for BlocklessWitCtx {
+    async fn create_tcp_bind_socket(
+        &mut self,
+        target: String,
+    ) -> Result<Resource<TcpListener>, SocketError> {
+        if !self.check_addr_permissions(&target, "create_tcp_bind_socket") {
+            error!("Permission Deny: {target}");
+            return Err(SocketError::ParameterError);
+        }
+        let listener = net::TcpListener::bind(&target).await.map_err(|e| {
+            error!("error bind in driver {}: {}", target, e);
+            socket_error(e)
+        })?;
+        let key = self.sockets.next_key();
+        self.sockets.listeners.insert(key, listener);
+        Ok(Resource::new_own(key))
+    }
+
+    async fn tcp_connect(&mut self, target: String) -> Result<Resource<TcpStream>, SocketError> {
+        if !self.check_addr_permissions(&target, "tcp_connect") {
+            error!("Permission Deny: {target}");
+            return Err(SocketError::ConnectionRefused);
+        }
+        let stream = net::TcpStream::connect(&target).await.map_err(|e| {
+            error!("error connect in driver {}: {}", target, e);
+            socket_error(e)
+        })?;
+        Ok(self.sockets.push_stream(stream))
+    }
+}
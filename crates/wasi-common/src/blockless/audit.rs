@@ -0,0 +1,222 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use json::JsonValue;
+use wiggle::{GuestMemory, GuestPtr};
+
+/// the value of the redacted arguments.
+pub const AUDIT_REDACTED: &str = "<redacted>";
+
+/// The configure of the host-call audit trace.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AuditConfig {
+    /// the file of the trace in JSON Lines, `-` means stderr.
+    pub path: String,
+    /// the argument names redacted in the trace, e.g. `url`, `*` redacts all arguments.
+    pub redact: Vec<String>,
+}
+
+impl AuditConfig {
+    pub fn new(path: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            redact: Vec::new(),
+        }
+    }
+
+    #[inline(always)]
+    fn is_redacted(&self, name: &str) -> bool {
+        self.redact.iter().any(|r| r == "*" || r == name)
+    }
+}
+
+/// The argument of the host call in the trace.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AuditArg {
+    Num(i64),
+    Str(String),
+}
+
+impl AuditArg {
+    /// read the string argument from the guest memory, the invalid string is
+    /// recorded as the pointer.
+    pub fn guest_str(memory: &GuestMemory<'_>, ptr: GuestPtr<str>) -> Self {
+        match memory.as_cow_str(ptr) {
+            Ok(s) => AuditArg::Str(s.into_owned()),
+            Err(_) => AuditArg::Num(ptr.offset().0 as i64),
+        }
+    }
+}
+
+impl From<AuditArg> for JsonValue {
+    fn from(arg: AuditArg) -> Self {
+        match arg {
+            AuditArg::Num(n) => n.into(),
+            AuditArg::Str(s) => s.into(),
+        }
+    }
+}
+
+/// The host-call audit trace, every blockless extension call and permission-checked
+/// wasi call of the run is written as a JSON line.
+pub struct AuditTrace {
+    config: AuditConfig,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl AuditTrace {
+    pub fn new(config: AuditConfig, writer: Box<dyn Write + Send>) -> Self {
+        Self {
+            config,
+            writer: Mutex::new(writer),
+        }
+    }
+
+    /// open the trace file of the configure.
+    pub fn open(config: &AuditConfig) -> anyhow::Result<Self> {
+        let writer: Box<dyn Write + Send> = match config.path.as_str() {
+            "-" => Box::new(io::stderr()),
+            path => {
+                let file = File::create(path)
+                    .with_context(|| format!("failed to create the audit trace `{path}`"))?;
+                Box::new(BufWriter::new(file))
+            }
+        };
+        Ok(Self::new(config.clone(), writer))
+    }
+
+    /// start the audit of the host call from the `module`.
+    pub fn call(
+        self: &Arc<Self>,
+        module: Option<Arc<str>>,
+        name: &'static str,
+        args: Vec<(&'static str, AuditArg)>,
+    ) -> AuditCall {
+        AuditCall {
+            trace: self.clone(),
+            module,
+            name,
+            args,
+            started: Instant::now(),
+        }
+    }
+
+    fn record(&self, call: AuditCall, errno: Option<i32>) {
+        let mut obj = json::object::Object::new();
+        let ts = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        obj["ts"] = (ts.as_millis() as u64).into();
+        obj["module"] = call.module.as_deref().into();
+        obj["call"] = call.name.into();
+        let mut args = json::object::Object::new();
+        for (name, arg) in call.args {
+            if self.config.is_redacted(name) {
+                args[name] = AUDIT_REDACTED.into();
+            } else {
+                args[name] = arg.into();
+            }
+        }
+        obj["args"] = args.into();
+        // the trap has no errno.
+        obj["errno"] = errno.into();
+        obj["duration_us"] = (call.started.elapsed().as_micros() as u64).into();
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(e) = writeln!(writer, "{}", obj.dump()) {
+            log::error!("failed to write the audit trace: {e}");
+        }
+    }
+
+    pub fn flush(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+}
+
+/// The host call in progress, it's recorded when finished.
+pub struct AuditCall {
+    trace: Arc<AuditTrace>,
+    module: Option<Arc<str>>,
+    name: &'static str,
+    args: Vec<(&'static str, AuditArg)>,
+    started: Instant,
+}
+
+impl AuditCall {
+    /// finish the call with the errno, `None` if the call is trapped.
+    pub fn finish(self, errno: Option<i32>) {
+        let trace = self.trace.clone();
+        trace.record(self, errno);
+    }
+}
+
+/// The host context with the audit trace, the host calls of the extensions
+/// generated by `linker_integration!` are recorded by the trace.
+pub trait HostCallAudit {
+    fn audit_trace(&self) -> Option<Arc<AuditTrace>>;
+
+    /// the module calling the host, it's from the store of the caller.
+    fn audit_module(&self) -> Option<Arc<str>>;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuf(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuf {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_audit_record() {
+        let buf = SharedBuf::default();
+        let config = AuditConfig {
+            path: "-".into(),
+            redact: vec!["opts".into()],
+        };
+        let trace = Arc::new(AuditTrace::new(config, Box::new(buf.clone())));
+        let module: Option<Arc<str>> = Some("main".into());
+        let args = vec![
+            ("url", AuditArg::Str("https://example.com".into())),
+            ("opts", AuditArg::Str("{\"method\":\"GET\"}".into())),
+            ("handle", AuditArg::Num(3)),
+        ];
+        trace
+            .call(module.clone(), "blockless_http.http_req", args)
+            .finish(Some(0));
+        trace.call(module, "path_open", Vec::new()).finish(None);
+
+        let output = String::from_utf8(buf.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = output.lines().map(|l| json::parse(l).unwrap()).collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["module"], "main");
+        assert_eq!(lines[0]["call"], "blockless_http.http_req");
+        assert_eq!(lines[0]["args"]["url"], "https://example.com");
+        assert_eq!(lines[0]["args"]["opts"], AUDIT_REDACTED);
+        assert_eq!(lines[0]["args"]["handle"], 3);
+        assert_eq!(lines[0]["errno"], 0);
+        assert!(lines[0]["duration_us"].is_number());
+        assert!(lines[1]["errno"].is_null());
+    }
+
+    #[test]
+    fn test_audit_redact_all() {
+        let config = AuditConfig {
+            path: "-".into(),
+            redact: vec!["*".into()],
+        };
+        assert!(config.is_redacted("url"));
+        assert!(!AuditConfig::new("-").is_redacted("url"));
+    }
+}
@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+use std::sync::{Arc, RwLock};
+
+use crate::pipe::WritePipe;
+
+/// the default max size of the captured output, 1MB.
+pub const DEFAULT_CAPTURE_MAX_SIZE: usize = 1024 * 1024;
+
+/// the default marker appended to the truncated output.
+pub const DEFAULT_TRUNCATION_MARKER: &str = "\n...[output truncated]";
+
+/// The configure of the output captured in memory.
+#[derive(Debug, Clone)]
+pub struct OutputCapture {
+    /// the max bytes captured, the output exceed the max size is dropped.
+    pub max_size: usize,
+    /// the marker appended to the captured output when the output is truncated.
+    pub truncation_marker: String,
+}
+
+impl OutputCapture {
+    pub fn new(max_size: usize, truncation_marker: impl Into<String>) -> Self {
+        Self {
+            max_size,
+            truncation_marker: truncation_marker.into(),
+        }
+    }
+}
+
+impl Default for OutputCapture {
+    fn default() -> Self {
+        Self::new(DEFAULT_CAPTURE_MAX_SIZE, DEFAULT_TRUNCATION_MARKER)
+    }
+}
+
+/// The output captured by the pipe.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CapturedOutput {
+    /// the output, the truncation marker is appended if the output is truncated.
+    pub data: Vec<u8>,
+    pub truncated: bool,
+}
+
+/// The bounded buffer of the captured output.
+#[derive(Debug)]
+pub struct CaptureBuffer {
+    data: Vec<u8>,
+    capture: OutputCapture,
+    truncated: bool,
+}
+
+impl CaptureBuffer {
+    fn new(capture: OutputCapture) -> Self {
+        Self {
+            data: Vec::new(),
+            capture,
+            truncated: false,
+        }
+    }
+}
+
+impl Write for CaptureBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let remaining = self.capture.max_size.saturating_sub(self.data.len());
+        if buf.len() > remaining {
+            self.truncated = true;
+        }
+        let n = buf.len().min(remaining);
+        self.data.extend_from_slice(&buf[..n]);
+        // the dropped output is treated as written, the app should not fail because of the capture.
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The pipe of the output captured in memory, the pipe can be shared by the embedder
+/// and the runtime, the embedder can read the captured output after the run.
+#[derive(Debug, Clone)]
+pub struct OutputPipe {
+    buffer: Arc<RwLock<CaptureBuffer>>,
+}
+
+impl OutputPipe {
+    pub fn new(capture: OutputCapture) -> Self {
+        Self {
+            buffer: Arc::new(RwLock::new(CaptureBuffer::new(capture))),
+        }
+    }
+
+    /// the preview1 pipe write to the buffer.
+    pub fn write_pipe(&self) -> WritePipe<CaptureBuffer> {
+        WritePipe::from_shared(self.buffer.clone())
+    }
+
+    /// write the output to the buffer.
+    pub fn write(&self, buf: &[u8]) {
+        let _ = self.buffer.write().unwrap().write(buf);
+    }
+
+    /// the snapshot of the captured output.
+    pub fn captured(&self) -> CapturedOutput {
+        let buffer = self.buffer.read().unwrap();
+        let mut data = buffer.data.clone();
+        if buffer.truncated {
+            data.extend_from_slice(buffer.capture.truncation_marker.as_bytes());
+        }
+        CapturedOutput {
+            data,
+            truncated: buffer.truncated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_output_pipe_truncated() {
+        let pipe = OutputPipe::new(OutputCapture::new(5, "..."));
+        pipe.write(b"hel");
+        pipe.write(b"lo world");
+        let captured = pipe.captured();
+        assert!(captured.truncated);
+        assert_eq!(captured.data, b"hello...");
+    }
+
+    #[test]
+    fn test_output_pipe() {
+        let pipe = OutputPipe::new(OutputCapture::new(5, "..."));
+        pipe.write(b"hello");
+        let captured = pipe.captured();
+        assert!(!captured.truncated);
+        assert_eq!(captured.data, b"hello");
+    }
+}
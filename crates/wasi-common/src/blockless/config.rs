@@ -9,7 +9,7 @@ use std::{
 };
 use wasmtime::OptLevel;
 
-use super::set_is_inherit_stdin;
+use super::{AuditConfig, OutputCapture, OutputPipe, set_is_inherit_stdin};
 
 const ENTRY: &str = "_start";
 
@@ -49,6 +49,24 @@ pub enum Stdout {
     Inherit,
     //stdout redirect to file.
     FileName(String),
+    //stdout captured in memory, returned with the run report.
+    Buffer(OutputCapture),
+    //stdout write to the pipe shared with the embedder.
+    Pipe(OutputPipe),
+}
+
+impl Stdout {
+    /// the capture pipe of the stdout, the `Buffer` is replaced with a new pipe,
+    /// so every run has its own buffer.
+    pub fn capture_pipe(&mut self) -> Option<OutputPipe> {
+        if let Stdout::Buffer(capture) = self {
+            *self = Stdout::Pipe(OutputPipe::new(capture.clone()));
+        }
+        match self {
+            Stdout::Pipe(pipe) => Some(pipe.clone()),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -59,6 +77,71 @@ pub enum Stderr {
     Inherit,
     //stderr redirect to file.
     FileName(String),
+    //stderr captured in memory, returned with the run report.
+    Buffer(OutputCapture),
+    //stderr write to the pipe shared with the embedder.
+    Pipe(OutputPipe),
+}
+
+impl Stderr {
+    /// the capture pipe of the stderr, the `Buffer` is replaced with a new pipe,
+    /// so every run has its own buffer.
+    pub fn capture_pipe(&mut self) -> Option<OutputPipe> {
+        if let Stderr::Buffer(capture) = self {
+            *self = Stderr::Pipe(OutputPipe::new(capture.clone()));
+        }
+        match self {
+            Stderr::Pipe(pipe) => Some(pipe.clone()),
+            _ => None,
+        }
+    }
+}
+
+/// The compilation cache setting.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum CacheSetting {
+    /// load the cache configure from the default path of wasmtime.
+    #[default]
+    Default,
+    /// the compilation cache is disabled.
+    Disabled,
+    /// load the cache configure from the wasmtime cache configure file.
+    ConfigFile(String),
+    /// cache in the directory, the size of cache files is capped by `max_size` in bytes.
+    Directory { path: String, max_size: Option<u64> },
+}
+
+/// Invoke the export with the arguments instead of the entry, the arguments
+/// are parsed by the signature of the export.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Invoke {
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl Invoke {
+    pub fn new(name: String, args: Vec<String>) -> Self {
+        Self { name, args }
+    }
+}
+
+/// The deterministic mode, the guest sees the virtual clocks and the seeded random
+/// generator, so the same job produces the same result when it's re-executed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DeterministicConfig {
+    /// the seed of the random generator.
+    pub seed: u64,
+    /// the start time of the virtual wall clock, in milliseconds since the unix epoch.
+    pub start_time: u64,
+    /// the nondeterministic extensions allowed to call, e.g. `http`, `ipfs`.
+    pub allowed_extensions: Vec<String>,
+}
+
+impl DeterministicConfig {
+    #[inline(always)]
+    pub fn is_extension_allowed(&self, name: &str) -> bool {
+        self.allowed_extensions.iter().any(|e| e == name)
+    }
 }
 
 #[derive(Clone)]
@@ -124,6 +207,27 @@ pub struct BlocklessModule {
     pub name: String,
     pub file: String,
     pub md5: String,
+    /// the library modules loaded before the module.
+    pub depends: Vec<String>,
+    /// the sandbox of the library module, `None` shares the context of the entry.
+    pub sandbox: Option<ModuleSandbox>,
+}
+
+/// The sandbox of the library module, the module runs with its own wasi context,
+/// the permissions, envs and dirs of the entry are not visible to the module.
+#[derive(Debug, Clone, Default)]
+pub struct ModuleSandbox {
+    /// the resources the module is allowed to access.
+    pub permissions: Vec<Permission>,
+    /// the file and network rights of the module.
+    pub permissions_config: PermissionsConfig,
+    pub envs: Vec<(String, String)>,
+    /// the host dirs mapped to the guest dirs.
+    pub dirs: Vec<(String, String)>,
+    /// the fuel budget of the module, it needs the `limited_fuel` of the configure.
+    pub limited_fuel: Option<u64>,
+    /// the max memory size in bytes of the module.
+    pub max_memory_size: Option<usize>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -422,6 +526,48 @@ bls_options! {
         /// Enable or disable the use of host signal handlers for traps.
         pub signals_based_traps: Option<bool>,
 
+        /// Enable or disable the SIMD proposal. (default: yes)
+        pub wasm_simd: Option<bool>,
+
+        /// Enable or disable the relaxed SIMD proposal, it requires the SIMD.
+        /// (default: yes)
+        pub wasm_relaxed_simd: Option<bool>,
+
+        /// Enable or disable the tail call proposal. (default: yes)
+        pub wasm_tail_call: Option<bool>,
+
+        /// Enable or disable the GC proposal, it requires the function
+        /// references. (default: no)
+        pub wasm_gc: Option<bool>,
+
+        /// Enable or disable the typed function references proposal. (default: no)
+        pub wasm_function_references: Option<bool>,
+
+        /// Enable or disable the exception handling proposal, it's not supported
+        /// by the runtime yet. (default: no)
+        pub wasm_exceptions: Option<bool>,
+
+        /// Enable or disable the memory64 proposal. (default: no)
+        pub wasm_memory64: Option<bool>,
+
+        /// Enable or disable the multi-memory proposal. (default: yes)
+        pub wasm_multi_memory: Option<bool>,
+
+        /// Enable or disable the threads proposal, it's enabled by
+        /// `--feature-thread`. (default: no)
+        pub wasm_threads: Option<bool>,
+
+        /// Enable or disable the component model. (default: yes)
+        pub wasm_component_model: Option<bool>,
+
+        /// Enable or disable more than 32 flags in the component `flags` type,
+        /// it requires the component model. (default: no)
+        pub wasm_component_model_more_flags: Option<bool>,
+
+        /// Enable or disable multiple return values of the component functions,
+        /// it requires the component model. (default: no)
+        pub wasm_component_model_multiple_returns: Option<bool>,
+
         /// DEPRECATED: Use `-Cmemory-guard-size=N` instead.
         pub dynamic_memory_guard_size: Option<u64>,
 
@@ -439,6 +585,51 @@ bls_options! {
     }
 }
 
+impl OptimizeOpts {
+    /// check the combination of the wasm features, the unsupported combinations are rejected.
+    pub fn validate_features(&self) -> anyhow::Result<()> {
+        let enabled = |opt: Option<bool>| opt == Some(true);
+        let disabled = |opt: Option<bool>| opt == Some(false);
+        if enabled(self.wasm_exceptions) {
+            bail!("the exception handling proposal is not supported.");
+        }
+        if enabled(self.wasm_relaxed_simd) && disabled(self.wasm_simd) {
+            bail!("the relaxed SIMD requires the SIMD.");
+        }
+        if enabled(self.wasm_gc) && disabled(self.wasm_function_references) {
+            bail!("the GC requires the function references.");
+        }
+        if disabled(self.wasm_component_model)
+            && (enabled(self.wasm_component_model_more_flags)
+                || enabled(self.wasm_component_model_multiple_returns))
+        {
+            bail!("the component model features require the component model.");
+        }
+        Ok(())
+    }
+
+    /// the options other than the wasm features are set, they tune the engine
+    /// with the pooling allocator.
+    pub fn has_engine_tuning(&self) -> bool {
+        let tuning = OptimizeOpts {
+            wasm_simd: None,
+            wasm_relaxed_simd: None,
+            wasm_tail_call: None,
+            wasm_gc: None,
+            wasm_function_references: None,
+            wasm_exceptions: None,
+            wasm_memory64: None,
+            wasm_multi_memory: None,
+            wasm_threads: None,
+            wasm_component_model: None,
+            wasm_component_model_more_flags: None,
+            wasm_component_model_multiple_returns: None,
+            ..self.clone()
+        };
+        !tuning.is_empty()
+    }
+}
+
 #[derive(Clone)]
 pub struct Stdio {
     pub stdin: Stdin,
@@ -559,6 +750,8 @@ pub struct BlocklessConfig {
     pub is_carfile: bool,
     pub opts: OptimizeOpts,
     pub feature_thread: bool,
+    /// the maximum number of the threads running at the same time, `None` is unlimited.
+    pub max_threads: Option<u32>,
     pub run_time: Option<u64>,
     pub nn_graph: Vec<BlsNnGraph>,
     pub stdin_args: Vec<String>,
@@ -584,6 +777,12 @@ pub struct BlocklessConfig {
     pub tcp_listens: Vec<(SocketAddr, Option<u32>)>,
     pub group_permisions: HashMap<String, Vec<Permission>>,
     pub permissions_config: PermissionsConfig,
+    pub cache: CacheSetting,
+    pub invoke: Option<Invoke>,
+    /// the file of the guest profile, the profile is written in the Firefox profiler format.
+    pub profile: Option<String>,
+    pub deterministic: Option<DeterministicConfig>,
+    pub audit: Option<AuditConfig>,
 }
 
 impl BlocklessConfig {
@@ -602,6 +801,7 @@ impl BlocklessConfig {
             stdio: Default::default(),
             runtime_logger: None,
             feature_thread: false,
+            max_threads: None,
             //vm instruction limit.
             limited_fuel: None,
             limited_time: None,
@@ -623,9 +823,64 @@ impl BlocklessConfig {
             runtime_logger_level: LoggerLevel::WARN,
             version: BlocklessConfigVersion::Version0,
             permissions_config: Default::default(),
+            cache: Default::default(),
+            invoke: None,
+            profile: None,
+            deterministic: None,
+            audit: None,
         }
     }
 
+    #[inline(always)]
+    pub fn set_cache(&mut self, cache: CacheSetting) {
+        self.cache = cache;
+    }
+
+    #[inline(always)]
+    pub fn cache_ref(&self) -> &CacheSetting {
+        &self.cache
+    }
+
+    #[inline(always)]
+    pub fn set_invoke(&mut self, invoke: Option<Invoke>) {
+        self.invoke = invoke;
+    }
+
+    #[inline(always)]
+    pub fn invoke_ref(&self) -> Option<&Invoke> {
+        self.invoke.as_ref()
+    }
+
+    #[inline(always)]
+    pub fn set_profile(&mut self, profile: Option<String>) {
+        self.profile = profile;
+    }
+
+    #[inline(always)]
+    pub fn profile_ref(&self) -> Option<&str> {
+        self.profile.as_deref()
+    }
+
+    #[inline(always)]
+    pub fn set_deterministic(&mut self, deterministic: Option<DeterministicConfig>) {
+        self.deterministic = deterministic;
+    }
+
+    #[inline(always)]
+    pub fn deterministic_ref(&self) -> Option<&DeterministicConfig> {
+        self.deterministic.as_ref()
+    }
+
+    #[inline(always)]
+    pub fn set_audit(&mut self, audit: Option<AuditConfig>) {
+        self.audit = audit;
+    }
+
+    #[inline(always)]
+    pub fn audit_ref(&self) -> Option<&AuditConfig> {
+        self.audit.as_ref()
+    }
+
     #[inline(always)]
     pub fn version(&self) -> BlocklessConfigVersion {
         self.version
@@ -641,6 +896,16 @@ impl BlocklessConfig {
         self.feature_thread = t;
     }
 
+    #[inline(always)]
+    pub fn max_threads(&self) -> Option<u32> {
+        self.max_threads
+    }
+
+    #[inline(always)]
+    pub fn set_max_threads(&mut self, max_threads: Option<u32>) {
+        self.max_threads = max_threads;
+    }
+
     #[inline(always)]
     pub fn envs_ref(&self) -> &Vec<(String, String)> {
         self.envs.as_ref()
@@ -961,6 +1226,50 @@ mod test {
         assert_eq!(config.entry_ref(), "_start");
     }
 
+    #[test]
+    fn test_optimize_features() {
+        let mut opts = OptimizeOpts::default();
+        opts.config(vec![
+            ("wasm_tail_call".into(), "yes".into()),
+            ("wasm_memory64".into(), "yes".into()),
+        ])
+        .unwrap();
+        assert_eq!(opts.wasm_tail_call, Some(true));
+        assert_eq!(opts.wasm_memory64, Some(true));
+        assert!(opts.validate_features().is_ok());
+        // the wasm features don't tune the engine.
+        assert!(!opts.has_engine_tuning());
+        opts.opt_level = Some(OptLevel::Speed);
+        assert!(opts.has_engine_tuning());
+
+        let opts = OptimizeOpts {
+            wasm_simd: Some(false),
+            wasm_relaxed_simd: Some(true),
+            ..Default::default()
+        };
+        assert!(opts.validate_features().is_err());
+
+        let opts = OptimizeOpts {
+            wasm_gc: Some(true),
+            wasm_function_references: Some(false),
+            ..Default::default()
+        };
+        assert!(opts.validate_features().is_err());
+
+        let opts = OptimizeOpts {
+            wasm_component_model: Some(false),
+            wasm_component_model_more_flags: Some(true),
+            ..Default::default()
+        };
+        assert!(opts.validate_features().is_err());
+
+        let opts = OptimizeOpts {
+            wasm_exceptions: Some(true),
+            ..Default::default()
+        };
+        assert!(opts.validate_features().is_err());
+    }
+
     #[test]
     fn test_version_convert() {
         let _version0: BlocklessConfigVersion = 0.into();
@@ -1,9 +1,13 @@
+mod audit;
+mod capture;
 mod colors;
 mod config;
 mod error;
 mod permission_parser;
 mod permissions;
 mod prompter;
+pub use audit::*;
+pub use capture::*;
 pub use config::*;
 pub use error::*;
 pub use permission_parser::*;
@@ -325,6 +325,35 @@ impl BlsRuntimePermissionsContainer {
         self.inner.query_ffi(path)
     }
 
+    /// query the read permission of the path without the prompt,
+    /// only the granted permission is allowed.
+    #[inline(always)]
+    pub fn is_read_granted(&self, path: &str) -> bool {
+        matches!(self.query_read(Some(path)), Ok(PermissionState::Granted))
+    }
+
+    /// query the write permission of the path without the prompt,
+    /// only the granted permission is allowed.
+    #[inline(always)]
+    pub fn is_write_granted(&self, path: &str) -> bool {
+        matches!(self.query_write(Some(path)), Ok(PermissionState::Granted))
+    }
+
+    /// query the net permission of the host and port without the prompt,
+    /// only the granted permission is allowed. The IPv6 host can be with or without brackets.
+    pub fn is_net_granted(&self, host: &str, port: Option<u16>) -> bool {
+        let host = if host.contains(':') && !host.starts_with('[') {
+            format!("[{host}]")
+        } else {
+            host.to_string()
+        };
+        let host = match port {
+            Some(port) => format!("{host}:{port}"),
+            None => host,
+        };
+        matches!(self.query_net(Some(&host)), Ok(PermissionState::Granted))
+    }
+
     // revoke
 
     #[inline(always)]
@@ -4,7 +4,9 @@ use crate::file::{FileAccessMode, FileEntry, WasiFile};
 use crate::sched::WasiSched;
 use crate::string_array::StringArray;
 use crate::table::Table;
-use crate::{BlocklessConfig, BlsRuntimePermissionsContainer, PermissionsConfig};
+use crate::{
+    AuditTrace, BlocklessConfig, BlsRuntimePermissionsContainer, HostCallAudit, PermissionsConfig,
+};
 use crate::{Error, StringArrayError};
 use bls_permissions::Url;
 use cap_rand::RngCore;
@@ -17,8 +19,10 @@ use std::sync::{Arc, Mutex};
 /// signature of `fd_fdstat_set_flags`; if that changes, there are a variety of
 /// improvements that can be made (TODO:
 /// <https://github.com/bytecodealliance/wasmtime/issues/5643)>.
+///
+/// The module calling the host is not shared, the host sets it for every store.
 #[derive(Clone)]
-pub struct WasiCtx(Arc<WasiCtxInner>);
+pub struct WasiCtx(Arc<WasiCtxInner>, Option<Arc<str>>);
 
 pub struct WasiCtxInner {
     pub args: StringArray,
@@ -32,6 +36,7 @@ pub struct WasiCtxInner {
     pub table: Table,
     pub perms_container: BlsRuntimePermissionsContainer,
     pub blockless_config: Mutex<Option<BlocklessConfig>>,
+    pub audit_trace: Mutex<Option<Arc<AuditTrace>>>,
 }
 
 impl WasiCtx {
@@ -42,27 +47,62 @@ impl WasiCtx {
         table: Table,
         cwd: Option<&str>,
     ) -> Self {
-        let s = WasiCtx(Arc::new(WasiCtxInner {
-            args: StringArray::new(),
-            env: StringArray::new(),
-            random: Mutex::new(random),
-            blockless_config: Mutex::new(None),
-            perms_container: BlsRuntimePermissionsContainer::new_with_env_cwd(cwd),
-            clocks,
-            sched,
-            table,
-        }));
+        let s = WasiCtx(
+            Arc::new(WasiCtxInner {
+                args: StringArray::new(),
+                env: StringArray::new(),
+                random: Mutex::new(random),
+                blockless_config: Mutex::new(None),
+                audit_trace: Mutex::new(None),
+                perms_container: BlsRuntimePermissionsContainer::new_with_env_cwd(cwd),
+                clocks,
+                sched,
+                table,
+            }),
+            None,
+        );
         s.set_stdin(Box::new(crate::pipe::ReadPipe::new(std::io::empty())));
         s.set_stdout(Box::new(crate::pipe::WritePipe::new(std::io::sink())));
         s.set_stderr(Box::new(crate::pipe::WritePipe::new(std::io::sink())));
         s
     }
 
+    /// replace the random generator, e.g. the seeded random generator of the deterministic mode.
+    pub fn set_random(&mut self, random: Box<dyn RngCore + Send + Sync>) {
+        *self.0.random.lock().unwrap() = random;
+    }
+
+    /// replace the clocks, the ctx must not be shared.
+    pub fn set_clocks(&mut self, clocks: WasiClocks) {
+        Arc::get_mut(&mut self.0)
+            .expect("the clocks can't be set after the ctx is shared")
+            .clocks = clocks;
+    }
+
+    /// share the stdin, stdout and stderr of the other ctx.
+    pub fn share_stdio(&self, other: &WasiCtx) {
+        for fd in 0..3 {
+            if let Ok(entry) = other.table().get::<FileEntry>(fd) {
+                self.table().insert_at(fd, entry);
+            }
+        }
+    }
+
     pub fn set_blockless_config(&mut self, c: Option<BlocklessConfig>) {
         let mut lock = self.0.blockless_config.lock().unwrap();
         c.map(|c| lock.replace(c));
     }
 
+    /// set the audit trace, the host calls are recorded by the trace.
+    pub fn set_audit_trace(&self, trace: Option<Arc<AuditTrace>>) {
+        *self.0.audit_trace.lock().unwrap() = trace;
+    }
+
+    /// set the module calling the host, the host calls are recorded with the module name.
+    pub fn set_audit_module(&mut self, module: Option<Arc<str>>) {
+        self.1 = module;
+    }
+
     pub fn config_drivers_root_path_ref(&mut self) -> Option<String> {
         let lock = self.0.blockless_config.lock().unwrap();
         lock.as_ref()
@@ -165,6 +205,16 @@ impl WasiCtx {
     }
 }
 
+impl HostCallAudit for WasiCtx {
+    fn audit_trace(&self) -> Option<Arc<AuditTrace>> {
+        self.0.audit_trace.lock().unwrap().clone()
+    }
+
+    fn audit_module(&self) -> Option<Arc<str>> {
+        self.1.clone()
+    }
+}
+
 impl Deref for WasiCtx {
     type Target = WasiCtxInner;
     fn deref(&self) -> &Self::Target {
@@ -8,7 +8,7 @@ use crate::{
         subscription::{RwEventFlags, SubscriptionResult},
         Poll, Userdata,
     },
-    I32Exit, SystemTimeSpec, WasiCtx,
+    AuditArg, AuditCall, HostCallAudit, I32Exit, SystemTimeSpec, WasiCtx,
 };
 use cap_std::time::{Duration, SystemClock};
 use std::io::{IoSlice, IoSliceMut};
@@ -41,6 +41,56 @@ impl wiggle::GuestErrorType for types::Errno {
     }
 }
 
+impl WasiCtx {
+    /// start the audit of the permission-checked call on the fd, only the files
+    /// opened by path and the preopened dirs are checked.
+    fn audit_fd_call(&self, name: &'static str, fd: types::Fd) -> Option<AuditCall> {
+        let trace = self.audit_trace()?;
+        let fd = u32::from(fd);
+        let table = self.table();
+        let path = match table.get_file(fd) {
+            Ok(f) => f.name.clone(),
+            Err(_) => table.get_dir(fd).ok().and_then(|d| {
+                d.preopen_path()
+                    .as_ref()
+                    .map(|p| p.to_string_lossy().into_owned())
+            }),
+        }?;
+        let args = vec![
+            ("fd", AuditArg::Num(fd as i64)),
+            ("path", AuditArg::Str(path)),
+        ];
+        Some(trace.call(self.audit_module(), name, args))
+    }
+
+    /// start the audit of the permission-checked call on the path.
+    fn audit_path_call(
+        &self,
+        memory: &GuestMemory<'_>,
+        name: &'static str,
+        dirfd: types::Fd,
+        path: GuestPtr<str>,
+    ) -> Option<AuditCall> {
+        let trace = self.audit_trace()?;
+        let args = vec![
+            ("dirfd", AuditArg::Num(u32::from(dirfd) as i64)),
+            ("path", AuditArg::guest_str(memory, path)),
+        ];
+        Some(trace.call(self.audit_module(), name, args))
+    }
+}
+
+/// finish the audit with the errno of the result, the trap has no errno.
+fn audit_finish<T>(audit: Option<AuditCall>, result: &Result<T, Error>) {
+    if let Some(audit) = audit {
+        let errno = match result {
+            Ok(_) => Some(0),
+            Err(e) => e.downcast_ref().map(|errno: &types::Errno| *errno as i32),
+        };
+        audit.finish(errno);
+    }
+}
+
 #[wiggle::async_trait]
 impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
     async fn args_get(
@@ -255,32 +305,38 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
 
     async fn fd_filestat_get(
         &mut self,
-        _memory: &mut GuestMemory<'_>,
+        memory: &mut GuestMemory<'_>,
         fd: types::Fd,
     ) -> Result<types::Filestat, Error> {
-        let table = self.table();
-        let fd = u32::from(fd);
-        if table.is::<FileEntry>(fd) {
-            let file_entry = table.get_file(fd)?;
-            if let Some(name) = file_entry.name.as_ref() {
-                self.perms_container
-                    .check_read(name, "fd_filestat_get")
-                    .map_err(|_| Error::perm())?;
-            }
-            let filestat = file_entry.file.get_filestat().await?;
-            Ok(filestat.into())
-        } else if table.is::<DirEntry>(fd) {
-            let dir_entry = table.get_dir(fd)?;
-            if let Some(name) = dir_entry.preopen_path() {
-                self.perms_container
-                    .check_read(&name.to_string_lossy(), "fd_filestat_get")
-                    .map_err(|_| Error::perm())?;
+        let audit = self.audit_fd_call("fd_filestat_get", fd);
+        let result: Result<types::Filestat, Error> = async {
+            let table = self.table();
+            let fd = u32::from(fd);
+            if table.is::<FileEntry>(fd) {
+                let file_entry = table.get_file(fd)?;
+                if let Some(name) = file_entry.name.as_ref() {
+                    self.perms_container
+                        .check_read(name, "fd_filestat_get")
+                        .map_err(|_| Error::perm())?;
+                }
+                let filestat = file_entry.file.get_filestat().await?;
+                Ok(filestat.into())
+            } else if table.is::<DirEntry>(fd) {
+                let dir_entry = table.get_dir(fd)?;
+                if let Some(name) = dir_entry.preopen_path() {
+                    self.perms_container
+                        .check_read(&name.to_string_lossy(), "fd_filestat_get")
+                        .map_err(|_| Error::perm())?;
+                }
+                let filestat = dir_entry.dir.get_filestat().await?;
+                Ok(filestat.into())
+            } else {
+                Err(Error::badf())
             }
-            let filestat = dir_entry.dir.get_filestat().await?;
-            Ok(filestat.into())
-        } else {
-            Err(Error::badf())
         }
+        .await;
+        audit_finish(audit, &result);
+        result
     }
 
     async fn fd_filestat_set_size(
@@ -341,73 +397,80 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         fd: types::Fd,
         iovs: types::IovecArray,
     ) -> Result<types::Size, Error> {
-        let f = self.table().get_file(u32::from(fd))?;
-        if let Some(name) = f.name.as_ref() {
-            self.perms_container
-                .check_read(&name, "fd_read")
-                .map_err(|_| Error::perm())?;
-        }
-        // Access mode check normalizes error returned (windows would prefer ACCES here)
-        if !f.access_mode.contains(FileAccessMode::READ) {
-            Err(types::Errno::Badf)?
-        }
-        let f = &f.file;
-
-        let iovs: Vec<wiggle::GuestPtr<[u8]>> = iovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Iovec = memory.read(iov_ptr)?;
-                Ok(iov.buf.as_array(iov.buf_len))
-            })
-            .collect::<Result<_, Error>>()?;
-
-        // If the first iov structure is from shared memory we can safely assume
-        // all the rest will be. We then read into memory based on the memory's
-        // shared-ness:
-        // - if not shared, we copy directly into the Wasm memory
-        // - if shared, we use an intermediate buffer; this avoids Rust unsafety
-        //   due to holding on to a `&mut [u8]` of Wasm memory when we cannot
-        //   guarantee the `&mut` exclusivity--other threads could be modifying
-        //   the data as this functions writes to it. Though likely there is no
-        //   issue with OS writing to io structs in multi-threaded scenarios,
-        //   since we do not know here if `&dyn WasiFile` does anything else
-        //   (e.g., read), we cautiously incur some performance overhead by
-        //   copying twice.
-        let is_shared_memory = memory.is_shared_memory();
-        let bytes_read: u64 = if is_shared_memory {
-            // For shared memory, read into an intermediate buffer. Only the
-            // first iov will be filled and even then the read is capped by the
-            // `MAX_SHARED_BUFFER_SIZE`, so users are expected to re-call.
-            let iov = iovs.into_iter().next();
-            if let Some(iov) = iov {
-                let mut buffer = vec![0; (iov.len() as usize).min(MAX_SHARED_BUFFER_SIZE)];
-                let bytes_read = f.read_vectored(&mut [IoSliceMut::new(&mut buffer)]).await?;
-                let iov = iov
-                    .get_range(0..bytes_read.try_into()?)
-                    .expect("it should always be possible to slice the iov smaller");
-                memory.copy_from_slice(&buffer[0..bytes_read.try_into()?], iov)?;
-                bytes_read
-            } else {
-                return Ok(0);
+        let audit = self.audit_fd_call("fd_read", fd);
+        let result: Result<types::Size, Error> = async {
+            let f = self.table().get_file(u32::from(fd))?;
+            if let Some(name) = f.name.as_ref() {
+                self.perms_container
+                    .check_read(&name, "fd_read")
+                    .map_err(|_| Error::perm())?;
             }
-        } else {
-            // Convert the first unsafe guest slice into a safe one--Wiggle
-            // can only track mutable borrows for an entire region, and converting
-            // all guest pointers to slices would cause a runtime borrow-checking
-            // error. As read is allowed to return less than the requested amount,
-            // it's valid (though not as efficient) for us to only perform the
-            // read of the first buffer.
-            let guest_slice: &mut [u8] = match iovs.into_iter().filter(|iov| iov.len() > 0).next() {
-                Some(iov) => memory.as_slice_mut(iov)?.unwrap(),
-                None => return Ok(0),
+            // Access mode check normalizes error returned (windows would prefer ACCES here)
+            if !f.access_mode.contains(FileAccessMode::READ) {
+                Err(types::Errno::Badf)?
+            }
+            let f = &f.file;
+
+            let iovs: Vec<wiggle::GuestPtr<[u8]>> = iovs
+                .iter()
+                .map(|iov_ptr| {
+                    let iov_ptr = iov_ptr?;
+                    let iov: types::Iovec = memory.read(iov_ptr)?;
+                    Ok(iov.buf.as_array(iov.buf_len))
+                })
+                .collect::<Result<_, Error>>()?;
+
+            // If the first iov structure is from shared memory we can safely assume
+            // all the rest will be. We then read into memory based on the memory's
+            // shared-ness:
+            // - if not shared, we copy directly into the Wasm memory
+            // - if shared, we use an intermediate buffer; this avoids Rust unsafety
+            //   due to holding on to a `&mut [u8]` of Wasm memory when we cannot
+            //   guarantee the `&mut` exclusivity--other threads could be modifying
+            //   the data as this functions writes to it. Though likely there is no
+            //   issue with OS writing to io structs in multi-threaded scenarios,
+            //   since we do not know here if `&dyn WasiFile` does anything else
+            //   (e.g., read), we cautiously incur some performance overhead by
+            //   copying twice.
+            let is_shared_memory = memory.is_shared_memory();
+            let bytes_read: u64 = if is_shared_memory {
+                // For shared memory, read into an intermediate buffer. Only the
+                // first iov will be filled and even then the read is capped by the
+                // `MAX_SHARED_BUFFER_SIZE`, so users are expected to re-call.
+                let iov = iovs.into_iter().next();
+                if let Some(iov) = iov {
+                    let mut buffer = vec![0; (iov.len() as usize).min(MAX_SHARED_BUFFER_SIZE)];
+                    let bytes_read = f.read_vectored(&mut [IoSliceMut::new(&mut buffer)]).await?;
+                    let iov = iov
+                        .get_range(0..bytes_read.try_into()?)
+                        .expect("it should always be possible to slice the iov smaller");
+                    memory.copy_from_slice(&buffer[0..bytes_read.try_into()?], iov)?;
+                    bytes_read
+                } else {
+                    return Ok(0);
+                }
+            } else {
+                // Convert the first unsafe guest slice into a safe one--Wiggle
+                // can only track mutable borrows for an entire region, and converting
+                // all guest pointers to slices would cause a runtime borrow-checking
+                // error. As read is allowed to return less than the requested amount,
+                // it's valid (though not as efficient) for us to only perform the
+                // read of the first buffer.
+                let guest_slice: &mut [u8] =
+                    match iovs.into_iter().filter(|iov| iov.len() > 0).next() {
+                        Some(iov) => memory.as_slice_mut(iov)?.unwrap(),
+                        None => return Ok(0),
+                    };
+
+                // Read directly into the Wasm memory.
+                f.read_vectored(&mut [IoSliceMut::new(guest_slice)]).await?
             };
 
-            // Read directly into the Wasm memory.
-            f.read_vectored(&mut [IoSliceMut::new(guest_slice)]).await?
-        };
-
-        Ok(types::Size::try_from(bytes_read)?)
+            Ok(types::Size::try_from(bytes_read)?)
+        }
+        .await;
+        audit_finish(audit, &result);
+        result
     }
 
     async fn fd_pread(
@@ -417,71 +480,78 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         iovs: types::IovecArray,
         offset: types::Filesize,
     ) -> Result<types::Size, Error> {
-        let f = self.table().get_file(u32::from(fd))?;
-        if let Some(name) = f.name.as_ref() {
-            self.perms_container
-                .check_read(&name, "fd_pread")
-                .map_err(|_| Error::perm())?;
-        }
-        // Access mode check normalizes error returned (windows would prefer ACCES here)
-        if !f.access_mode.contains(FileAccessMode::READ) {
-            Err(types::Errno::Badf)?
-        }
-        let f = &f.file;
-
-        let iovs: Vec<wiggle::GuestPtr<[u8]>> = iovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Iovec = memory.read(iov_ptr)?;
-                Ok(iov.buf.as_array(iov.buf_len))
-            })
-            .collect::<Result<_, Error>>()?;
-
-        // If the first iov structure is from shared memory we can safely assume
-        // all the rest will be. We then read into memory based on the memory's
-        // shared-ness:
-        // - if not shared, we copy directly into the Wasm memory
-        // - if shared, we use an intermediate buffer; this avoids Rust unsafety
-        //   due to holding on to a `&mut [u8]` of Wasm memory when we cannot
-        //   guarantee the `&mut` exclusivity--other threads could be modifying
-        //   the data as this functions writes to it. Though likely there is no
-        //   issue with OS writing to io structs in multi-threaded scenarios,
-        //   since we do not know here if `&dyn WasiFile` does anything else
-        //   (e.g., read), we cautiously incur some performance overhead by
-        //   copying twice.
-        let is_shared_memory = memory.is_shared_memory();
-        let bytes_read: u64 = if is_shared_memory {
-            // For shared memory, read into an intermediate buffer. Only the
-            // first iov will be filled and even then the read is capped by the
-            // `MAX_SHARED_BUFFER_SIZE`, so users are expected to re-call.
-            let iov = iovs.into_iter().next();
-            if let Some(iov) = iov {
-                let mut buffer = vec![0; (iov.len() as usize).min(MAX_SHARED_BUFFER_SIZE)];
-                let bytes_read = f
-                    .read_vectored_at(&mut [IoSliceMut::new(&mut buffer)], offset)
-                    .await?;
-                let iov = iov
-                    .get_range(0..bytes_read.try_into()?)
-                    .expect("it should always be possible to slice the iov smaller");
-                memory.copy_from_slice(&buffer[0..bytes_read.try_into()?], iov)?;
-                bytes_read
-            } else {
-                return Ok(0);
+        let audit = self.audit_fd_call("fd_pread", fd);
+        let result: Result<types::Size, Error> = async {
+            let f = self.table().get_file(u32::from(fd))?;
+            if let Some(name) = f.name.as_ref() {
+                self.perms_container
+                    .check_read(&name, "fd_pread")
+                    .map_err(|_| Error::perm())?;
             }
-        } else {
-            // Convert unsafe guest slices to safe ones.
-            let guest_slice: &mut [u8] = match iovs.into_iter().filter(|iov| iov.len() > 0).next() {
-                Some(iov) => memory.as_slice_mut(iov)?.unwrap(),
-                None => return Ok(0),
+            // Access mode check normalizes error returned (windows would prefer ACCES here)
+            if !f.access_mode.contains(FileAccessMode::READ) {
+                Err(types::Errno::Badf)?
+            }
+            let f = &f.file;
+
+            let iovs: Vec<wiggle::GuestPtr<[u8]>> = iovs
+                .iter()
+                .map(|iov_ptr| {
+                    let iov_ptr = iov_ptr?;
+                    let iov: types::Iovec = memory.read(iov_ptr)?;
+                    Ok(iov.buf.as_array(iov.buf_len))
+                })
+                .collect::<Result<_, Error>>()?;
+
+            // If the first iov structure is from shared memory we can safely assume
+            // all the rest will be. We then read into memory based on the memory's
+            // shared-ness:
+            // - if not shared, we copy directly into the Wasm memory
+            // - if shared, we use an intermediate buffer; this avoids Rust unsafety
+            //   due to holding on to a `&mut [u8]` of Wasm memory when we cannot
+            //   guarantee the `&mut` exclusivity--other threads could be modifying
+            //   the data as this functions writes to it. Though likely there is no
+            //   issue with OS writing to io structs in multi-threaded scenarios,
+            //   since we do not know here if `&dyn WasiFile` does anything else
+            //   (e.g., read), we cautiously incur some performance overhead by
+            //   copying twice.
+            let is_shared_memory = memory.is_shared_memory();
+            let bytes_read: u64 = if is_shared_memory {
+                // For shared memory, read into an intermediate buffer. Only the
+                // first iov will be filled and even then the read is capped by the
+                // `MAX_SHARED_BUFFER_SIZE`, so users are expected to re-call.
+                let iov = iovs.into_iter().next();
+                if let Some(iov) = iov {
+                    let mut buffer = vec![0; (iov.len() as usize).min(MAX_SHARED_BUFFER_SIZE)];
+                    let bytes_read = f
+                        .read_vectored_at(&mut [IoSliceMut::new(&mut buffer)], offset)
+                        .await?;
+                    let iov = iov
+                        .get_range(0..bytes_read.try_into()?)
+                        .expect("it should always be possible to slice the iov smaller");
+                    memory.copy_from_slice(&buffer[0..bytes_read.try_into()?], iov)?;
+                    bytes_read
+                } else {
+                    return Ok(0);
+                }
+            } else {
+                // Convert unsafe guest slices to safe ones.
+                let guest_slice: &mut [u8] =
+                    match iovs.into_iter().filter(|iov| iov.len() > 0).next() {
+                        Some(iov) => memory.as_slice_mut(iov)?.unwrap(),
+                        None => return Ok(0),
+                    };
+
+                // Read directly into the Wasm memory.
+                f.read_vectored_at(&mut [IoSliceMut::new(guest_slice)], offset)
+                    .await?
             };
 
-            // Read directly into the Wasm memory.
-            f.read_vectored_at(&mut [IoSliceMut::new(guest_slice)], offset)
-                .await?
-        };
-
-        Ok(types::Size::try_from(bytes_read)?)
+            Ok(types::Size::try_from(bytes_read)?)
+        }
+        .await;
+        audit_finish(audit, &result);
+        result
     }
 
     async fn fd_write(
@@ -490,34 +560,40 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         fd: types::Fd,
         ciovs: types::CiovecArray,
     ) -> Result<types::Size, Error> {
-        let f = self.table().get_file(u32::from(fd))?;
-        if let Some(name) = f.name.as_ref() {
-            self.perms_container
-                .check_write(&name, "fd_write")
-                .map_err(|_| Error::perm())?;
-        }
-        // Access mode check normalizes error returned (windows would prefer ACCES here)
-        if !f.access_mode.contains(FileAccessMode::WRITE) {
-            Err(types::Errno::Badf)?
+        let audit = self.audit_fd_call("fd_write", fd);
+        let result: Result<types::Size, Error> = async {
+            let f = self.table().get_file(u32::from(fd))?;
+            if let Some(name) = f.name.as_ref() {
+                self.perms_container
+                    .check_write(&name, "fd_write")
+                    .map_err(|_| Error::perm())?;
+            }
+            // Access mode check normalizes error returned (windows would prefer ACCES here)
+            if !f.access_mode.contains(FileAccessMode::WRITE) {
+                Err(types::Errno::Badf)?
+            }
+            let f = &f.file;
+
+            let guest_slices: Vec<Cow<[u8]>> = ciovs
+                .iter()
+                .map(|iov_ptr| {
+                    let iov_ptr = iov_ptr?;
+                    let iov: types::Ciovec = memory.read(iov_ptr)?;
+                    Ok(memory.as_cow(iov.buf.as_array(iov.buf_len))?)
+                })
+                .collect::<Result<_, Error>>()?;
+
+            let ioslices: Vec<IoSlice> = guest_slices
+                .iter()
+                .map(|s| IoSlice::new(s.deref()))
+                .collect();
+            let bytes_written = f.write_vectored(&ioslices).await?;
+
+            Ok(types::Size::try_from(bytes_written)?)
         }
-        let f = &f.file;
-
-        let guest_slices: Vec<Cow<[u8]>> = ciovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Ciovec = memory.read(iov_ptr)?;
-                Ok(memory.as_cow(iov.buf.as_array(iov.buf_len))?)
-            })
-            .collect::<Result<_, Error>>()?;
-
-        let ioslices: Vec<IoSlice> = guest_slices
-            .iter()
-            .map(|s| IoSlice::new(s.deref()))
-            .collect();
-        let bytes_written = f.write_vectored(&ioslices).await?;
-
-        Ok(types::Size::try_from(bytes_written)?)
+        .await;
+        audit_finish(audit, &result);
+        result
     }
 
     async fn fd_pwrite(
@@ -527,34 +603,40 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         ciovs: types::CiovecArray,
         offset: types::Filesize,
     ) -> Result<types::Size, Error> {
-        let f = self.table().get_file(u32::from(fd))?;
-        if let Some(name) = f.name.as_ref() {
-            self.perms_container
-                .check_write(&name, "fd_pwrite")
-                .map_err(|_| Error::perm())?;
-        }
-        // Access mode check normalizes error returned (windows would prefer ACCES here)
-        if !f.access_mode.contains(FileAccessMode::WRITE) {
-            Err(types::Errno::Badf)?
+        let audit = self.audit_fd_call("fd_pwrite", fd);
+        let result: Result<types::Size, Error> = async {
+            let f = self.table().get_file(u32::from(fd))?;
+            if let Some(name) = f.name.as_ref() {
+                self.perms_container
+                    .check_write(&name, "fd_pwrite")
+                    .map_err(|_| Error::perm())?;
+            }
+            // Access mode check normalizes error returned (windows would prefer ACCES here)
+            if !f.access_mode.contains(FileAccessMode::WRITE) {
+                Err(types::Errno::Badf)?
+            }
+            let f = &f.file;
+
+            let guest_slices: Vec<Cow<[u8]>> = ciovs
+                .iter()
+                .map(|iov_ptr| {
+                    let iov_ptr = iov_ptr?;
+                    let iov: types::Ciovec = memory.read(iov_ptr)?;
+                    Ok(memory.as_cow(iov.buf.as_array(iov.buf_len))?)
+                })
+                .collect::<Result<_, Error>>()?;
+
+            let ioslices: Vec<IoSlice> = guest_slices
+                .iter()
+                .map(|s| IoSlice::new(s.deref()))
+                .collect();
+            let bytes_written = f.write_vectored_at(&ioslices, offset).await?;
+
+            Ok(types::Size::try_from(bytes_written)?)
         }
-        let f = &f.file;
-
-        let guest_slices: Vec<Cow<[u8]>> = ciovs
-            .iter()
-            .map(|iov_ptr| {
-                let iov_ptr = iov_ptr?;
-                let iov: types::Ciovec = memory.read(iov_ptr)?;
-                Ok(memory.as_cow(iov.buf.as_array(iov.buf_len))?)
-            })
-            .collect::<Result<_, Error>>()?;
-
-        let ioslices: Vec<IoSlice> = guest_slices
-            .iter()
-            .map(|s| IoSlice::new(s.deref()))
-            .collect();
-        let bytes_written = f.write_vectored_at(&ioslices, offset).await?;
-
-        Ok(types::Size::try_from(bytes_written)?)
+        .await;
+        audit_finish(audit, &result);
+        result
     }
 
     async fn fd_prestat_get(
@@ -730,23 +812,29 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         flags: types::Lookupflags,
         path: GuestPtr<str>,
     ) -> Result<types::Filestat, Error> {
-        let dir_entry = self.table().get_dir(u32::from(dirfd))?;
-        let filen = memory.as_cow_str(path)?;
-        if let Some(dir) = dir_entry.preopen_path() {
-            let full_path =
-                dir.join(PathBuf::from_str(&filen).map_err(|_| Error::invalid_argument())?);
-            self.perms_container
-                .check_read(&full_path.to_string_lossy(), "path_filestat_get")
-                .map_err(|_| Error::perm())?;
+        let audit = self.audit_path_call(memory, "path_filestat_get", dirfd, path);
+        let result: Result<types::Filestat, Error> = async {
+            let dir_entry = self.table().get_dir(u32::from(dirfd))?;
+            let filen = memory.as_cow_str(path)?;
+            if let Some(dir) = dir_entry.preopen_path() {
+                let full_path =
+                    dir.join(PathBuf::from_str(&filen).map_err(|_| Error::invalid_argument())?);
+                self.perms_container
+                    .check_read(&full_path.to_string_lossy(), "path_filestat_get")
+                    .map_err(|_| Error::perm())?;
+            }
+            let filestat = dir_entry
+                .dir
+                .get_path_filestat(
+                    memory.as_cow_str(path)?.deref(),
+                    flags.contains(types::Lookupflags::SYMLINK_FOLLOW),
+                )
+                .await?;
+            Ok(types::Filestat::from(filestat))
         }
-        let filestat = dir_entry
-            .dir
-            .get_path_filestat(
-                memory.as_cow_str(path)?.deref(),
-                flags.contains(types::Lookupflags::SYMLINK_FOLLOW),
-            )
-            .await?;
-        Ok(types::Filestat::from(filestat))
+        .await;
+        audit_finish(audit, &result);
+        result
     }
 
     async fn path_filestat_set_times(
@@ -817,56 +905,64 @@ impl wasi_snapshot_preview1::WasiSnapshotPreview1 for WasiCtx {
         _fs_rights_inheriting: types::Rights,
         fdflags: types::Fdflags,
     ) -> Result<types::Fd, Error> {
-        let table = self.table();
-        let dirfd = u32::from(dirfd);
-        if table.is::<FileEntry>(dirfd) {
-            return Err(Error::not_dir());
-        }
-        let dir_entry = table.get_dir(dirfd)?;
-
-        let symlink_follow = dirflags.contains(types::Lookupflags::SYMLINK_FOLLOW);
-
-        let oflags = OFlags::from(&oflags);
-        let fdflags = FdFlags::from(fdflags);
-        let path = memory.as_cow_str(path)?;
-
-        //permission check.
-        let full_path = dir_entry
-            .preopen_path()
-            .as_ref()
-            .map(|dir| PathBuf::from(dir.join(path.as_ref())));
-        if let Some(ref full_path) = full_path {
-            self.perms_container
-                .check_read(&full_path.to_string_lossy(), "path_open")
-                .map_err(|_| Error::perm())?;
-        }
+        let audit = self.audit_path_call(memory, "path_open", dirfd, path);
+        let result: Result<types::Fd, Error> = async {
+            let table = self.table();
+            let dirfd = u32::from(dirfd);
+            if table.is::<FileEntry>(dirfd) {
+                return Err(Error::not_dir());
+            }
+            let dir_entry = table.get_dir(dirfd)?;
 
-        let read = fs_rights_base.contains(types::Rights::FD_READ);
-        let write = fs_rights_base.contains(types::Rights::FD_WRITE);
-        let access_mode = if read {
-            FileAccessMode::READ
-        } else {
-            FileAccessMode::empty()
-        } | if write {
-            FileAccessMode::WRITE
-        } else {
-            FileAccessMode::empty()
-        };
-        let file = dir_entry
-            .dir
-            .open_file(symlink_follow, path.deref(), oflags, read, write, fdflags)
-            .await?;
-        drop(dir_entry);
+            let symlink_follow = dirflags.contains(types::Lookupflags::SYMLINK_FOLLOW);
+
+            let oflags = OFlags::from(&oflags);
+            let fdflags = FdFlags::from(fdflags);
+            let path = memory.as_cow_str(path)?;
 
-        let fd = match file {
-            OpenResult::File(file) => {
-                let mut file_entry = FileEntry::new(file, access_mode);
-                file_entry.set_name(full_path.map(|p| p.to_string_lossy().to_string()));
-                table.push(Arc::new(file_entry))?
+            //permission check.
+            let full_path = dir_entry
+                .preopen_path()
+                .as_ref()
+                .map(|dir| PathBuf::from(dir.join(path.as_ref())));
+            if let Some(ref full_path) = full_path {
+                self.perms_container
+                    .check_read(&full_path.to_string_lossy(), "path_open")
+                    .map_err(|_| Error::perm())?;
             }
-            OpenResult::Dir(child_dir) => table.push(Arc::new(DirEntry::new(None, child_dir)))?,
-        };
-        Ok(types::Fd::from(fd))
+
+            let read = fs_rights_base.contains(types::Rights::FD_READ);
+            let write = fs_rights_base.contains(types::Rights::FD_WRITE);
+            let access_mode = if read {
+                FileAccessMode::READ
+            } else {
+                FileAccessMode::empty()
+            } | if write {
+                FileAccessMode::WRITE
+            } else {
+                FileAccessMode::empty()
+            };
+            let file = dir_entry
+                .dir
+                .open_file(symlink_follow, path.deref(), oflags, read, write, fdflags)
+                .await?;
+            drop(dir_entry);
+
+            let fd = match file {
+                OpenResult::File(file) => {
+                    let mut file_entry = FileEntry::new(file, access_mode);
+                    file_entry.set_name(full_path.map(|p| p.to_string_lossy().to_string()));
+                    table.push(Arc::new(file_entry))?
+                }
+                OpenResult::Dir(child_dir) => {
+                    table.push(Arc::new(DirEntry::new(None, child_dir)))?
+                }
+            };
+            Ok(types::Fd::from(fd))
+        }
+        .await;
+        audit_finish(audit, &result);
+        result
     }
 
     async fn path_readlink(
@@ -140,6 +140,14 @@ impl WasiCtxBuilder {
             .insert_file(fd, file, FileAccessMode::READ | FileAccessMode::WRITE);
         Ok(self)
     }
+    pub fn random(&mut self, random: Box<dyn RngCore + Send + Sync>) -> &mut Self {
+        self.ctx.set_random(random);
+        self
+    }
+    pub fn clocks(&mut self, clocks: crate::clocks::WasiClocks) -> &mut Self {
+        self.ctx.set_clocks(clocks);
+        self
+    }
     pub fn build(&mut self) -> WasiCtx {
         assert!(!self.built);
         let WasiCtxBuilder { ctx, .. } = mem::replace(self, Self::new());